@@ -2,12 +2,21 @@
   // src/gui-sdk/index.ts
   var version = "0.3.0";
   var config = null;
+  var liveReload = null;
+  var spaNavigation = false;
+  var autoCapture = false;
+  var eventQueue = [];
+  var flushTimer = null;
+  var unloadFlush = false;
+  var MAX_BATCH = 100;
   async function init(opts = {}) {
     config = {
       tenantDomain: opts.tenantDomain || window.location.host,
-      configUrl: opts.configUrl || "/api/gui/config",
-      eventsUrl: opts.eventsUrl || "/api/gui/events",
-      workerMessageUrl: opts.workerMessageUrl || "/api/gui/worker/message"
+      configUrl: opts.configUrl || serverUrl("/api/gui/config"),
+      eventsUrl: opts.eventsUrl || serverUrl("/api/gui/events"),
+      workerMessageUrl: opts.workerMessageUrl || serverUrl("/api/gui/worker/message"),
+      batchSize: opts.batchSize || 20,
+      flushIntervalMs: opts.flushIntervalMs || 5e3
     };
     try {
       const res = await fetch(config.configUrl);
@@ -15,8 +24,37 @@
     } catch (err) {
       console.warn("GreenticGUI: failed to load GUI config", err);
     }
+    startLiveReload(config.guiConfig?.live_reload);
+    startSpaNavigation();
+    startAutoCapture(config.guiConfig?.telemetry);
+    startUnloadFlush();
     return config;
   }
+  function startLiveReload(url) {
+    if (!url || liveReload || typeof EventSource === "undefined") return;
+    liveReload = new EventSource(url);
+    liveReload.addEventListener("pack-change", (evt) => {
+      let change;
+      try {
+        change = JSON.parse(evt.data);
+      } catch (err) {
+        return;
+      }
+      if (change.reload === "css") {
+        swapStylesheets();
+      } else {
+        window.location.reload();
+      }
+    });
+  }
+  function swapStylesheets() {
+    document.querySelectorAll('link[rel="stylesheet"]').forEach((link) => {
+      const url = new URL(link.href, window.location.href);
+      if (url.origin !== window.location.origin) return;
+      url.searchParams.set("greentic-reload", String(Date.now()));
+      link.href = url.toString();
+    });
+  }
   function attachWorker({ workerId, selector, routes = [] }) {
     const el = document.querySelector(selector);
     if (!el) {
@@ -32,37 +70,255 @@
     const body = {
       worker_id: workerId,
       payload,
-      context: Object.assign({ path: window.location.pathname }, context)
+      context: Object.assign({ path: appPath(window.location.pathname) }, context)
     };
     const res = await fetch(config.workerMessageUrl, {
       method: "POST",
-      headers: { "Content-Type": "application/json" },
+      headers: Object.assign({ "Content-Type": "application/json" }, traceHeaders()),
       body: JSON.stringify(body)
     });
     return res.json();
   }
   async function sendEvent({ eventType, metadata = {} }) {
     if (!config) await init();
-    try {
-      await fetch(config.eventsUrl, {
-        method: "POST",
-        headers: { "Content-Type": "application/json" },
-        body: JSON.stringify({
-          event_type: eventType,
-          path: window.location.pathname,
-          timestamp: Date.now(),
-          metadata
-        })
+    if (!shouldSend(eventType)) return;
+    eventQueue.push({
+      event_type: eventType,
+      path: appPath(window.location.pathname),
+      timestamp: Date.now(),
+      traceparent: pageTraceparent(),
+      metadata
+    });
+    if (eventQueue.length >= config.batchSize) {
+      await flush();
+    } else {
+      scheduleFlush();
+    }
+  }
+  function pageMeta(name) {
+    if (typeof document === "undefined") return void 0;
+    const meta = document.querySelector?.(`meta[name="${name}"]`);
+    return meta?.getAttribute("content") || void 0;
+  }
+  function basePath() {
+    return (pageMeta("greentic-base") || "").replace(/\/+$/, "");
+  }
+  function serverUrl(path) {
+    return basePath() + path;
+  }
+  function appPath(pathname) {
+    const base = basePath();
+    if (!base || pathname !== base && !pathname.startsWith(`${base}/`)) return pathname;
+    return pathname.slice(base.length) || "/";
+  }
+  function pageTraceparent() {
+    return pageMeta("traceparent");
+  }
+  function traceHeaders() {
+    const parts = pageTraceparent()?.split("-");
+    if (!parts || parts.length !== 4) return {};
+    const headers = { traceparent: `00-${parts[1]}-${randomHex(8)}-${parts[3]}` };
+    const state = pageMeta("tracestate");
+    if (state) headers.tracestate = state;
+    return headers;
+  }
+  function randomHex(bytes) {
+    const buf = new Uint8Array(bytes);
+    if (typeof crypto !== "undefined" && crypto.getRandomValues) {
+      crypto.getRandomValues(buf);
+    } else {
+      for (let i = 0; i < bytes; i++) buf[i] = Math.floor(Math.random() * 256);
+    }
+    if (buf.every((b) => b === 0)) buf[bytes - 1] = 1;
+    return Array.from(buf, (b) => b.toString(16).padStart(2, "0")).join("");
+  }
+  function scheduleFlush() {
+    if (flushTimer || typeof setTimeout === "undefined") return;
+    flushTimer = setTimeout(() => {
+      flushTimer = null;
+      flush();
+    }, config.flushIntervalMs);
+  }
+  async function flush({ beacon = false } = {}) {
+    if (flushTimer) {
+      clearTimeout(flushTimer);
+      flushTimer = null;
+    }
+    if (!config) return;
+    while (eventQueue.length) {
+      const events = eventQueue.splice(0, MAX_BATCH);
+      const body = JSON.stringify({ sent_at: Date.now(), events });
+      if (beacon && typeof navigator !== "undefined" && navigator.sendBeacon?.(config.eventsUrl, body)) {
+        continue;
+      }
+      try {
+        await fetch(config.eventsUrl, {
+          method: "POST",
+          headers: Object.assign({ "Content-Type": "application/json" }, traceHeaders()),
+          body,
+          keepalive: beacon
+        });
+      } catch (err) {
+        console.warn("GreenticGUI: failed to send events", err);
+      }
+    }
+  }
+  function startUnloadFlush() {
+    if (unloadFlush || typeof document === "undefined") return;
+    unloadFlush = true;
+    document.addEventListener("visibilitychange", () => {
+      if (document.visibilityState === "hidden") flush({ beacon: true });
+    });
+  }
+  function shouldSend(eventType) {
+    const policy = config?.guiConfig?.telemetry;
+    if (!policy) return true;
+    const allowed = policy.allowed_events || [];
+    const listed = allowed.some(
+      (entry) => entry.endsWith("*") ? eventType.startsWith(entry.slice(0, -1)) : entry === eventType
+    );
+    if (allowed.length && !listed) return false;
+    const rate = policy.sampling?.[eventType] ?? policy.sample_rate ?? 1;
+    return Math.random() < rate;
+  }
+  function startAutoCapture(policy) {
+    const capture = policy?.auto_capture;
+    if (!capture || autoCapture || typeof document === "undefined") return;
+    autoCapture = true;
+    if (capture.page_views) {
+      const pageView = (route) => sendEvent({ eventType: "page_view", metadata: { title: document.title, pattern: route.pattern } });
+      pageView(getRoute());
+      window.addEventListener("greentic:navigate", (evt) => pageView(evt.detail));
+    }
+    if (capture.clicks) {
+      document.addEventListener(
+        "click",
+        (evt) => {
+          const el = evt.target?.closest?.("[data-track]");
+          if (el) sendEvent({ eventType: "click", metadata: { track: el.getAttribute("data-track") } });
+        },
+        true
+      );
+    }
+    if (capture.errors) {
+      window.addEventListener("error", (evt) => {
+        const err = evt;
+        sendEvent({
+          eventType: "js_error",
+          metadata: { message: err.message, source: err.filename, line: err.lineno, column: err.colno }
+        });
+      });
+      window.addEventListener("unhandledrejection", (evt) => {
+        const reason = evt.reason;
+        sendEvent({ eventType: "js_error", metadata: { message: String(reason?.message ?? reason), unhandled: true } });
       });
+    }
+    if (capture.web_vitals) startWebVitals();
+  }
+  function startWebVitals() {
+    if (typeof PerformanceObserver === "undefined") return;
+    const vitals = {};
+    const observe = (type, onEntry, opts = {}) => {
+      try {
+        new PerformanceObserver((list) => list.getEntries().forEach(onEntry)).observe({
+          type,
+          buffered: true,
+          ...opts
+        });
+      } catch (err) {
+      }
+    };
+    observe("largest-contentful-paint", (entry) => vitals.LCP = entry.startTime);
+    observe("layout-shift", (entry) => {
+      if (!entry.hadRecentInput) vitals.CLS = (vitals.CLS || 0) + entry.value;
+    });
+    observe("event", (entry) => vitals.INP = Math.max(vitals.INP || 0, entry.duration), { durationThreshold: 40 });
+    let reported = false;
+    document.addEventListener("visibilitychange", () => {
+      if (reported || document.visibilityState !== "hidden") return;
+      reported = true;
+      Object.keys(vitals).forEach((name) => sendEvent({ eventType: "web_vital", metadata: { name, value: vitals[name] } }));
+    });
+  }
+  function readJsonScript(id) {
+    if (typeof document === "undefined") return null;
+    const el = document.getElementById(id);
+    if (!el || !el.textContent) return null;
+    try {
+      return JSON.parse(el.textContent);
+    } catch (err) {
+      return null;
+    }
+  }
+  function getRoute() {
+    const fallback = { path: appPath(window.location.pathname), pattern: null, params: {} };
+    return Object.assign(fallback, readJsonScript("greentic-route"));
+  }
+  function getRoutes() {
+    return readJsonScript("greentic-routes")?.routes || [];
+  }
+  function isSpa() {
+    return readJsonScript("greentic-routes") !== null;
+  }
+  async function navigate(path, { replace = false } = {}) {
+    const url = new URL(path, window.location.href);
+    if (!isSpa() || url.origin !== window.location.origin) {
+      window.location.assign(url.toString());
+      return false;
+    }
+    let data;
+    try {
+      const res = await fetch(serverUrl(`/api/gui/fragments?path=${encodeURIComponent(appPath(url.pathname))}`));
+      if (!res.ok) throw new Error(`status ${res.status}`);
+      data = await res.json();
     } catch (err) {
-      console.warn("GreenticGUI: failed to send event", err);
+      window.location.assign(url.toString());
+      return false;
     }
+    data.fragments.forEach((fragment) => {
+      const el = document.querySelector(fragment.selector);
+      if (el) el.innerHTML = fragment.html;
+    });
+    const target = url.pathname + url.search + url.hash;
+    if (replace) {
+      window.history.replaceState({ greentic: true }, "", target);
+    } else {
+      window.history.pushState({ greentic: true }, "", target);
+    }
+    const route = { path: data.path, pattern: data.pattern, params: data.params };
+    let routeEl = document.getElementById("greentic-route");
+    if (!routeEl) {
+      routeEl = document.createElement("script");
+      routeEl.id = "greentic-route";
+      routeEl.setAttribute("type", "application/json");
+      document.head.appendChild(routeEl);
+    }
+    routeEl.textContent = JSON.stringify(route);
+    window.dispatchEvent(new CustomEvent("greentic:navigate", { detail: route }));
+    return true;
+  }
+  function startSpaNavigation() {
+    if (spaNavigation || !isSpa()) return;
+    spaNavigation = true;
+    window.addEventListener("popstate", () => {
+      navigate(window.location.pathname + window.location.search, { replace: true });
+    });
+    document.addEventListener("click", (evt) => {
+      const link = evt.target?.closest?.("a[data-greentic-nav]");
+      if (!link || evt.defaultPrevented || evt.button !== 0) return;
+      const mouse = evt;
+      if (mouse.metaKey || mouse.ctrlKey || mouse.shiftKey || mouse.altKey) return;
+      evt.preventDefault();
+      navigate(link.href);
+    });
   }
-  async function startSession({ userId, team }) {
+  async function startSession({ userId, team, token }) {
     if (!config) await init();
-    const res = await fetch("/api/gui/session", {
+    const headers = { "Content-Type": "application/json" };
+    if (token) headers.Authorization = `Bearer ${token}`;
+    const res = await fetch(serverUrl("/api/gui/session"), {
       method: "POST",
-      headers: { "Content-Type": "application/json" },
+      headers,
       body: JSON.stringify({ user_id: userId, team })
     });
     if (!res.ok) {
@@ -70,5 +326,5 @@
     }
     return res.json();
   }
-  window.GreenticGUI = { version, init, attachWorker, sendWorkerMessage, sendEvent, startSession };
+  window.GreenticGUI = { version, init, attachWorker, sendWorkerMessage, sendEvent, flush, startSession, getRoute, getRoutes, navigate };
 })();
@@ -29,12 +29,177 @@ sandbox.window.location = { host: "localhost", pathname: "/" };
 
 (async () => {
   await sandbox.window.GreenticGUI.sendEvent({ eventType: "test", metadata: { ok: true } });
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "test", metadata: { ok: false } });
+  assert(!events.some((e) => e.url.includes("/api/gui/events")), "sendEvent queues events");
+  await sandbox.window.GreenticGUI.flush();
   const eventCalls = events.filter((e) => e.url.includes("/api/gui/events"));
-  assert.strictEqual(eventCalls.length, 1, "sendEvent should POST once");
+  assert.strictEqual(eventCalls.length, 1, "flush should POST one batch");
+  const batch = JSON.parse(eventCalls[0].opts.body);
+  assert.strictEqual(batch.events.length, 2);
+  assert.strictEqual(typeof batch.sent_at, "number");
 
   // Ensure init sets defaults and does not throw when fetch fails (we override fetch).
   await sandbox.window.GreenticGUI.init({ configUrl: "/api/gui/config" });
   await sandbox.window.GreenticGUI.sendWorkerMessage({ workerId: "w", payload: { a: 1 } });
   assert(events.some((e) => e.url.includes("/api/gui/worker/message")), "worker message should POST");
+
+  // getRoute reads the server-embedded route descriptor and falls back to the location.
+  sandbox.document = { getElementById: () => null };
+  assert.strictEqual(sandbox.window.GreenticGUI.getRoute().path, "/");
+  sandbox.document = {
+    getElementById: (id) =>
+      id === "greentic-route"
+        ? { textContent: '{"path":"/invoices/7","pattern":"/invoices/:id","params":{"id":"7"}}' }
+        : null,
+  };
+  const route = sandbox.window.GreenticGUI.getRoute();
+  assert.strictEqual(route.pattern, "/invoices/:id");
+  assert.strictEqual(route.params.id, "7");
+
+  // navigate swaps fragments in SPA layouts instead of reloading the page.
+  const scripts = {
+    "greentic-routes": { textContent: '{"routes":[{"path":"/invoices/:id","authenticated":false,"fragments":["summary"],"pack":"billing"}]}' },
+    "greentic-route": { textContent: '{"path":"/","pattern":null,"params":{}}' },
+  };
+  const summary = { innerHTML: "" };
+  const pushed = [];
+  let dispatched = null;
+  sandbox.URL = URL;
+  sandbox.CustomEvent = class {
+    constructor(type, init) {
+      this.type = type;
+      this.detail = init.detail;
+    }
+  };
+  sandbox.document = {
+    getElementById: (id) => scripts[id] || null,
+    querySelector: (selector) => (selector === "#summary" ? summary : null),
+  };
+  sandbox.window.location = { host: "localhost", pathname: "/", href: "http://localhost/", origin: "http://localhost" };
+  sandbox.window.history = { pushState: (_s, _t, url) => pushed.push(url), replaceState: () => {} };
+  sandbox.window.dispatchEvent = (evt) => (dispatched = evt);
+  sandbox.fetch = async (url) => {
+    events.push({ url });
+    return {
+      ok: true,
+      json: async () => ({
+        path: "/invoices/9",
+        pattern: "/invoices/:id",
+        params: { id: "9" },
+        fragments: [{ id: "summary", selector: "#summary", html: "<b>9</b>" }],
+      }),
+    };
+  };
+  assert.strictEqual(sandbox.window.GreenticGUI.getRoutes()[0].pack, "billing");
+  assert.strictEqual(await sandbox.window.GreenticGUI.navigate("/invoices/9?tab=1"), true);
+  assert(events.some((e) => e.url === "/api/gui/fragments?path=%2Finvoices%2F9"));
+  assert.strictEqual(summary.innerHTML, "<b>9</b>");
+  assert.deepStrictEqual(pushed, ["/invoices/9?tab=1"]);
+  assert.strictEqual(JSON.parse(scripts["greentic-route"].textContent).params.id, "9");
+  assert.strictEqual(dispatched.type, "greentic:navigate");
+
+  // The telemetry policy drops unlisted events and turns on page-view auto-capture.
+  const posted = [];
+  const listeners = {};
+  const docListeners = {};
+  sandbox.window.addEventListener = (type, fn) => (listeners[type] = fn);
+  const traceparent = "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-01";
+  sandbox.document = {
+    title: "Invoices",
+    visibilityState: "visible",
+    getElementById: () => null,
+    querySelector: (selector) =>
+      selector === 'meta[name="traceparent"]' ? { getAttribute: () => traceparent } : null,
+    addEventListener: (type, fn) => (docListeners[type] = fn),
+  };
+  sandbox.fetch = async (url, opts) => {
+    if (url.includes("/api/gui/events")) posted.push(...JSON.parse(opts.body).events);
+    return {
+      ok: true,
+      json: async () => ({
+        telemetry: {
+          sample_rate: 1,
+          sampling: { "billing.noise": 0 },
+          allowed_events: ["page_view", "billing.*"],
+          auto_capture: { page_views: true, clicks: false, errors: false, web_vitals: false },
+        },
+      }),
+    };
+  };
+  await sandbox.window.GreenticGUI.init({ batchSize: 3 });
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "billing.paid" });
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "billing.noise" });
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "click" });
+  await new Promise((resolve) => setImmediate(resolve));
+  await sandbox.window.GreenticGUI.flush();
+  assert.deepStrictEqual(
+    posted.map((e) => e.event_type).sort(),
+    ["billing.paid", "page_view"]
+  );
+  assert.strictEqual(posted.find((e) => e.event_type === "page_view").metadata.title, "Invoices");
+  assert.strictEqual(posted.find((e) => e.event_type === "page_view").traceparent, traceparent);
+
+  // Worker messages start a new span in the page's trace.
+  let workerHeaders = null;
+  sandbox.fetch = async (url, opts) => {
+    if (url.includes("/api/gui/worker/message")) workerHeaders = opts.headers;
+    return { ok: true, json: async () => ({}) };
+  };
+  await sandbox.window.GreenticGUI.sendWorkerMessage({ workerId: "w" });
+  const [version, traceId, spanId] = workerHeaders.traceparent.split("-");
+  assert.strictEqual(version, "00");
+  assert.strictEqual(traceId, "0102030405060708090a0b0c0d0e0f10");
+  assert.match(spanId, /^[0-9a-f]{16}$/);
+  assert.notStrictEqual(spanId, "0102030405060708");
+  sandbox.fetch = async (url, opts) => {
+    if (url.includes("/api/gui/events")) posted.push(...JSON.parse(opts.body).events);
+    return { ok: true, json: async () => ({}) };
+  };
+  assert(listeners["greentic:navigate"], "page views follow client-side navigation");
+
+  // A full queue flushes on its own; hiding the page sends the rest as a beacon.
+  posted.length = 0;
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "billing.a" });
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "billing.b" });
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "billing.c" });
+  assert.strictEqual(posted.length, 3, "batchSize events trigger a flush");
+  const beacons = [];
+  sandbox.navigator = { sendBeacon: (url, body) => beacons.push({ url, body }) > 0 };
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "billing.d" });
+  sandbox.document.visibilityState = "hidden";
+  docListeners.visibilitychange();
+  assert.strictEqual(beacons.length, 1);
+  assert.strictEqual(JSON.parse(beacons[0].body).events[0].event_type, "billing.d");
+
+  // Path-prefix tenants name their base; server URLs go under it and paths are matched without it.
+  const prefixed = [];
+  const pageRoute = { textContent: "" };
+  sandbox.navigator = undefined;
+  sandbox.document = {
+    visibilityState: "visible",
+    getElementById: (id) => (id === "greentic-routes" ? scripts[id] : id === "greentic-route" ? pageRoute : null),
+    querySelector: (selector) =>
+      selector === 'meta[name="greentic-base"]' ? { getAttribute: () => "/t/globex" } : null,
+    addEventListener: () => {},
+  };
+  sandbox.window.location = {
+    host: "localhost",
+    pathname: "/t/globex/invoices/7",
+    href: "http://localhost/t/globex/invoices/7",
+    origin: "http://localhost",
+  };
+  sandbox.fetch = async (url, opts) => {
+    prefixed.push({ url, opts });
+    return { ok: true, json: async () => ({ path: "/invoices/9", pattern: null, params: {}, fragments: [] }) };
+  };
+  await sandbox.window.GreenticGUI.init();
+  assert.strictEqual(prefixed[0].url, "/t/globex/api/gui/config");
+  assert.strictEqual(sandbox.window.GreenticGUI.getRoute().path, "/invoices/7");
+  await sandbox.window.GreenticGUI.sendEvent({ eventType: "billing.e" });
+  await sandbox.window.GreenticGUI.flush();
+  const prefixedEvents = prefixed.find((e) => e.url === "/t/globex/api/gui/events");
+  assert.strictEqual(JSON.parse(prefixedEvents.opts.body).events[0].path, "/invoices/7");
+  await sandbox.window.GreenticGUI.navigate("/t/globex/invoices/9");
+  assert(prefixed.some((e) => e.url === "/t/globex/api/gui/fragments?path=%2Finvoices%2F9"));
   console.log("sdk-tests.js passed");
 })();
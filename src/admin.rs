@@ -0,0 +1,488 @@
+use crate::auth::{bearer_token, build_validation_config, constant_time_eq, session_cookie};
+use crate::domains::RequestTenant;
+use crate::integration::{TelemetryEvent, make_telemetry_ctx};
+use crate::server::AppState;
+use axum::Json;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use greentic_oauth_sdk::validate_bearer_token;
+use serde_json::json;
+use tracing::warn;
+
+/// Caller authorized for an admin endpoint; inserted into request extensions by [`require_admin`].
+#[derive(Debug, Clone)]
+pub struct AdminPrincipal {
+    pub kind: PrincipalKind,
+    /// Subject (user id) for session/bearer callers, `"service"` for the service token.
+    pub subject: String,
+    /// Tenant the credential was issued for; `None` for the service token, which spans tenants.
+    pub tenant: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrincipalKind {
+    ServiceToken,
+    Bearer,
+    Session,
+}
+
+impl PrincipalKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            PrincipalKind::ServiceToken => "service_token",
+            PrincipalKind::Bearer => "bearer",
+            PrincipalKind::Session => "session",
+        }
+    }
+}
+
+impl AdminPrincipal {
+    /// Whether this principal may act on `tenant`.
+    pub fn may_manage(&self, tenant: &str) -> bool {
+        self.tenant.as_deref().is_none_or(|t| t == tenant)
+    }
+}
+
+#[derive(Debug)]
+pub enum AdminError {
+    /// No credential, or one that failed validation.
+    Unauthorized(&'static str),
+    /// Valid credential lacking the admin scope or bound to another tenant.
+    Forbidden(&'static str),
+}
+
+impl AdminError {
+    fn status(&self) -> StatusCode {
+        match self {
+            AdminError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AdminError::Forbidden(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            AdminError::Unauthorized(reason) | AdminError::Forbidden(reason) => reason,
+        }
+    }
+}
+
+impl IntoResponse for AdminError {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let error = match self {
+            AdminError::Unauthorized(_) => "unauthorized",
+            AdminError::Forbidden(_) => "forbidden",
+        };
+        let mut resp = (
+            status,
+            Json(json!({ "error": error, "reason": self.reason() })),
+        )
+            .into_response();
+        if status == StatusCode::UNAUTHORIZED {
+            resp.headers_mut().insert(
+                header::WWW_AUTHENTICATE,
+                header::HeaderValue::from_static("Bearer"),
+            );
+        }
+        resp
+    }
+}
+
+/// Route layer guarding admin endpoints.
+///
+/// Accepts the configured service token, a bearer token validated by greentic-oauth-sdk, or a
+/// session cookie; the latter two must carry `oauth_admin_scope` for the request tenant. Every
+/// attempt, allowed or not, is recorded as an `admin.audit` telemetry event.
+pub async fn require_admin(
+    State(state): State<AppState>,
+    tenant: RequestTenant,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    match authorize(&state, &tenant, req.headers()).await {
+        Ok(principal) => {
+            req.extensions_mut().insert(principal.clone());
+            let resp = next.run(req).await;
+            audit(
+                &state,
+                &path,
+                &tenant,
+                Some(&principal),
+                resp.status(),
+                None,
+            )
+            .await;
+            resp
+        }
+        Err(err) => {
+            warn!(path = %path, tenant = %tenant.tenant, reason = err.reason(), "admin request denied");
+            audit(
+                &state,
+                &path,
+                &tenant,
+                None,
+                err.status(),
+                Some(err.reason()),
+            )
+            .await;
+            err.into_response()
+        }
+    }
+}
+
+async fn authorize(
+    state: &AppState,
+    tenant: &RequestTenant,
+    headers: &HeaderMap,
+) -> Result<AdminPrincipal, AdminError> {
+    let config = &state.config;
+    if let Some(token) = bearer_token(headers) {
+        if let Some(expected) = &config.admin_token
+            && constant_time_eq(token.as_bytes(), expected.as_bytes())
+        {
+            return Ok(AdminPrincipal {
+                kind: PrincipalKind::ServiceToken,
+                subject: "service".into(),
+                tenant: None,
+            });
+        }
+        let cfg = build_validation_config(config)
+            .map_err(|_| AdminError::Unauthorized("invalid_token"))?;
+        let (claims, _) = validate_bearer_token(&token, &cfg)
+            .await
+            .map_err(|_| AdminError::Unauthorized("invalid_token"))?;
+        let principal = AdminPrincipal {
+            kind: PrincipalKind::Bearer,
+            subject: claims.user_id,
+            tenant: Some(claims.tenant_id),
+        };
+        return check_grant(config, tenant, principal, &claims.scopes);
+    }
+
+    let Some(session_id) = session_cookie(headers) else {
+        return Err(AdminError::Unauthorized("missing_credentials"));
+    };
+    let session = match state.session_manager.validate(Some(session_id)).await {
+        Ok(Some(session)) => session,
+        Ok(None) => return Err(AdminError::Unauthorized("invalid_session")),
+        Err(err) => {
+            warn!(?err, "session validation failed");
+            return Err(AdminError::Unauthorized("invalid_session"));
+        }
+    };
+    let principal = AdminPrincipal {
+        kind: PrincipalKind::Session,
+        subject: session.user_id.unwrap_or_default(),
+        tenant: Some(session.tenant_ctx.tenant_id.to_string()),
+    };
+    check_grant(config, tenant, principal, &session.scopes)
+}
+
+fn check_grant(
+    config: &crate::config::AppConfig,
+    tenant: &RequestTenant,
+    principal: AdminPrincipal,
+    scopes: &[String],
+) -> Result<AdminPrincipal, AdminError> {
+    if !scopes.iter().any(|s| s == &config.oauth_admin_scope) {
+        return Err(AdminError::Forbidden("missing_admin_scope"));
+    }
+    if !principal.may_manage(&tenant.tenant) {
+        return Err(AdminError::Forbidden("tenant_mismatch"));
+    }
+    Ok(principal)
+}
+
+async fn audit(
+    state: &AppState,
+    path: &str,
+    tenant: &RequestTenant,
+    principal: Option<&AdminPrincipal>,
+    status: StatusCode,
+    denied: Option<&str>,
+) {
+    let event = TelemetryEvent {
+        event_type: "admin.audit".into(),
+        path: path.to_string(),
+        timestamp_ms: chrono::Utc::now().timestamp_millis(),
+        metadata: json!({
+            "tenant": tenant.tenant,
+            "principal": principal.map(|p| p.subject.as_str()),
+            "principal_kind": principal.map(|p| p.kind.as_str()),
+            "status": status.as_u16(),
+            "allowed": denied.is_none(),
+            "reason": denied,
+        }),
+        ctx: make_telemetry_ctx(&tenant.tenant, None, Some("gui")),
+        ..Default::default()
+    };
+    state.telemetry.record_event(event).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fragments::CompositeFragmentRenderer;
+    use crate::integration::{
+        NullTelemetrySink, SessionError, SessionInfo, SessionManager, TelemetrySink,
+        build_tenant_ctx,
+    };
+    use crate::packs::{FsPackProvider, GuiPack, PackProvider};
+    use crate::worker::{StubWorkerBackend, WorkerHost};
+    use async_trait::async_trait;
+    use axum::body::{Body, to_bytes};
+    use greentic_types::{FlowId, TenantCtx};
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    /// Resolves `admin` and `viewer` sessions with and without the admin scope.
+    struct ScopedSessions;
+
+    #[async_trait]
+    impl SessionManager for ScopedSessions {
+        async fn validate(
+            &self,
+            cookie: Option<String>,
+        ) -> Result<Option<SessionInfo>, SessionError> {
+            let scopes = match cookie.as_deref() {
+                Some("admin") => vec!["openid".to_string(), "gui.admin".to_string()],
+                Some("viewer") => vec!["openid".to_string()],
+                _ => return Ok(None),
+            };
+            Ok(Some(SessionInfo {
+                session_id: cookie.unwrap_or_default(),
+                tenant_ctx: build_tenant_ctx("dev", "tenant", None, Some("alice")),
+                user_id: Some("alice".into()),
+                scopes,
+            }))
+        }
+
+        async fn issue(
+            &self,
+            _ctx: TenantCtx,
+            _flow_id: FlowId,
+            _scopes: Vec<String>,
+        ) -> Result<SessionInfo, SessionError> {
+            Err(SessionError::Provider("not supported".into()))
+        }
+
+        async fn revoke(&self, _session_id: &str) -> Result<(), SessionError> {
+            Ok(())
+        }
+
+        async fn revoke_all_for_user(&self, _ctx: &TenantCtx) -> Result<usize, SessionError> {
+            Ok(0)
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<TelemetryEvent>>);
+
+    #[async_trait]
+    impl TelemetrySink for RecordingSink {
+        async fn record_event(&self, event: TelemetryEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    /// Pack provider without packs that records cache clears (`None` for a global clear).
+    #[derive(Default)]
+    struct ClearRecorder(Mutex<Vec<Option<String>>>);
+
+    #[async_trait]
+    impl PackProvider for ClearRecorder {
+        async fn load_layout(&self, tenant: &str) -> anyhow::Result<GuiPack> {
+            anyhow::bail!("no layout pack for {tenant}")
+        }
+
+        async fn load_auth(&self, _tenant: &str) -> anyhow::Result<Option<GuiPack>> {
+            Ok(None)
+        }
+
+        async fn load_skin(&self, _tenant: &str) -> anyhow::Result<Option<GuiPack>> {
+            Ok(None)
+        }
+
+        async fn load_telemetry(&self, _tenant: &str) -> anyhow::Result<Option<GuiPack>> {
+            Ok(None)
+        }
+
+        async fn load_features(&self, _tenant: &str) -> anyhow::Result<Vec<GuiPack>> {
+            Ok(vec![])
+        }
+
+        async fn clear_cache(&self) {
+            self.0.lock().unwrap().push(None);
+        }
+
+        async fn clear_tenant_cache(&self, tenant: &str) {
+            self.0.lock().unwrap().push(Some(tenant.to_string()));
+        }
+    }
+
+    fn admin_state(telemetry: Arc<dyn TelemetrySink>) -> AppState {
+        admin_state_with_packs(
+            telemetry,
+            Arc::new(FsPackProvider::new(std::env::temp_dir().join("no-packs"))),
+        )
+    }
+
+    fn admin_state_with_packs(
+        telemetry: Arc<dyn TelemetrySink>,
+        packs: Arc<dyn PackProvider>,
+    ) -> AppState {
+        let mut config = crate::config::test_config();
+        config.admin_token = Some("s3cret".into());
+        AppState::new(
+            config,
+            packs,
+            Arc::new(CompositeFragmentRenderer::file_only()),
+            Arc::new(ScopedSessions),
+            telemetry,
+            Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+        )
+    }
+
+    fn clear_request(header: Option<(header::HeaderName, &str)>) -> Request {
+        let mut builder = Request::post("/api/gui/cache/clear").header(header::HOST, "localhost");
+        if let Some((name, value)) = header {
+            builder = builder.header(name, value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_and_invalid_credentials() {
+        let app = crate::server::router(admin_state(Arc::new(NullTelemetrySink)));
+        let resp = app.clone().oneshot(clear_request(None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "unauthorized");
+        assert_eq!(body["reason"], "missing_credentials");
+
+        let resp = app
+            .clone()
+            .oneshot(clear_request(Some((header::AUTHORIZATION, "Bearer wrong"))))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = app
+            .oneshot(clear_request(Some((
+                header::COOKIE,
+                "greentic_session_id=unknown",
+            ))))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn session_needs_admin_scope() {
+        let app = crate::server::router(admin_state(Arc::new(NullTelemetrySink)));
+        let resp = app
+            .clone()
+            .oneshot(clear_request(Some((
+                header::COOKIE,
+                "greentic_session_id=viewer",
+            ))))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "forbidden");
+
+        let resp = app
+            .oneshot(clear_request(Some((
+                header::COOKIE,
+                "greentic_session_id=admin",
+            ))))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn service_token_is_accepted_and_audited() {
+        let sink = Arc::new(RecordingSink::default());
+        let app = crate::server::router(admin_state(sink.clone()));
+        let resp = app
+            .clone()
+            .oneshot(clear_request(Some((
+                header::AUTHORIZATION,
+                "Bearer s3cret",
+            ))))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        app.oneshot(clear_request(None)).await.unwrap();
+
+        let events = sink.0.lock().unwrap();
+        let audits: Vec<_> = events
+            .iter()
+            .filter(|e| e.event_type == "admin.audit")
+            .collect();
+        assert_eq!(audits.len(), 2);
+        assert_eq!(audits[0].metadata["principal_kind"], "service_token");
+        assert_eq!(audits[0].metadata["allowed"], true);
+        assert_eq!(audits[1].metadata["allowed"], false);
+        assert_eq!(audits[1].metadata["status"], 401);
+    }
+
+    #[tokio::test]
+    async fn tenant_admins_only_clear_their_own_tenant() {
+        let packs = Arc::new(ClearRecorder::default());
+        let app = crate::server::router(admin_state_with_packs(
+            Arc::new(NullTelemetrySink),
+            packs.clone(),
+        ));
+        let admin = "greentic_session_id=admin";
+
+        let resp = app
+            .clone()
+            .oneshot(clear_request(Some((header::COOKIE, admin))))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        let reload_other = Request::post("/api/gui/packs/reload")
+            .header(header::HOST, "localhost")
+            .header(header::COOKIE, admin)
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"tenant":"other"}"#))
+            .unwrap();
+        let resp = app.clone().oneshot(reload_other).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+        let resp = app
+            .oneshot(clear_request(Some((
+                header::AUTHORIZATION,
+                "Bearer s3cret",
+            ))))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        assert_eq!(
+            *packs.0.lock().unwrap(),
+            vec![Some("tenant".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn parses_bearer_tokens() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "bearer abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers).as_deref(), Some("abc"));
+        headers.insert(header::AUTHORIZATION, "Basic abc".parse().unwrap());
+        assert_eq!(bearer_token(&headers), None);
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+    }
+}
@@ -1,13 +1,29 @@
-use crate::integration::{TelemetryEvent, build_tenant_ctx};
+use crate::admin::AdminPrincipal;
+use crate::assets::pack_asset_url;
+use crate::auth::{bearer_token, build_validation_config, make_session_cookie, session_cookie};
+use crate::domains::{RequestTenant, client_ip};
+use crate::fragments::{FragmentRequest, render_fragments};
+use crate::integration::{TelemetryEvent, build_tenant_ctx, make_telemetry_ctx};
+use crate::otlp::{TraceContext, TraceParent};
+use crate::packs::normalize_route;
+use crate::routing::{login_redirect, route_for_path};
 use crate::server::AppState;
+use crate::telemetry::{correct_timestamp, digest};
 use crate::tenant::TenantGuiConfig;
 use crate::worker::MissingSecretsError;
 use axum::Json;
-use axum::extract::State;
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, Extension, Query, State};
 use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{IntoResponse, Response};
+use greentic_oauth_sdk::validate_bearer_token;
 use serde::Deserialize;
 use serde_json::json;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::BroadcastStream;
 
 pub async fn serve_sdk(State(_state): State<AppState>) -> impl IntoResponse {
     match std::fs::read_to_string("assets/gui-sdk.js") {
@@ -31,14 +47,32 @@ pub async fn serve_sdk(State(_state): State<AppState>) -> impl IntoResponse {
     }
 }
 
+/// Base stylesheet of the built-in pages; colors come from skin tokens when a skin is mounted.
+pub async fn serve_theme() -> impl IntoResponse {
+    match tokio::fs::read_to_string("assets/theme.css").await {
+        Ok(css) => ([(header::CONTENT_TYPE, "text/css")], css).into_response(),
+        Err(_) => (StatusCode::NOT_FOUND, "not found").into_response(),
+    }
+}
+
 pub async fn get_gui_config(
     State(state): State<AppState>,
+    tenant: RequestTenant,
     headers: HeaderMap,
 ) -> impl IntoResponse {
-    let domain = super::server::host_from_headers(&headers)
-        .unwrap_or_else(|| state.config.default_tenant.clone());
-    match state.load_tenant(&domain).await {
+    match state.load_tenant(&tenant.tenant, &tenant.domain).await {
         Ok(cfg) => {
+            let session = match state
+                .session_manager
+                .validate(session_cookie(&headers))
+                .await
+            {
+                Ok(session) => session,
+                Err(err) => {
+                    tracing::warn!(?err, "session validation failed");
+                    None
+                }
+            };
             let routes: Vec<serde_json::Value> = cfg
                 .features
                 .iter()
@@ -61,10 +95,18 @@ pub async fn get_gui_config(
                 "tenant": cfg.tenant_did,
                 "domain": cfg.domain,
                 "routes": routes,
+                "route_conflicts": cfg.route_conflicts,
+                "menu": cfg.menu(session.as_ref()),
                 "workers": workers,
-                "skin": cfg.skin.as_ref().map(|s| s.assets.to_string_lossy()),
+                "skin": cfg.skin.as_ref().map(|s| pack_asset_url("skin", &s.location)),
+                "theme": cfg.theme(tenant.path_prefix.as_deref()),
+                "telemetry": cfg.telemetry_client_policy(),
                 "secret_requirements": cfg.secret_requirements,
                 "pack_init_hint": pack_init_hint,
+                "live_reload": state
+                    .config
+                    .is_dev()
+                    .then(|| tenant.url_for("/api/gui/dev/reload")),
             });
             Json(body).into_response()
         }
@@ -75,12 +117,11 @@ pub async fn get_gui_config(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::AppConfig;
     use crate::fragments::{FragmentContext, FragmentRenderer};
     use crate::integration::{
         SessionError, SessionInfo, SessionManager, TelemetryEvent, TelemetrySink,
     };
-    use crate::packs::{GuiPack, LayoutConfig, LayoutManifest, PackProvider};
+    use crate::packs::{GuiPack, LayoutConfig, LayoutManifest, PackProvider, TelemetryManifest};
     use crate::worker::{WorkerBackend, WorkerHost};
     use async_trait::async_trait;
     use axum::body::to_bytes;
@@ -90,10 +131,8 @@ mod tests {
         FlowId, SecretFormat, SecretKey, SecretRequirement, SecretScope, TenantCtx,
     };
     use std::collections::HashMap;
-    use std::net::SocketAddr;
     use std::path::Path;
     use std::sync::Arc;
-    use std::time::Duration;
 
     #[tokio::test]
     async fn missing_secrets_response_includes_remediation_hint() {
@@ -137,8 +176,9 @@ mod tests {
             Some(pack_hint.clone()),
             Arc::new(StubWorkerBackend),
         );
-        let headers = HeaderMap::new();
-        let resp = get_gui_config(State(state), headers).await.into_response();
+        let resp = get_gui_config(State(state), test_tenant(), HeaderMap::new())
+            .await
+            .into_response();
         assert_eq!(resp.status(), StatusCode::OK);
         let body = to_bytes(resp.into_body(), usize::MAX).await.expect("body");
         let json: serde_json::Value = serde_json::from_slice(&body).expect("json");
@@ -150,6 +190,190 @@ mod tests {
         assert_eq!(json["pack_init_hint"].as_str().unwrap(), pack_hint);
     }
 
+    #[tokio::test]
+    async fn telemetry_pack_filters_and_redacts_events() {
+        let manifest: TelemetryManifest = serde_json::from_value(json!({
+            "kind": "gui-telemetry",
+            "sample_rate": 0.5,
+            "allowed_events": ["page_view", "billing.*"],
+            "redact": [{ "field": "email", "action": "remove" }],
+            "auto_capture": { "page_views": true },
+        }))
+        .unwrap();
+        let sink = Arc::new(RecordingSink::default());
+        let mut state = test_state(vec![], None, Arc::new(StubWorkerBackend));
+        state.pack_provider = Arc::new(MockPackProvider {
+            requirements: vec![],
+            pack_hint: None,
+            telemetry: Some(manifest),
+        });
+        state.telemetry = sink.clone();
+
+        let post = |event_type: &str| {
+            let body = json!({
+                "event_type": event_type,
+                "path": "/invoices",
+                "metadata": { "email": "a@b.c", "plan": "pro" },
+            });
+            post_events(
+                State(state.clone()),
+                test_tenant(),
+                None,
+                json_headers("application/json"),
+                Bytes::from(body.to_string()),
+            )
+        };
+        assert_eq!(
+            post("billing.paid").await.into_response().status(),
+            StatusCode::ACCEPTED
+        );
+        assert_eq!(
+            post("click").await.into_response().status(),
+            StatusCode::FORBIDDEN
+        );
+        let events = sink.0.lock().unwrap().clone();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].metadata, json!({ "plan": "pro" }));
+
+        let resp = get_gui_config(State(state), test_tenant(), HeaderMap::new())
+            .await
+            .into_response();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["telemetry"]["sample_rate"], 0.5);
+        assert_eq!(json["telemetry"]["auto_capture"]["page_views"], true);
+        assert!(json["telemetry"].get("redact").is_none());
+    }
+
+    #[tokio::test]
+    async fn event_batches_accept_beacon_bodies_and_rate_limit_sessions() {
+        let sink = Arc::new(RecordingSink::default());
+        let base = test_state(vec![], None, Arc::new(StubWorkerBackend));
+        let mut cfg = crate::config::test_config();
+        cfg.telemetry_rate_limit = 3;
+        let state = AppState::new(
+            cfg,
+            base.pack_provider,
+            base.fragment_renderer,
+            Arc::new(KnownSession("s1")),
+            sink.clone(),
+            base.worker_host,
+        );
+        let now = chrono::Utc::now().timestamp_millis();
+        let batch = json!({
+            "sent_at": now - 60_000,
+            "events": [
+                {
+                    "event_type": "a",
+                    "path": "/",
+                    "timestamp": now - 62_000,
+                    "traceparent": "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-01",
+                },
+                { "event_type": "b", "path": "/" },
+            ],
+        });
+        let mut headers = json_headers("text/plain;charset=UTF-8");
+        headers.insert(header::COOKIE, "greentic_session_id=s1".parse().unwrap());
+        let resp = post_events(
+            State(state.clone()),
+            test_tenant(),
+            None,
+            headers.clone(),
+            Bytes::from(batch.to_string()),
+        )
+        .await
+        .into_response();
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["accepted"], 2);
+
+        let events = sink.0.lock().unwrap().clone();
+        // The client clock runs a minute behind; the first event happened 2s before sending.
+        let skew = events[0].timestamp_ms - now;
+        assert!((-3_000..=-1_000).contains(&skew), "corrected skew {skew}");
+        assert!(events[1].timestamp_ms >= now);
+        // Events carry the page's trace and a digest of the session, never the session id.
+        assert_eq!(
+            events[0].trace.as_ref().map(|t| t.span_id.as_str()),
+            Some("0102030405060708")
+        );
+        assert_eq!(events[0].ctx.session, Some(digest("s1")));
+        assert_eq!(events[1].trace, None);
+
+        // Only one event of the session's budget remains, then the window is exhausted.
+        let resp = post_events(
+            State(state.clone()),
+            test_tenant(),
+            None,
+            headers.clone(),
+            Bytes::from(batch.to_string()),
+        )
+        .await
+        .into_response();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            (json["accepted"].as_u64(), json["dropped"].as_u64()),
+            (Some(1), Some(1))
+        );
+        let resp = post_events(
+            State(state.clone()),
+            test_tenant(),
+            None,
+            headers,
+            Bytes::from(batch.to_string()),
+        )
+        .await
+        .into_response();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key(header::RETRY_AFTER));
+
+        // Unknown cookies do not get budgets of their own: they share their client's anonymous
+        // one, while another client keeps its full budget.
+        let mut accepted = Vec::new();
+        for (forged, peer) in [
+            ("forged-1", "203.0.113.9:4000"),
+            ("forged-2", "203.0.113.9:4001"),
+            ("forged-3", "198.51.100.7:4000"),
+        ] {
+            let mut headers = json_headers("application/json");
+            let cookie = format!("greentic_session_id={forged}");
+            headers.insert(header::COOKIE, cookie.parse().unwrap());
+            let peer = ConnectInfo(peer.parse::<SocketAddr>().unwrap());
+            let resp = post_events(
+                State(state.clone()),
+                test_tenant(),
+                Some(Extension(peer)),
+                headers,
+                Bytes::from(batch.to_string()),
+            )
+            .await
+            .into_response();
+            let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+            let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            accepted.push(json["accepted"].as_u64());
+        }
+        assert_eq!(accepted, [Some(2), Some(1), Some(2)]);
+
+        let resp = post_events(
+            State(state),
+            test_tenant(),
+            None,
+            json_headers("application/x-www-form-urlencoded"),
+            Bytes::from(batch.to_string()),
+        )
+        .await
+        .into_response();
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    fn json_headers(content_type: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+        headers
+    }
+
     #[tokio::test]
     async fn worker_missing_secrets_returns_428_with_hint() {
         let req = sample_req();
@@ -164,7 +388,7 @@ mod tests {
             payload: serde_json::json!({}),
             context: WorkerRequestContext::default(),
         };
-        let resp = post_worker_message(State(state), Json(body))
+        let resp = post_worker_message(State(state), test_tenant(), HeaderMap::new(), Json(body))
             .await
             .into_response();
         assert_eq!(resp.status(), StatusCode::PRECONDITION_REQUIRED);
@@ -186,6 +410,124 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn router_mounts_pack_asset_route() {
+        use tower::ServiceExt;
+
+        let state = test_state(vec![], None, Arc::new(StubWorkerBackend));
+        let app = crate::server::router(state);
+        let req = axum::http::Request::builder()
+            .uri("/_packs/feature/unknown/app.css")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn unmapped_host_returns_unknown_tenant() {
+        use tower::ServiceExt;
+
+        let mut state = test_state(vec![], None, Arc::new(StubWorkerBackend));
+        state.config.domains = toml::from_str(
+            r#"
+            [[domain]]
+            host = "acme.example"
+            tenant = "acme"
+            "#,
+        )
+        .unwrap();
+        let app = crate::server::router(state);
+        let req = axum::http::Request::builder()
+            .uri("/api/gui/config")
+            .header(header::HOST, "other.example")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.expect("body");
+        let json: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(json["error"], "unknown_tenant");
+        assert_eq!(json["domain"], "other.example");
+    }
+
+    #[tokio::test]
+    async fn session_minting_is_dev_only_without_bearer() {
+        use tower::ServiceExt;
+
+        let session_req = |auth: Option<&str>| {
+            let mut builder = axum::http::Request::post("/api/gui/session")
+                .header(header::HOST, "localhost")
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(auth) = auth {
+                builder = builder.header(header::AUTHORIZATION, auth);
+            }
+            builder
+                .body(axum::body::Body::from(r#"{"user_id":"alice"}"#))
+                .unwrap()
+        };
+
+        let mut state = test_state(vec![], None, Arc::new(StubWorkerBackend));
+        state.config.session_ttl = std::time::Duration::from_secs(60);
+        let resp = crate::server::router(state.clone())
+            .oneshot(session_req(None))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let cookie = resp.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.contains("HttpOnly; SameSite=Lax"));
+        assert!(cookie.contains("Max-Age=60"));
+
+        state.config.env_id = "prod".into();
+        let app = crate::server::router(state);
+        let resp = app.clone().oneshot(session_req(None)).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(resp.headers().get(header::SET_COOKIE).is_none());
+        let body = to_bytes(resp.into_body(), usize::MAX).await.expect("body");
+        let json: serde_json::Value = serde_json::from_slice(&body).expect("json");
+        assert_eq!(json["reason"], "bearer_required");
+
+        let resp = app
+            .oneshot(session_req(Some("Bearer not-a-jwt")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(resp.headers().get(header::SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn dev_reload_streams_only_own_tenant_changes() {
+        use crate::watch::{PackChange, ReloadKind};
+
+        let state = test_state(vec![], None, Arc::new(StubWorkerBackend));
+        let resp = dev_reload_events(State(state.clone()), test_tenant()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        state.notify_pack_change(PackChange {
+            tenant: "other".into(),
+            packs: vec!["layout".into()],
+            reload: ReloadKind::Full,
+        });
+        state.notify_pack_change(PackChange {
+            tenant: "tenant".into(),
+            packs: vec!["brand".into()],
+            reload: ReloadKind::Css,
+        });
+        let mut body = resp.into_body().into_data_stream();
+        let chunk = body.next().await.expect("event").expect("bytes");
+        let text = String::from_utf8(chunk.to_vec()).unwrap();
+        assert!(text.contains("event: pack-change"));
+        assert!(text.contains("\"reload\":\"css\""));
+        assert!(!text.contains("other"));
+    }
+
+    fn test_tenant() -> RequestTenant {
+        RequestTenant {
+            tenant: "tenant".into(),
+            domain: "localhost".into(),
+            path_prefix: None,
+        }
+    }
+
     fn sample_req() -> SecretRequirement {
         let mut req = SecretRequirement::default();
         req.key = SecretKey::new("api/token").unwrap();
@@ -204,50 +546,11 @@ mod tests {
         pack_hint: Option<String>,
         worker_backend: Arc<dyn WorkerBackend>,
     ) -> AppState {
-        let cfg = AppConfig {
-            bind_addr: "127.0.0.1:0".parse::<SocketAddr>().unwrap(),
-            public_base_url: None,
-            pack_root: std::path::PathBuf::from("./packs"),
-            default_tenant: "tenant".into(),
-            enable_cors: false,
-            pack_cache_ttl: Duration::from_secs(0),
-            session_ttl: Duration::from_secs(0),
-            env_id: "dev".into(),
-            default_team: "team".into(),
-            distributor: None,
-            oauth_broker_url: None,
-            oauth_issuer: None,
-            oauth_audience: None,
-            oauth_jwks_url: None,
-            oauth_required_scopes: vec![],
-            resolved: greentic_config_types::GreenticConfig {
-                schema_version: greentic_config_types::ConfigVersion::v1(),
-                environment: greentic_config_types::EnvironmentConfig {
-                    env_id: greentic_types::EnvId::new("dev").unwrap(),
-                    deployment: None,
-                    connection: None,
-                    region: None,
-                },
-                paths: greentic_config_types::PathsConfig {
-                    greentic_root: std::path::PathBuf::from("."),
-                    state_dir: std::path::PathBuf::from("."),
-                    cache_dir: std::path::PathBuf::from("."),
-                    logs_dir: std::path::PathBuf::from("."),
-                },
-                packs: None,
-                services: None,
-                events: None,
-                runtime: greentic_config_types::RuntimeConfig::default(),
-                telemetry: greentic_config_types::TelemetryConfig::default(),
-                network: greentic_config_types::NetworkConfig::default(),
-                deployer: None,
-                secrets: greentic_config_types::SecretsBackendRefConfig::default(),
-                dev: None,
-            },
-        };
+        let cfg = crate::config::test_config();
         let pack_provider: Arc<dyn PackProvider> = Arc::new(MockPackProvider {
             requirements,
             pack_hint,
+            telemetry: None,
         });
         let fragment_renderer: Arc<dyn FragmentRenderer> = Arc::new(NullFragmentRenderer);
         let session_manager: Arc<dyn SessionManager> = Arc::new(NullSessionManager);
@@ -266,6 +569,7 @@ mod tests {
     struct MockPackProvider {
         requirements: Vec<SecretRequirement>,
         pack_hint: Option<String>,
+        telemetry: Option<TelemetryManifest>,
     }
 
     #[async_trait]
@@ -297,7 +601,12 @@ mod tests {
         }
 
         async fn load_telemetry(&self, _tenant: &str) -> anyhow::Result<Option<GuiPack>> {
-            Ok(None)
+            Ok(self.telemetry.clone().map(|manifest| GuiPack::Telemetry {
+                manifest,
+                root: std::path::PathBuf::from("/tmp/telemetry"),
+                secret_requirements: vec![],
+                pack_hint: None,
+            }))
         }
 
         async fn load_features(&self, _tenant: &str) -> anyhow::Result<Vec<GuiPack>> {
@@ -305,6 +614,8 @@ mod tests {
         }
 
         async fn clear_cache(&self) {}
+
+        async fn clear_tenant_cache(&self, _tenant: &str) {}
     }
 
     struct NullFragmentRenderer;
@@ -323,6 +634,41 @@ mod tests {
 
     struct NullSessionManager;
 
+    /// Validates only the given session id.
+    struct KnownSession(&'static str);
+
+    #[async_trait]
+    impl SessionManager for KnownSession {
+        async fn validate(
+            &self,
+            token: Option<String>,
+        ) -> Result<Option<SessionInfo>, SessionError> {
+            Ok(token.filter(|id| id == self.0).map(|id| SessionInfo {
+                session_id: id,
+                tenant_ctx: build_tenant_ctx("dev", "tenant", None, None),
+                user_id: None,
+                scopes: vec![],
+            }))
+        }
+
+        async fn issue(
+            &self,
+            _ctx: TenantCtx,
+            _flow_id: FlowId,
+            _scopes: Vec<String>,
+        ) -> Result<SessionInfo, SessionError> {
+            Err(SessionError::Provider("not supported".into()))
+        }
+
+        async fn revoke(&self, _session_id: &str) -> Result<(), SessionError> {
+            Ok(())
+        }
+
+        async fn revoke_all_for_user(&self, _ctx: &TenantCtx) -> Result<usize, SessionError> {
+            Ok(0)
+        }
+    }
+
     #[async_trait]
     impl SessionManager for NullSessionManager {
         async fn validate(
@@ -336,13 +682,23 @@ mod tests {
             &self,
             ctx: TenantCtx,
             _flow_id: FlowId,
+            scopes: Vec<String>,
         ) -> Result<SessionInfo, SessionError> {
             Ok(SessionInfo {
                 session_id: "session".into(),
                 tenant_ctx: ctx,
                 user_id: None,
+                scopes,
             })
         }
+
+        async fn revoke(&self, _session_id: &str) -> Result<(), SessionError> {
+            Ok(())
+        }
+
+        async fn revoke_all_for_user(&self, _ctx: &TenantCtx) -> Result<usize, SessionError> {
+            Ok(0)
+        }
     }
 
     struct NullTelemetrySink;
@@ -352,6 +708,16 @@ mod tests {
         async fn record_event(&self, _event: TelemetryEvent) {}
     }
 
+    #[derive(Default)]
+    struct RecordingSink(std::sync::Mutex<Vec<TelemetryEvent>>);
+
+    #[async_trait]
+    impl TelemetrySink for RecordingSink {
+        async fn record_event(&self, event: TelemetryEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
     #[derive(Clone)]
     struct StubWorkerBackend;
 
@@ -397,8 +763,12 @@ fn pick_pack_hint(cfg: &TenantGuiConfig) -> Option<String> {
                 .iter()
                 .find_map(|f| f.location.pack_hint.clone())
         })
-        .or_else(|| cfg.skin.as_ref().and_then(|s| s.pack_hint.clone()))
-        .or_else(|| cfg.telemetry.as_ref().and_then(|t| t.pack_hint.clone()))
+        .or_else(|| cfg.skin.as_ref().and_then(|s| s.location.pack_hint.clone()))
+        .or_else(|| {
+            cfg.telemetry
+                .as_ref()
+                .and_then(|t| t.location.pack_hint.clone())
+        })
 }
 
 #[derive(Debug, Deserialize)]
@@ -422,17 +792,24 @@ pub struct WorkerRequestContext {
 
 pub async fn post_worker_message(
     State(state): State<AppState>,
+    tenant: RequestTenant,
+    headers: HeaderMap,
     Json(body): Json<WorkerMessageRequest>,
 ) -> impl IntoResponse {
     let tenant_ctx = build_tenant_ctx(
         &state.config.env_id,
-        &state.config.default_tenant,
+        &tenant.tenant,
         Some(&state.config.default_team),
         body.context.user_id.as_deref(),
     );
     match state
         .worker_host
-        .invoke_worker(tenant_ctx, &body.worker_id, body.payload)
+        .invoke_worker(
+            tenant_ctx,
+            &body.worker_id,
+            body.payload,
+            TraceContext::from_headers(&headers),
+        )
         .await
     {
         Ok(response) => Json(response).into_response(),
@@ -465,59 +842,313 @@ fn missing_secrets_response(missing: &MissingSecretsError, pack_hint: Option<Str
 pub struct TelemetryRequest {
     pub event_type: String,
     pub path: String,
+    /// Client clock (ms since the epoch) when the event happened.
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+    /// `traceparent` of the page the event happened on.
+    #[serde(default)]
+    pub traceparent: Option<String>,
     #[serde(default)]
     pub metadata: serde_json::Value,
 }
 
+/// Body of `/api/gui/events`: one event, a bare array, or a batch stamped with the client clock
+/// at send time so event timestamps can be corrected for skew.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TelemetryPayload {
+    Batch {
+        #[serde(default)]
+        sent_at: Option<i64>,
+        events: Vec<TelemetryRequest>,
+    },
+    Events(Vec<TelemetryRequest>),
+    Event(TelemetryRequest),
+}
+
+const MAX_EVENT_BATCH: usize = 100;
+
+/// Record telemetry events. Accepts `application/json` and the `text/plain` bodies
+/// `navigator.sendBeacon` sends. A single event answers 403 when its type is not allowed;
+/// batches drop such events and report them as `rejected`.
 pub async fn post_events(
     State(state): State<AppState>,
-    Json(body): Json<TelemetryRequest>,
+    tenant: RequestTenant,
+    peer: Option<Extension<ConnectInfo<SocketAddr>>>,
+    headers: HeaderMap,
+    body: Bytes,
 ) -> impl IntoResponse {
-    crate::integration::set_request_telemetry_ctx(&state.config.default_tenant, None, Some("gui"));
-    let event = TelemetryEvent {
-        event_type: body.event_type,
-        path: body.path,
-        timestamp_ms: chrono::Utc::now().timestamp_millis(),
-        metadata: body.metadata,
+    crate::integration::set_request_telemetry_ctx(&tenant.tenant, None, Some("gui"));
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !content_type.starts_with("application/json") && !content_type.starts_with("text/plain") {
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    }
+    let payload: TelemetryPayload = match serde_json::from_slice(&body) {
+        Ok(payload) => payload,
+        Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+    };
+    let (single, sent_at, events) = match payload {
+        TelemetryPayload::Event(event) => (true, None, vec![event]),
+        TelemetryPayload::Events(events) => (false, None, events),
+        TelemetryPayload::Batch { sent_at, events } => (false, sent_at, events),
     };
-    state.telemetry.record_event(event).await;
-    StatusCode::ACCEPTED
+    if events.len() > MAX_EVENT_BATCH {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            Json(json!({ "error": "batch_too_large", "max_events": MAX_EVENT_BATCH })),
+        )
+            .into_response();
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    // Only validated sessions get their own budget; anonymous visitors and unknown cookies
+    // share one per tenant and client address, so minting cookies does not mint budget.
+    let session_id = match state
+        .session_manager
+        .validate(session_cookie(&headers))
+        .await
+    {
+        Ok(session) => session.map(|session| session.session_id),
+        Err(err) => {
+            tracing::warn!(?err, "session validation failed");
+            None
+        }
+    };
+    let budget = match &session_id {
+        Some(id) => format!("session:{id}"),
+        None => {
+            let peer = peer.map(|Extension(ConnectInfo(addr))| addr);
+            match client_ip(&state.config, &headers, peer) {
+                Some(ip) => format!("anonymous:{}:{ip}", tenant.tenant),
+                None => format!("anonymous:{}", tenant.tenant),
+            }
+        }
+    };
+    let granted = state.take_events(&budget, events.len(), now);
+    if granted == 0 && !events.is_empty() {
+        let retry_after = crate::telemetry::EventRateLimiter::retry_after_secs(now);
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after.to_string())],
+            Json(json!({ "error": "rate_limited" })),
+        )
+            .into_response();
+    }
+
+    let tenant_cfg = match state.load_tenant(&tenant.tenant, &tenant.domain).await {
+        Ok(cfg) => cfg,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+    let policy = tenant_cfg.telemetry_policy();
+    // Session ids are credentials, so telemetry only sees a digest.
+    let ctx = make_telemetry_ctx(
+        &tenant.tenant,
+        session_id.map(|id| digest(&id)).as_deref(),
+        Some("gui"),
+    );
+    // Events name the `gui_request` span of their page. Others join this request's span, which
+    // continues the SDK's trace; beacons cannot send headers, so theirs must name the page.
+    let request_span = tracing::info_span!("gui_events", tenant = %tenant.tenant);
+    let request_trace = TraceContext::from_headers(&headers)
+        .map(|incoming| TraceContext::for_span(&request_span, Some(&incoming)).parent);
+    let dropped = events.len() - granted;
+    let mut accepted = 0;
+    let mut rejected = 0;
+    for event in events.into_iter().take(granted) {
+        if !policy.allows(&event.event_type) {
+            if single {
+                return (
+                    StatusCode::FORBIDDEN,
+                    Json(json!({ "error": "event_not_allowed", "event_type": event.event_type })),
+                )
+                    .into_response();
+            }
+            rejected += 1;
+            continue;
+        }
+        let mut metadata = event.metadata;
+        policy.redact(&mut metadata);
+        let route = route_for_path(&tenant_cfg, &event.path);
+        let event = TelemetryEvent {
+            timestamp_ms: correct_timestamp(event.timestamp, sent_at, now),
+            metadata,
+            ctx: ctx.clone(),
+            route: route.as_ref().and_then(|r| r.pattern.clone()),
+            pack: route.map(|r| r.source.pack_name()),
+            trace: event
+                .traceparent
+                .as_deref()
+                .and_then(TraceParent::parse)
+                .or_else(|| request_trace.clone()),
+            event_type: event.event_type,
+            path: event.path,
+        };
+        state.telemetry.record_event(event).await;
+        accepted += 1;
+    }
+    (
+        StatusCode::ACCEPTED,
+        Json(json!({ "accepted": accepted, "rejected": rejected, "dropped": dropped })),
+    )
+        .into_response()
 }
 
-pub async fn clear_cache(State(state): State<AppState>) -> impl IntoResponse {
-    state.clear_cache().await;
-    StatusCode::NO_CONTENT
+/// Dev-only Server-Sent Events stream of pack changes for the request's tenant.
+pub async fn dev_reload_events(State(state): State<AppState>, tenant: RequestTenant) -> Response {
+    if !state.config.is_dev() {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    }
+    let changes = BroadcastStream::new(state.subscribe_pack_changes());
+    let stream = changes.filter_map(move |change| match change {
+        Ok(change) if change.tenant == tenant.tenant => Event::default()
+            .event("pack-change")
+            .json_data(&change)
+            .ok()
+            .map(Ok::<_, Infallible>),
+        _ => None,
+    });
+    Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response()
 }
 
 #[derive(Debug, Deserialize)]
+pub struct FragmentsQuery {
+    pub path: String,
+}
+
+/// Render the fragments of one route so the SDK can navigate without a full page load.
+pub async fn get_route_fragments(
+    State(state): State<AppState>,
+    tenant: RequestTenant,
+    headers: HeaderMap,
+    Query(query): Query<FragmentsQuery>,
+) -> Response {
+    let tenant_cfg = match state.load_tenant(&tenant.tenant, &tenant.domain).await {
+        Ok(cfg) => cfg,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+    let path = normalize_route(&query.path);
+    let Some(route) = route_for_path(&tenant_cfg, &path) else {
+        return (StatusCode::NOT_FOUND, Json(json!({ "error": "not_found" }))).into_response();
+    };
+    let session = match state
+        .session_manager
+        .validate(session_cookie(&headers))
+        .await
+    {
+        Ok(session) => session,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+    if route.authenticated && session.is_none() {
+        let login = login_redirect(tenant_cfg.auth.as_ref(), &query.path);
+        let body = json!({ "error": "unauthenticated", "login": tenant.url_for(&login) });
+        return (StatusCode::UNAUTHORIZED, Json(body)).into_response();
+    }
+    let fragments = render_fragments(
+        &route.fragments,
+        FragmentRequest {
+            session: session.as_ref(),
+            tenant_did: &tenant_cfg.tenant_did,
+            route: &path,
+            params: &route.params,
+            trace: TraceContext::from_headers(&headers).as_ref(),
+        },
+        state.fragment_renderer.clone(),
+        &state.config.fragment_budget,
+    )
+    .await;
+    Json(json!({
+        "path": path,
+        "pattern": route.pattern,
+        "params": route.params,
+        "fragments": fragments,
+    }))
+    .into_response()
+}
+
+/// Clear cached packs: every tenant's for the service token, only its own tenant's otherwise.
+pub async fn clear_cache(
+    State(state): State<AppState>,
+    Extension(principal): Extension<AdminPrincipal>,
+) -> impl IntoResponse {
+    match &principal.tenant {
+        Some(tenant) => state.clear_tenant_cache(tenant).await,
+        None => state.clear_cache().await,
+    }
+    StatusCode::NO_CONTENT
+}
+
+#[derive(Debug, Default, Deserialize)]
 pub struct SessionIssueRequest {
-    pub user_id: String,
+    /// Only honored in dev; otherwise the user comes from the bearer token.
+    #[serde(default)]
+    pub user_id: Option<String>,
     #[serde(default)]
     pub team: Option<String>,
 }
 
+/// Issue a session cookie.
+///
+/// Callers presenting a bearer token get a session for the validated claims. Without one the
+/// endpoint only works in dev, where `user_id` is taken from the body.
 pub async fn issue_session(
     State(state): State<AppState>,
-    Json(body): Json<SessionIssueRequest>,
+    tenant: RequestTenant,
+    headers: HeaderMap,
+    body: Option<Json<SessionIssueRequest>>,
 ) -> impl IntoResponse {
-    let tenant_ctx = build_tenant_ctx(
-        &state.config.env_id,
-        &state.config.default_tenant,
-        body.team
-            .as_deref()
-            .or(Some(state.config.default_team.as_str())),
-        Some(body.user_id.as_str()),
-    );
+    let body = body.map(|Json(body)| body).unwrap_or_default();
+    let (tenant_ctx, scopes) = if let Some(token) = bearer_token(&headers) {
+        let cfg = match build_validation_config(&state.config) {
+            Ok(cfg) => cfg,
+            Err(err) => {
+                tracing::warn!(?err, "bearer validation is not configured");
+                return session_error(StatusCode::UNAUTHORIZED, "invalid_token");
+            }
+        };
+        let (claims, tenant_ctx) = match validate_bearer_token(&token, &cfg).await {
+            Ok(res) => res,
+            Err(err) => {
+                tracing::debug!(?err, "session bearer rejected");
+                return session_error(StatusCode::UNAUTHORIZED, "invalid_token");
+            }
+        };
+        if claims.tenant_id != tenant.tenant {
+            return session_error(StatusCode::FORBIDDEN, "tenant_mismatch");
+        }
+        (tenant_ctx, claims.scopes)
+    } else if state.config.is_dev() {
+        let Some(user_id) = body.user_id.as_deref().filter(|u| !u.is_empty()) else {
+            return session_error(StatusCode::BAD_REQUEST, "missing_user_id");
+        };
+        let tenant_ctx = build_tenant_ctx(
+            &state.config.env_id,
+            &tenant.tenant,
+            body.team
+                .as_deref()
+                .or(Some(state.config.default_team.as_str())),
+            Some(user_id),
+        );
+        (tenant_ctx, vec![])
+    } else {
+        return session_error(StatusCode::UNAUTHORIZED, "bearer_required");
+    };
+
     let flow_id = greentic_types::FlowId::new("gui").expect("valid flow id");
-    match state.session_manager.issue(tenant_ctx, flow_id).await {
+    match state
+        .session_manager
+        .issue(tenant_ctx, flow_id, scopes)
+        .await
+    {
         Ok(session) => (
             StatusCode::CREATED,
             [(
                 header::SET_COOKIE,
-                format!(
-                    "greentic_session_id={}; Path=/; HttpOnly",
-                    session.session_id
-                ),
+                make_session_cookie(&state.config, &session.session_id),
             )],
             Json(serde_json::json!({
                 "session_id": session.session_id,
@@ -528,3 +1159,14 @@ pub async fn issue_session(
         Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
     }
 }
+
+fn session_error(status: StatusCode, reason: &str) -> Response {
+    let error = if status == StatusCode::FORBIDDEN {
+        "forbidden"
+    } else if status == StatusCode::BAD_REQUEST {
+        "bad_request"
+    } else {
+        "unauthorized"
+    };
+    (status, Json(json!({ "error": error, "reason": reason }))).into_response()
+}
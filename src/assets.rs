@@ -0,0 +1,327 @@
+use crate::domains::RequestTenant;
+use crate::server::AppState;
+use crate::tenant::PackLocation;
+use crate::vfs;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use sha2::{Digest, Sha256};
+use std::path::{Component, Path as FsPath, PathBuf};
+use tracing::{debug, warn};
+
+/// URL prefix under which pack assets are mounted: `/_packs/{kind}/{pack}/{*path}`.
+pub const PACK_ASSET_PREFIX: &str = "/_packs";
+
+const IMMUTABLE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+const REVALIDATE_CACHE_CONTROL: &str = "no-cache";
+
+/// Public URL for the asset root of a mounted pack.
+pub fn pack_asset_url(kind: &str, location: &PackLocation) -> String {
+    format!("{PACK_ASSET_PREFIX}/{kind}/{}/", location.pack_name())
+}
+
+pub async fn serve_pack_asset(
+    State(state): State<AppState>,
+    tenant: RequestTenant,
+    Path((kind, pack, path)): Path<(String, String, String)>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let tenant_cfg = match state.load_tenant(&tenant.tenant, &tenant.domain).await {
+        Ok(cfg) => cfg,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+    let Some(location) = tenant_cfg.pack_location(&kind, &pack) else {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    };
+    // Skin assets shadow layout assets with the same name.
+    if kind == "layout"
+        && let Some(skin) = &tenant_cfg.skin
+        && resolve_asset_path(&skin.location.assets, &path)
+            .await
+            .is_some()
+    {
+        return serve_asset_file(&skin.location.assets, &path, &headers).await;
+    }
+    serve_asset_file(&location.assets, &path, &headers).await
+}
+
+/// Serve `rel_path` from `assets_root`, honoring conditional and range requests.
+pub async fn serve_asset_file(
+    assets_root: &FsPath,
+    rel_path: &str,
+    headers: &HeaderMap,
+) -> Response {
+    let Some(file_path) = resolve_asset_path(assets_root, rel_path).await else {
+        return (StatusCode::NOT_FOUND, "not found").into_response();
+    };
+    let bytes = match vfs::read(&file_path).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            debug!(path = ?file_path, ?err, "asset not readable");
+            return (StatusCode::NOT_FOUND, "not found").into_response();
+        }
+    };
+
+    let etag = strong_etag(&bytes);
+    let cache_control = if is_fingerprinted(&file_path) {
+        IMMUTABLE_CACHE_CONTROL
+    } else {
+        REVALIDATE_CACHE_CONTROL
+    };
+    let content_type = mime_guess::from_path(&file_path)
+        .first_or_octet_stream()
+        .to_string();
+
+    let mut builder = Response::builder()
+        .header(header::ETAG, etag.as_str())
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    if if_none_match(headers, &etag) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap_or_else(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    builder = builder.header(header::CONTENT_TYPE, content_type);
+    let total = bytes.len() as u64;
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(|raw| parse_range(raw, total));
+
+    let response = match range {
+        Some(ByteRange::Satisfiable(start, end)) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {start}-{end}/{total}"),
+            )
+            .body(Body::from(bytes[start as usize..=end as usize].to_vec())),
+        Some(ByteRange::Unsatisfiable) => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{total}"))
+            .body(Body::empty()),
+        Some(ByteRange::Ignored) | None => builder.status(StatusCode::OK).body(Body::from(bytes)),
+    };
+    response.unwrap_or_else(|err| {
+        warn!(?err, "failed to build asset response");
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+/// Resolve a request path under the assets root, rejecting anything that escapes it.
+async fn resolve_asset_path(assets_root: &FsPath, rel_path: &str) -> Option<PathBuf> {
+    if rel_path.contains('\\') || rel_path.contains('\0') {
+        return None;
+    }
+    let rel = FsPath::new(rel_path.trim_start_matches('/'));
+    if rel.as_os_str().is_empty() || rel.components().any(|c| !matches!(c, Component::Normal(_))) {
+        return None;
+    }
+    if vfs::is_archive_path(assets_root) {
+        // Archive entries cannot be symlinks; the component check above keeps them in the root.
+        let candidate = assets_root.join(rel);
+        return vfs::is_file(&candidate).await.then_some(candidate);
+    }
+    let root = tokio::fs::canonicalize(assets_root).await.ok()?;
+    let candidate = tokio::fs::canonicalize(root.join(rel)).await.ok()?;
+    if !candidate.starts_with(&root) {
+        warn!(path = %rel_path, "asset path escapes pack asset root");
+        return None;
+    }
+    let meta = tokio::fs::metadata(&candidate).await.ok()?;
+    meta.is_file().then_some(candidate)
+}
+
+fn strong_etag(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    format!("\"{}\"", hex::encode(&digest[..16]))
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate == etag)
+}
+
+/// Hex digits of the content hash pack builds put into asset names.
+const FINGERPRINT_LEN: usize = 8;
+
+/// A file is fingerprinted when it is named `{name}.{hash}.{ext}` with an 8-digit lowercase hex
+/// content hash, e.g. `app.3f2a9c1b.js`. All-digit hashes are not trusted, so version or date
+/// stamps like `report.20240101.pdf` are revalidated.
+fn is_fingerprinted(path: &FsPath) -> bool {
+    let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+        return false;
+    };
+    if path.extension().is_none_or(|ext| ext.is_empty()) {
+        return false;
+    }
+    let Some((name, hash)) = stem.rsplit_once('.') else {
+        return false;
+    };
+    !name.is_empty()
+        && hash.len() == FINGERPRINT_LEN
+        && hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+        && hash.chars().any(|c| c.is_ascii_alphabetic())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    Satisfiable(u64, u64),
+    Unsatisfiable,
+    /// Malformed or multi-range requests are answered with the full body.
+    Ignored,
+}
+
+fn parse_range(raw: &str, total: u64) -> ByteRange {
+    let Some(spec) = raw.trim().strip_prefix("bytes=") else {
+        return ByteRange::Ignored;
+    };
+    if spec.contains(',') {
+        return ByteRange::Ignored;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return ByteRange::Ignored;
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", "") => return ByteRange::Ignored,
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(len) => (total.saturating_sub(len), total.saturating_sub(1)),
+            Err(_) => return ByteRange::Ignored,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, total.saturating_sub(1)),
+            Err(_) => return ByteRange::Ignored,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(total.saturating_sub(1))),
+            _ => return ByteRange::Ignored,
+        },
+    };
+    if total == 0 || start >= total {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Satisfiable(start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use axum::http::HeaderValue;
+
+    fn assets_dir() -> tempfile::TempDir {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("app.css"), "body { color: red; }").unwrap();
+        std::fs::write(temp.path().join("app.3f2a9c1b.js"), "console.log(1);").unwrap();
+        std::fs::write(temp.path().join("..secret"), "nope").unwrap();
+        temp
+    }
+
+    #[tokio::test]
+    async fn serves_asset_with_mime_and_etag() {
+        let temp = assets_dir();
+        let resp = serve_asset_file(temp.path(), "app.css", &HeaderMap::new()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[header::CONTENT_TYPE], "text/css");
+        assert_eq!(
+            resp.headers()[header::CACHE_CONTROL],
+            REVALIDATE_CACHE_CONTROL
+        );
+        let etag = resp.headers()[header::ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with('"'));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::IF_NONE_MATCH, etag);
+        let resp = serve_asset_file(temp.path(), "app.css", &headers).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    }
+
+    #[tokio::test]
+    async fn fingerprinted_assets_are_immutable() {
+        let temp = assets_dir();
+        let resp = serve_asset_file(temp.path(), "app.3f2a9c1b.js", &HeaderMap::new()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers()[header::CACHE_CONTROL],
+            IMMUTABLE_CACHE_CONTROL
+        );
+    }
+
+    #[tokio::test]
+    async fn serves_byte_ranges() {
+        let temp = assets_dir();
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=0-3"));
+        let resp = serve_asset_file(temp.path(), "app.css", &headers).await;
+        assert_eq!(resp.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(resp.headers()[header::CONTENT_RANGE], "bytes 0-3/20");
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"body");
+
+        headers.insert(header::RANGE, HeaderValue::from_static("bytes=100-"));
+        let resp = serve_asset_file(temp.path(), "app.css", &headers).await;
+        assert_eq!(resp.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    }
+
+    #[tokio::test]
+    async fn rejects_path_traversal() {
+        let temp = assets_dir();
+        let nested = temp.path().join("gui");
+        std::fs::create_dir(&nested).unwrap();
+        for path in [
+            "../app.css",
+            "gui/../../app.css",
+            "/etc/passwd",
+            "..\\app.css",
+        ] {
+            let resp = serve_asset_file(&nested, path, &HeaderMap::new()).await;
+            assert_eq!(
+                resp.status(),
+                StatusCode::NOT_FOUND,
+                "{path} should be rejected"
+            );
+        }
+        let resp = serve_asset_file(temp.path(), "..secret", &HeaderMap::new()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-4", 10), ByteRange::Satisfiable(6, 9));
+        assert_eq!(parse_range("bytes=2-", 10), ByteRange::Satisfiable(2, 9));
+        assert_eq!(parse_range("bytes=0-1,4-5", 10), ByteRange::Ignored);
+    }
+
+    #[test]
+    fn only_build_fingerprints_count() {
+        for name in [
+            "app.3f2a9c1b.js",
+            "vendor.min.0badc0de.css",
+            "logo.abcdef12.svg",
+        ] {
+            assert!(is_fingerprinted(FsPath::new(name)), "{name}");
+        }
+        for name in [
+            "app.js",
+            "app-3f2a9c1b.js",
+            "app.3F2A9C1B.js",
+            "app.3f2a9c1b0d.js",
+            "report.20240101.pdf",
+            "app.3f2a9c1b",
+            ".3f2a9c1b.js",
+            "jquery-ui.deadbeefcafebabe.js",
+        ] {
+            assert!(!is_fingerprinted(FsPath::new(name)), "{name}");
+        }
+    }
+}
@@ -1,22 +1,41 @@
 use crate::config::AppConfig;
+use crate::domains::{RequestTenant, request_host};
+use crate::oauth_flow::{FLOW_COOKIE, FLOW_TTL, FlowError, PendingFlow};
+use crate::routing::RETURN_TO_COOKIE;
 use crate::server::AppState;
-use axum::extract::{Path, State};
-use axum::http::{HeaderMap, StatusCode, header};
-use axum::response::{IntoResponse, Redirect};
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, Method, StatusCode, header};
+use axum::response::{IntoResponse, Redirect, Response};
 use greentic_oauth_client::{Client, ClientBuilder, OwnerKind, StartRequest};
 use greentic_oauth_sdk::{TokenValidationConfig, validate_bearer_token};
 use serde::Deserialize;
 
+#[derive(Debug, Default, Deserialize)]
+pub struct StartQuery {
+    /// Page to restore after login; falls back to the return-to cookie set on redirect.
+    pub return_to: Option<String>,
+}
+
 pub async fn start_auth(
     State(state): State<AppState>,
+    tenant: RequestTenant,
     Path(provider): Path<String>,
+    Query(query): Query<StartQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     match build_oauth_client(&state.config) {
         Ok(client) => {
-            let redirect_uri = format!("/auth/{provider}/callback");
+            let mut flow =
+                PendingFlow::new(&provider, &tenant.tenant, chrono::Utc::now().timestamp());
+            let requested = query.return_to.or_else(|| return_to_cookie(&headers));
+            flow.return_to = match requested {
+                Some(raw) => validated_return_to(&state, &tenant, &raw).await,
+                None => None,
+            };
+            let redirect_uri = tenant.url_for(&format!("/auth/{provider}/callback"));
             let req = StartRequest {
                 env: state.config.env_id.clone(),
-                tenant: state.config.default_tenant.clone(),
+                tenant: tenant.tenant.clone(),
                 provider: provider.clone(),
                 team: Some(state.config.default_team.clone()),
                 owner_kind: OwnerKind::User,
@@ -25,15 +44,27 @@ pub async fn start_auth(
                 scopes: vec!["openid".into(), "profile".into(), "email".into()],
                 redirect_uri: Some(redirect_uri),
                 visibility: None,
-                extra_params: None,
+                extra_params: Some(flow.start_params()),
             };
             match client.start(req).await {
-                Ok(resp) => (
-                    StatusCode::FOUND,
-                    [(header::LOCATION, resp.start_url)],
-                    "redirecting",
-                )
-                    .into_response(),
+                Ok(resp) => {
+                    let mut response = (
+                        StatusCode::FOUND,
+                        [(header::LOCATION, resp.start_url)],
+                        "redirecting",
+                    )
+                        .into_response();
+                    let headers = response.headers_mut();
+                    headers.append(
+                        header::SET_COOKIE,
+                        make_flow_cookie(&state.config, &flow).parse().unwrap(),
+                    );
+                    headers.append(
+                        header::SET_COOKIE,
+                        expired_cookie(RETURN_TO_COOKIE).parse().unwrap(),
+                    );
+                    response
+                }
                 Err(err) => (StatusCode::BAD_GATEWAY, err.to_string()).into_response(),
             }
         }
@@ -43,16 +74,47 @@ pub async fn start_auth(
 
 #[derive(Debug, Deserialize)]
 pub struct CallbackQuery {
+    pub state: Option<String>,
+    /// Authorization code, redeemed at the broker with the flow's PKCE verifier.
+    pub code: Option<String>,
+    /// Token already exchanged by the broker.
     pub id_token: Option<String>,
+    pub error: Option<String>,
 }
 
 pub async fn auth_callback(
     State(state): State<AppState>,
-    Path(_provider): Path<String>,
-    query: axum::extract::Query<CallbackQuery>,
+    tenant: RequestTenant,
+    Path(provider): Path<String>,
+    headers: HeaderMap,
+    Query(query): Query<CallbackQuery>,
 ) -> impl IntoResponse {
-    let Some(id_token) = query.id_token.clone() else {
-        return (StatusCode::BAD_REQUEST, "missing id_token").into_response();
+    if let Some(error) = query.error.as_deref() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            format!("authorization failed: {error}"),
+        )
+            .into_response();
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let flow = match pending_flow(&state, &headers, &query, &provider, &tenant, now) {
+        Ok(flow) => flow,
+        Err(err) => return flow_rejected(err),
+    };
+
+    let id_token = match (query.code.as_deref(), query.id_token) {
+        (Some(code), _) => {
+            let redirect_uri = tenant.url_for(&format!("/auth/{provider}/callback"));
+            match exchange_code(&state.config, code, &flow.code_verifier, &redirect_uri).await {
+                Ok(token) => token,
+                Err(err) => return (StatusCode::BAD_GATEWAY, err.to_string()).into_response(),
+            }
+        }
+        (None, Some(id_token)) => id_token,
+        (None, None) => {
+            return (StatusCode::BAD_REQUEST, "missing code or id_token").into_response();
+        }
     };
 
     let cfg = match build_validation_config(&state.config) {
@@ -60,25 +122,92 @@ pub async fn auth_callback(
         Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
     };
 
-    let (_claims, tenant_ctx) = match validate_bearer_token(&id_token, &cfg).await {
+    let (claims, tenant_ctx) = match validate_bearer_token(&id_token, &cfg).await {
         Ok(res) => res,
         Err(err) => return (StatusCode::UNAUTHORIZED, err.to_string()).into_response(),
     };
+    if let Err(err) = flow.verify_nonce(&id_token) {
+        return flow_rejected(err);
+    }
+    if claims.tenant_id != tenant.tenant {
+        return (StatusCode::FORBIDDEN, "token issued for another tenant").into_response();
+    }
 
     let flow_id = greentic_types::FlowId::new("gui-auth").unwrap();
-    match state.session_manager.issue(tenant_ctx, flow_id).await {
+    match state
+        .session_manager
+        .issue(tenant_ctx, flow_id, claims.scopes)
+        .await
+    {
         Ok(session) => {
             let cookie = make_session_cookie(&state.config, &session.session_id);
-            let mut response = Redirect::to("/").into_response();
-            response
-                .headers_mut()
-                .insert(header::SET_COOKIE, cookie.parse().unwrap());
+            let return_to = flow.return_to.as_deref().unwrap_or("/");
+            let mut response = Redirect::to(&tenant.url_for(return_to)).into_response();
+            let headers = response.headers_mut();
+            headers.append(header::SET_COOKIE, cookie.parse().unwrap());
+            headers.append(
+                header::SET_COOKIE,
+                expired_cookie(FLOW_COOKIE).parse().unwrap(),
+            );
             response
         }
         Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
     }
 }
 
+/// Load the flow cookie, check it against the callback and mark it consumed.
+fn pending_flow(
+    state: &AppState,
+    headers: &HeaderMap,
+    query: &CallbackQuery,
+    provider: &str,
+    tenant: &RequestTenant,
+    now: i64,
+) -> Result<PendingFlow, FlowError> {
+    let cookie = cookie_value(headers, FLOW_COOKIE).ok_or(FlowError::Missing)?;
+    let flow = PendingFlow::decode(&cookie, &state.config.oauth_flow_key, now)?;
+    let echoed = query.state.as_deref().ok_or(FlowError::StateMismatch)?;
+    flow.verify_callback(echoed, provider, &tenant.tenant)?;
+    // Consume before redeeming the code so concurrent replays cannot both succeed.
+    state.consume_oauth_flow(&flow, now)?;
+    Ok(flow)
+}
+
+fn flow_rejected(err: FlowError) -> Response {
+    tracing::warn!(reason = err.reason(), "rejected oauth callback");
+    (StatusCode::BAD_REQUEST, err.to_string()).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Redeem an authorization code at the broker's `/oauth/token` endpoint.
+async fn exchange_code(
+    config: &AppConfig,
+    code: &str,
+    code_verifier: &str,
+    redirect_uri: &str,
+) -> anyhow::Result<String> {
+    let base = config
+        .oauth_broker_url
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("OAUTH_BROKER_URL not set"))?;
+    let url = url::Url::parse(base)?.join("oauth/token")?;
+    let resp = reqwest::Client::new()
+        .post(url)
+        .json(&serde_json::json!({
+            "code": code,
+            "code_verifier": code_verifier,
+            "redirect_uri": redirect_uri,
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(resp.json::<TokenResponse>().await?.id_token)
+}
+
 fn build_oauth_client(config: &AppConfig) -> anyhow::Result<Client> {
     let base = config
         .oauth_broker_url
@@ -87,7 +216,7 @@ fn build_oauth_client(config: &AppConfig) -> anyhow::Result<Client> {
     Ok(ClientBuilder::new().base_url(base)?.build()?)
 }
 
-fn build_validation_config(config: &AppConfig) -> anyhow::Result<TokenValidationConfig> {
+pub fn build_validation_config(config: &AppConfig) -> anyhow::Result<TokenValidationConfig> {
     let jwks = config
         .oauth_jwks_url
         .clone()
@@ -110,25 +239,130 @@ fn build_validation_config(config: &AppConfig) -> anyhow::Result<TokenValidation
     Ok(cfg)
 }
 
-fn make_session_cookie(config: &AppConfig, session_id: &str) -> String {
+/// Session cookie policy shared by every endpoint that establishes a session.
+pub fn make_session_cookie(config: &AppConfig, session_id: &str) -> String {
     let mut cookie = format!(
         "greentic_session_id={}; Path=/; HttpOnly; SameSite=Lax",
         session_id
     );
+    if is_https(config) {
+        cookie.push_str("; Secure");
+    }
     if !config.session_ttl.is_zero() {
         cookie.push_str(&format!("; Max-Age={}", config.session_ttl.as_secs()));
     }
     cookie
 }
 
-pub async fn logout(State(_state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
-    let mut response = Redirect::to("/").into_response();
-    if let Some(session_id) = session_cookie(&headers) {
-        // Best-effort: drop cookie; store cleanup is handled elsewhere.
-        let expired = format!(
-            "greentic_session_id={}; Path=/; Expires=Thu, 01 Jan 1970 00:00:00 GMT; HttpOnly; SameSite=Lax",
-            session_id
-        );
+/// Short-lived cookie carrying the signed login flow; `Lax` so it survives the IdP redirect.
+fn make_flow_cookie(config: &AppConfig, flow: &PendingFlow) -> String {
+    let mut cookie = format!(
+        "{FLOW_COOKIE}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        flow.encode(&config.oauth_flow_key),
+        FLOW_TTL.as_secs()
+    );
+    if is_https(config) {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+/// Remembers the page that triggered a login redirect until `/auth/{provider}/start`.
+pub fn make_return_to_cookie(config: &AppConfig, requested: &str) -> String {
+    let encoded: String = url::form_urlencoded::byte_serialize(requested.as_bytes()).collect();
+    let mut cookie = format!(
+        "{RETURN_TO_COOKIE}={encoded}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        FLOW_TTL.as_secs()
+    );
+    if is_https(config) {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+fn return_to_cookie(headers: &HeaderMap) -> Option<String> {
+    let raw = cookie_value(headers, RETURN_TO_COOKIE)?;
+    url::form_urlencoded::parse(format!("v={raw}").as_bytes())
+        .next()
+        .map(|(_, value)| value.into_owned())
+}
+
+async fn validated_return_to(
+    state: &AppState,
+    tenant: &RequestTenant,
+    raw: &str,
+) -> Option<String> {
+    let cfg = state
+        .load_tenant(&tenant.tenant, &tenant.domain)
+        .await
+        .ok()?;
+    let target = cfg.safe_return_to(raw);
+    if target.is_none() {
+        tracing::debug!(return_to = %raw, "ignoring unsafe return_to");
+    }
+    target
+}
+
+fn expired_cookie(name: &str) -> String {
+    format!("{name}=; Path=/; Max-Age=0; HttpOnly; SameSite=Lax")
+}
+
+fn is_https(config: &AppConfig) -> bool {
+    config
+        .public_base_url
+        .as_deref()
+        .is_some_and(|url| url.starts_with("https://"))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogoutQuery {
+    /// Revoke every session of the user, not just this one.
+    #[serde(default)]
+    pub all: bool,
+}
+
+/// Revoke the session server-side, expire the cookie, and hand off to the auth pack's
+/// end-session endpoint when one is declared.
+///
+/// `POST` must come from a same-origin page. Signing out of every device (`all=true`) is
+/// `POST`-only, so a cross-site link or image cannot trigger it.
+pub async fn logout(
+    State(state): State<AppState>,
+    tenant: RequestTenant,
+    method: Method,
+    Query(query): Query<LogoutQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if query.all && method != Method::POST {
+        return (
+            StatusCode::METHOD_NOT_ALLOWED,
+            [(header::ALLOW, "POST")],
+            "logging out everywhere requires POST",
+        )
+            .into_response();
+    }
+    if method == Method::POST && !same_origin(&state.config, &headers) {
+        return (StatusCode::FORBIDDEN, "cross-origin logout rejected").into_response();
+    }
+
+    let session_id = session_cookie(&headers);
+    if let Some(session_id) = &session_id
+        && let Err(err) = revoke_session(&state, session_id, query.all).await
+    {
+        tracing::warn!(?err, "failed to revoke session on logout");
+    }
+
+    let target = match end_session_redirect(&state, &tenant).await {
+        Some(url) => url,
+        None => tenant.url_for("/"),
+    };
+    let mut response = Redirect::to(&target).into_response();
+    if session_id.is_some() {
+        let mut expired =
+            "greentic_session_id=; Path=/; Max-Age=0; HttpOnly; SameSite=Lax".to_string();
+        if is_https(&state.config) {
+            expired.push_str("; Secure");
+        }
         response
             .headers_mut()
             .insert(header::SET_COOKIE, expired.parse().unwrap());
@@ -136,15 +370,443 @@ pub async fn logout(State(_state): State<AppState>, headers: HeaderMap) -> impl
     response
 }
 
-fn session_cookie(headers: &HeaderMap) -> Option<String> {
+/// Whether the request's `Origin` (or, without one, `Referer`) is the host it was sent to.
+fn same_origin(config: &AppConfig, headers: &HeaderMap) -> bool {
+    let Some(host) = request_host(config, headers) else {
+        return false;
+    };
     headers
-        .get(header::COOKIE)
-        .and_then(|v| v.to_str().ok())
-        .and_then(|cookies| {
-            cookies
-                .split(';')
-                .map(|c| c.trim())
-                .find_map(|c| c.strip_prefix("greentic_session_id="))
-                .map(|s| s.to_string())
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| url::Url::parse(value).ok())
+        .is_some_and(|source| {
+            let authority = match (source.host_str(), source.port()) {
+                (Some(h), Some(port)) => format!("{h}:{port}"),
+                (Some(h), None) => h.to_string(),
+                (None, _) => return false,
+            };
+            authority.eq_ignore_ascii_case(&host)
         })
 }
+
+async fn revoke_session(
+    state: &AppState,
+    session_id: &str,
+    all: bool,
+) -> Result<(), crate::integration::SessionError> {
+    if all
+        && let Some(session) = state
+            .session_manager
+            .validate(Some(session_id.to_string()))
+            .await?
+    {
+        state
+            .session_manager
+            .revoke_all_for_user(&session.tenant_ctx)
+            .await?;
+    }
+    state.session_manager.revoke(session_id).await
+}
+
+async fn end_session_redirect(state: &AppState, tenant: &RequestTenant) -> Option<String> {
+    let cfg = state
+        .load_tenant(&tenant.tenant, &tenant.domain)
+        .await
+        .ok()?;
+    let mut url = cfg.auth?.manifest.end_session_url()?;
+    if let Some(base) = &state.config.public_base_url {
+        let home = format!("{}{}", base.trim_end_matches('/'), tenant.url_for("/"));
+        url.query_pairs_mut()
+            .append_pair("post_logout_redirect_uri", &home);
+    }
+    Some(url.to_string())
+}
+
+/// Token from an `Authorization: Bearer <token>` header.
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then(|| token.to_string())
+}
+
+/// Compare secrets without leaking the position of the first difference.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn session_cookie(headers: &HeaderMap) -> Option<String> {
+    cookie_value(headers, "greentic_session_id")
+}
+
+fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|c| c.trim().strip_prefix(name)?.strip_prefix('='))
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fragments::CompositeFragmentRenderer;
+    use crate::integration::{NullTelemetrySink, StubSessionManager};
+    use crate::oauth_flow::pkce_challenge;
+    use crate::packs::FsPackProvider;
+    use crate::worker::{StubWorkerBackend, WorkerHost};
+    use axum::Json;
+    use axum::body::Body;
+    use axum::http::Request;
+    use axum::routing::{get, post};
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use serde_json::{Value, json};
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use tower::ServiceExt;
+
+    const JWT_SECRET: &[u8] = b"stand-in-broker-secret";
+    const ISSUER: &str = "https://issuer.test";
+    const AUDIENCE: &str = "greentic-gui";
+
+    fn id_token(nonce: &str) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("broker".into());
+        let claims = json!({
+            "iss": ISSUER,
+            "aud": AUDIENCE,
+            "exp": chrono::Utc::now().timestamp() + 300,
+            "tenant_id": "tenant",
+            "user_id": "alice",
+            "env": "dev",
+            "nonce": nonce,
+        });
+        jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(JWT_SECRET)).unwrap()
+    }
+
+    /// Stand-in broker: records the start parameters, redeems one code with PKCE, serves JWKS.
+    async fn spawn_broker() -> String {
+        let started: Arc<Mutex<BTreeMap<String, String>>> = Arc::default();
+        let on_start = started.clone();
+        let on_token = started.clone();
+        let app = axum::Router::new()
+            .route(
+                "/oauth/start",
+                post(move |Json(body): Json<Value>| async move {
+                    let params: BTreeMap<String, String> =
+                        serde_json::from_value(body["extra_params"].clone()).unwrap();
+                    let state = params["state"].clone();
+                    *on_start.lock().unwrap() = params;
+                    Json(
+                        json!({ "start_url": format!("https://idp.test/authorize?state={state}") }),
+                    )
+                }),
+            )
+            .route(
+                "/oauth/token",
+                post(move |Json(body): Json<Value>| async move {
+                    let params = on_token.lock().unwrap().clone();
+                    let verifier = body["code_verifier"].as_str().unwrap_or_default();
+                    if body["code"] != "auth-code"
+                        || params.get("code_challenge") != Some(&pkce_challenge(verifier))
+                    {
+                        return (
+                            StatusCode::BAD_REQUEST,
+                            Json(json!({ "error": "invalid_grant" })),
+                        );
+                    }
+                    (
+                        StatusCode::OK,
+                        Json(json!({ "id_token": id_token(&params["nonce"]) })),
+                    )
+                }),
+            )
+            .route(
+                "/jwks",
+                get(|| async {
+                    Json(json!({ "keys": [{
+                        "kty": "oct",
+                        "alg": "HS256",
+                        "kid": "broker",
+                        "k": URL_SAFE_NO_PAD.encode(JWT_SECRET),
+                    }]}))
+                }),
+            );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    fn write_manifest(root: &std::path::Path, pack: &str, manifest: Value) {
+        let gui = root.join("tenant").join(pack).join("gui");
+        std::fs::create_dir_all(&gui).unwrap();
+        std::fs::write(gui.join("manifest.json"), manifest.to_string()).unwrap();
+    }
+
+    fn write_layout(root: &std::path::Path) {
+        write_manifest(
+            root,
+            "layout",
+            json!({
+                "kind": "gui-layout",
+                "layout": { "slots": [], "entrypoint_html": "index.html", "spa": false, "slot_selectors": {} },
+            }),
+        );
+    }
+
+    async fn test_app(packs: &std::path::Path) -> axum::Router {
+        let broker = spawn_broker().await;
+        let mut config = crate::config::test_config();
+        config.oauth_broker_url = Some(format!("{broker}/"));
+        config.oauth_jwks_url = Some(format!("{broker}/jwks"));
+        config.oauth_issuer = Some(ISSUER.into());
+        config.oauth_audience = Some(AUDIENCE.into());
+        let state = AppState::new(
+            config,
+            Arc::new(FsPackProvider::new(packs.to_path_buf())),
+            Arc::new(CompositeFragmentRenderer::file_only()),
+            Arc::new(StubSessionManager),
+            Arc::new(NullTelemetrySink),
+            Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+        );
+        crate::server::router(state)
+    }
+
+    /// Start a login and return `(state, flow cookie)`.
+    async fn start(app: &axum::Router, query: &str, cookie: Option<&str>) -> (String, String) {
+        let mut req =
+            Request::get(format!("/auth/google/start{query}")).header(header::HOST, "localhost");
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        let resp = app
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let location = resp.headers()[header::LOCATION].to_str().unwrap();
+        let state = location.split("state=").nth(1).unwrap().to_string();
+        let cookie = resp.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.contains("HttpOnly; SameSite=Lax; Max-Age=600"));
+        let cookie = cookie.split(';').next().unwrap().to_string();
+        (state, cookie)
+    }
+
+    async fn callback(app: &axum::Router, query: &str, cookie: Option<&str>) -> Response {
+        let mut req = Request::get(format!("/auth/google/callback?{query}"))
+            .header(header::HOST, "localhost");
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        app.clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn logout_revokes_session_and_follows_end_session_endpoint() {
+        use crate::integration::{RealSessionManager, SessionManager, build_tenant_ctx};
+        use greentic_session::inmemory::InMemorySessionStore;
+
+        let temp = tempfile::tempdir().unwrap();
+        write_layout(temp.path());
+        write_manifest(
+            temp.path(),
+            "auth",
+            json!({
+                "kind": "gui-auth",
+                "routes": [],
+                "oauth": { "end_session_endpoint": "https://idp.test/logout" },
+                "ui_bindings": {},
+            }),
+        );
+
+        let mut config = crate::config::test_config();
+        config.public_base_url = Some("https://gui.test".into());
+        let sessions = Arc::new(RealSessionManager::new(Arc::new(
+            InMemorySessionStore::new(),
+        )));
+        let state = AppState::new(
+            config,
+            Arc::new(FsPackProvider::new(temp.path().to_path_buf())),
+            Arc::new(CompositeFragmentRenderer::file_only()),
+            sessions.clone(),
+            Arc::new(NullTelemetrySink),
+            Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+        );
+        let ctx = build_tenant_ctx("dev", "tenant", None, Some("alice"));
+        let flow = || greentic_types::FlowId::new("gui").unwrap();
+        let session = sessions.issue(ctx.clone(), flow(), vec![]).await.unwrap();
+        let other = sessions.issue(ctx, flow(), vec![]).await.unwrap();
+
+        let logout = |query: &str, session_id: &str| {
+            Request::get(format!("/auth/logout{query}"))
+                .header(header::HOST, "localhost")
+                .header(header::COOKIE, format!("greentic_session_id={session_id}"))
+                .body(Body::empty())
+                .unwrap()
+        };
+        let app = crate::server::router(state);
+        let resp = app
+            .clone()
+            .oneshot(logout("", &session.session_id))
+            .await
+            .unwrap();
+        assert!(resp.status().is_redirection());
+        assert_eq!(
+            resp.headers()[header::LOCATION],
+            "https://idp.test/logout?post_logout_redirect_uri=https%3A%2F%2Fgui.test%2F"
+        );
+        let cookie = resp.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with("greentic_session_id=; Path=/; Max-Age=0"));
+        assert!(cookie.ends_with("; Secure"));
+        let validate = |id: &str| sessions.validate(Some(id.to_string()));
+        assert!(validate(&session.session_id).await.unwrap().is_none());
+        assert!(validate(&other.session_id).await.unwrap().is_some());
+
+        // Signing out everywhere needs a same-origin POST.
+        let resp = app
+            .clone()
+            .oneshot(logout("?all=true", &other.session_id))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+        let logout_all = |origin: &str| {
+            Request::post("/auth/logout?all=true")
+                .header(header::HOST, "localhost")
+                .header(header::ORIGIN, origin)
+                .header(
+                    header::COOKIE,
+                    format!("greentic_session_id={}", other.session_id),
+                )
+                .body(Body::empty())
+                .unwrap()
+        };
+        let resp = app
+            .clone()
+            .oneshot(logout_all("https://evil.test"))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(validate(&other.session_id).await.unwrap().is_some());
+
+        let resp = app.oneshot(logout_all("http://localhost")).await.unwrap();
+        assert!(resp.status().is_redirection());
+        assert!(validate(&other.session_id).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn pkce_code_flow_issues_session_once() {
+        let temp = tempfile::tempdir().unwrap();
+        write_layout(temp.path());
+        let app = test_app(temp.path()).await;
+        let (state, cookie) = start(&app, "", None).await;
+        let query = format!("state={state}&code=auth-code");
+
+        let resp = callback(&app, &query, Some(&cookie)).await;
+        assert!(resp.status().is_redirection(), "{}", resp.status());
+        assert_eq!(resp.headers()[header::LOCATION], "/");
+        let cookies: Vec<_> = resp
+            .headers()
+            .get_all(header::SET_COOKIE)
+            .iter()
+            .map(|v| v.to_str().unwrap().to_string())
+            .collect();
+        assert!(
+            cookies
+                .iter()
+                .any(|c| c.starts_with("greentic_session_id="))
+        );
+        assert!(
+            cookies
+                .iter()
+                .any(|c| c.starts_with("greentic_oauth_flow=;"))
+        );
+
+        let replay = callback(&app, &query, Some(&cookie)).await;
+        assert_eq!(replay.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn restores_requested_page_after_login() {
+        let temp = tempfile::tempdir().unwrap();
+        write_layout(temp.path());
+        write_manifest(
+            temp.path(),
+            "invoices",
+            json!({
+                "kind": "gui-feature",
+                "routes": [{ "path": "/invoices", "authenticated": true, "html": "invoices.html" }],
+            }),
+        );
+        let app = test_app(temp.path()).await;
+
+        // An auth-pack login page that drops `return_to` still round-trips via the cookie.
+        let req = Request::get("/invoices?id=7")
+            .header(header::HOST, "localhost")
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(
+            resp.headers()[header::LOCATION],
+            "/login?return_to=%2Finvoices%3Fid%3D7"
+        );
+        let return_cookie = resp.headers()[header::SET_COOKIE].to_str().unwrap();
+        let return_cookie = return_cookie.split(';').next().unwrap().to_string();
+
+        let (state, cookie) = start(&app, "", Some(&return_cookie)).await;
+        let resp = callback(
+            &app,
+            &format!("state={state}&code=auth-code"),
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(resp.headers()[header::LOCATION], "/invoices?id=7");
+
+        // Off-origin or unknown targets fall back to the root.
+        for unsafe_target in ["%2F%2Fevil.test%2Finvoices", "%2Fnope"] {
+            let (state, cookie) = start(&app, &format!("?return_to={unsafe_target}"), None).await;
+            let resp = callback(
+                &app,
+                &format!("state={state}&code=auth-code"),
+                Some(&cookie),
+            )
+            .await;
+            assert_eq!(resp.headers()[header::LOCATION], "/");
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_csrf_and_injected_tokens() {
+        let temp = tempfile::tempdir().unwrap();
+        write_layout(temp.path());
+        let app = test_app(temp.path()).await;
+        let (state, cookie) = start(&app, "", None).await;
+
+        let missing_cookie = callback(&app, &format!("state={state}&code=auth-code"), None).await;
+        assert_eq!(missing_cookie.status(), StatusCode::BAD_REQUEST);
+        let wrong_state = callback(&app, "state=forged&code=auth-code", Some(&cookie)).await;
+        assert_eq!(wrong_state.status(), StatusCode::BAD_REQUEST);
+
+        // A validly signed token minted for another login must not be accepted.
+        let (state, cookie) = start(&app, "", None).await;
+        let injected = id_token("someone-elses-nonce");
+        let resp = callback(
+            &app,
+            &format!("state={state}&id_token={injected}"),
+            Some(&cookie),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(resp.headers().get(header::SET_COOKIE).is_none());
+    }
+}
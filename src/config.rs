@@ -1,3 +1,6 @@
+use crate::domains::DomainMap;
+use crate::fragments::{FragmentBudget, FragmentLimits};
+use crate::oauth_flow::FlowKey;
 use greentic_config::{ConfigLayer, ConfigResolver};
 use greentic_config_types::{GreenticConfig, PackSourceConfig};
 use std::net::SocketAddr;
@@ -11,8 +14,22 @@ pub struct AppConfig {
     pub public_base_url: Option<String>,
     pub pack_root: PathBuf,
     pub default_tenant: String,
+    /// Host/path to tenant map; empty means every request uses `default_tenant`.
+    pub domains: DomainMap,
     pub enable_cors: bool,
     pub pack_cache_ttl: Duration,
+    /// Watch `pack_root` and reload tenants on change; cached tenants then never expire.
+    pub watch_packs: bool,
+    /// Reject tenant configs with route conflicts instead of only warning about them.
+    pub strict_routes: bool,
+    /// Telemetry events recorded per session and minute; 0 disables the limit.
+    pub telemetry_rate_limit: u32,
+    /// OTLP/HTTP collector base URL that GUI telemetry events are exported to.
+    pub otlp_events_endpoint: Option<String>,
+    /// Time, fuel and memory budget for each Wasmtime fragment render.
+    pub fragment_limits: FragmentLimits,
+    /// Concurrency and overall deadline for rendering one page's fragments.
+    pub fragment_budget: FragmentBudget,
     pub session_ttl: Duration,
     pub env_id: String,
     pub default_team: String,
@@ -22,9 +39,17 @@ pub struct AppConfig {
     pub oauth_audience: Option<String>,
     pub oauth_jwks_url: Option<String>,
     pub oauth_required_scopes: Vec<String>,
+    /// Scope a session or bearer token must carry to call the admin endpoints.
+    pub oauth_admin_scope: String,
+    /// Static service token accepted on the admin endpoints (for automation).
+    pub admin_token: Option<String>,
+    /// Signs OAuth login flow cookies; random per process unless `GUI_COOKIE_SECRET` is set.
+    pub oauth_flow_key: FlowKey,
     pub resolved: GreenticConfig,
 }
 
+pub const DEFAULT_ADMIN_SCOPE: &str = "gui.admin";
+
 #[derive(Debug, Clone)]
 pub struct DistributorConfig {
     pub base_url: String,
@@ -43,9 +68,17 @@ pub struct LoadedConfig {
 }
 
 impl AppConfig {
-    pub fn tenant_for_domain<'a>(&'a self, _domain: &'a str) -> &'a str {
-        // No domain map yet; default to configured tenant.
-        &self.default_tenant
+    /// Dev-only endpoints (such as live reload) are served in the `dev` environment.
+    pub fn is_dev(&self) -> bool {
+        self.env_id == "dev"
+    }
+
+    /// Tenant for a request host; `None` when a domain map is configured but nothing matches.
+    pub fn tenant_for_domain(&self, domain: &str) -> Option<&str> {
+        if self.domains.is_empty() {
+            return Some(&self.default_tenant);
+        }
+        self.domains.tenant_for_host(domain)
     }
 }
 
@@ -65,7 +98,8 @@ pub fn load_config(
     }
 
     let resolved = resolver.load()?;
-    let app = map_to_app_config(resolved.config.clone(), cli);
+    let mut app = map_to_app_config(resolved.config.clone(), cli);
+    app.domains = DomainMap::load(&domain_map_path(&app.resolved))?;
     Ok(LoadedConfig {
         app,
         provenance: resolved.provenance,
@@ -100,8 +134,23 @@ fn map_to_app_config(resolved: GreenticConfig, cli: &crate::CliArgs) -> AppConfi
         public_base_url,
         pack_root,
         default_tenant,
+        domains: DomainMap::default(),
         enable_cors: false,
         pack_cache_ttl: Duration::from_secs(0),
+        watch_packs: cli.watch_packs,
+        strict_routes: cli.strict_routes,
+        telemetry_rate_limit: cli.telemetry_rate_limit,
+        otlp_events_endpoint: cli.otlp_events_endpoint.clone(),
+        fragment_limits: FragmentLimits {
+            timeout: Duration::from_millis(cli.fragment_timeout_ms),
+            fuel: (cli.fragment_fuel > 0).then_some(cli.fragment_fuel),
+            max_memory_bytes: cli.fragment_memory_mb << 20,
+            ..FragmentLimits::default()
+        },
+        fragment_budget: FragmentBudget {
+            concurrency: cli.fragment_concurrency,
+            page_timeout: Duration::from_millis(cli.fragment_page_timeout_ms),
+        },
         session_ttl: Duration::from_secs(0),
         env_id,
         default_team,
@@ -111,6 +160,18 @@ fn map_to_app_config(resolved: GreenticConfig, cli: &crate::CliArgs) -> AppConfi
         oauth_audience: std::env::var("OAUTH_AUDIENCE").ok(),
         oauth_jwks_url: std::env::var("OAUTH_JWKS_URL").ok(),
         oauth_required_scopes: oauth_required_scopes(),
+        oauth_admin_scope: std::env::var("OAUTH_ADMIN_SCOPE")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_ADMIN_SCOPE.to_string()),
+        admin_token: std::env::var("GUI_ADMIN_TOKEN")
+            .ok()
+            .filter(|s| !s.is_empty()),
+        oauth_flow_key: std::env::var("GUI_COOKIE_SECRET")
+            .ok()
+            .filter(|s| !s.is_empty())
+            .map(FlowKey::new)
+            .unwrap_or_else(FlowKey::random),
         resolved,
     }
 }
@@ -136,6 +197,14 @@ fn distributor_from_config(resolved: &GreenticConfig) -> Option<DistributorConfi
         })
 }
 
+fn domain_map_path(resolved: &GreenticConfig) -> PathBuf {
+    resolved
+        .paths
+        .greentic_root
+        .join("gui")
+        .join("domains.toml")
+}
+
 fn oauth_required_scopes() -> Vec<String> {
     std::env::var("OAUTH_REQUIRED_SCOPES")
         .ok()
@@ -152,3 +221,58 @@ fn project_root_from_cwd() -> anyhow::Result<Option<PathBuf>> {
     let cwd = std::env::current_dir()?;
     Ok(greentic_config::discover_project_root(&cwd))
 }
+
+#[cfg(test)]
+pub fn test_config() -> AppConfig {
+    AppConfig {
+        bind_addr: "127.0.0.1:0".parse::<SocketAddr>().unwrap(),
+        public_base_url: None,
+        pack_root: PathBuf::from("./packs"),
+        default_tenant: "tenant".into(),
+        domains: DomainMap::default(),
+        enable_cors: false,
+        pack_cache_ttl: Duration::from_secs(0),
+        watch_packs: false,
+        strict_routes: false,
+        telemetry_rate_limit: 600,
+        otlp_events_endpoint: None,
+        fragment_limits: FragmentLimits::default(),
+        fragment_budget: FragmentBudget::default(),
+        session_ttl: Duration::from_secs(0),
+        env_id: "dev".into(),
+        default_team: "team".into(),
+        distributor: None,
+        oauth_broker_url: None,
+        oauth_issuer: None,
+        oauth_audience: None,
+        oauth_jwks_url: None,
+        oauth_required_scopes: vec![],
+        oauth_admin_scope: DEFAULT_ADMIN_SCOPE.into(),
+        admin_token: None,
+        oauth_flow_key: FlowKey::new(b"test-flow-key"),
+        resolved: GreenticConfig {
+            schema_version: greentic_config_types::ConfigVersion::v1(),
+            environment: greentic_config_types::EnvironmentConfig {
+                env_id: greentic_types::EnvId::new("dev").unwrap(),
+                deployment: None,
+                connection: None,
+                region: None,
+            },
+            paths: greentic_config_types::PathsConfig {
+                greentic_root: PathBuf::from("."),
+                state_dir: PathBuf::from("."),
+                cache_dir: PathBuf::from("."),
+                logs_dir: PathBuf::from("."),
+            },
+            packs: None,
+            services: None,
+            events: None,
+            runtime: greentic_config_types::RuntimeConfig::default(),
+            telemetry: greentic_config_types::TelemetryConfig::default(),
+            network: greentic_config_types::NetworkConfig::default(),
+            deployer: None,
+            secrets: greentic_config_types::SecretsBackendRefConfig::default(),
+            dev: None,
+        },
+    }
+}
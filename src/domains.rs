@@ -0,0 +1,370 @@
+use crate::config::AppConfig;
+use crate::routing::escape_html;
+use crate::server::AppState;
+use anyhow::Context;
+use axum::Json;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, StatusCode, Uri, header};
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use serde::Deserialize;
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+
+/// Host/path to tenant mapping loaded from `gui/domains.toml` under the greentic root.
+///
+/// ```toml
+/// [[domain]]
+/// host = "acme.example"
+/// tenant = "acme"
+///
+/// [[domain]]
+/// host = "*.acme.example"
+/// tenant = "acme"
+///
+/// [[domain]]
+/// path_prefix = "/t/globex"
+/// tenant = "globex"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct DomainMap {
+    #[serde(default, rename = "domain")]
+    pub rules: Vec<DomainRule>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DomainRule {
+    /// Exact host (`acme.example`) or wildcard subdomain (`*.acme.example`).
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Path prefix that selects the tenant; stripped before routing.
+    #[serde(default)]
+    pub path_prefix: Option<String>,
+    pub tenant: String,
+}
+
+impl DomainMap {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("reading domain map {:?}", path))?;
+        let map: DomainMap =
+            toml::from_str(&contents).with_context(|| format!("parsing domain map {:?}", path))?;
+        Ok(map)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Resolve a tenant from host rules only; exact hosts win over the longest wildcard.
+    pub fn tenant_for_host(&self, host: &str) -> Option<&str> {
+        let host = normalize_host(host);
+        let mut best: Option<(&DomainRule, usize)> = None;
+        for rule in self.rules.iter().filter(|r| r.path_prefix.is_none()) {
+            let Some(pattern) = rule.host.as_deref() else {
+                continue;
+            };
+            let Some(score) = host_match_score(pattern, &host) else {
+                continue;
+            };
+            if best.is_none_or(|(_, s)| score > s) {
+                best = Some((rule, score));
+            }
+        }
+        best.map(|(rule, _)| rule.tenant.as_str())
+    }
+
+    /// Resolve a path-prefix tenant, returning the tenant and the matched prefix.
+    pub fn tenant_for_path(&self, host: &str, path: &str) -> Option<(&str, &str)> {
+        let host = normalize_host(host);
+        self.rules
+            .iter()
+            .filter_map(|rule| {
+                let prefix = rule.path_prefix.as_deref()?.trim_end_matches('/');
+                if prefix.is_empty() || !prefix_matches(prefix, path) {
+                    return None;
+                }
+                if let Some(pattern) = rule.host.as_deref()
+                    && host_match_score(pattern, &host).is_none()
+                {
+                    return None;
+                }
+                Some((rule.tenant.as_str(), prefix))
+            })
+            .max_by_key(|(_, prefix)| prefix.len())
+    }
+}
+
+fn normalize_host(host: &str) -> String {
+    let host = host.trim().to_ascii_lowercase();
+    if let Some(rest) = host.strip_prefix('[') {
+        // IPv6 literal, optionally followed by a port.
+        return rest.split(']').next().unwrap_or_default().to_string();
+    }
+    host.split(':').next().unwrap_or_default().to_string()
+}
+
+/// Exact matches score above any wildcard; longer wildcard suffixes score higher.
+fn host_match_score(pattern: &str, host: &str) -> Option<usize> {
+    let pattern = normalize_host(pattern);
+    if let Some(suffix) = pattern.strip_prefix("*.") {
+        let sub = host.strip_suffix(suffix)?;
+        return (sub.len() > 1 && sub.ends_with('.')).then_some(suffix.len());
+    }
+    (pattern == host).then_some(usize::MAX)
+}
+
+fn prefix_matches(prefix: &str, path: &str) -> bool {
+    path.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Host the request was addressed to, honoring `X-Forwarded-Host` behind a configured proxy.
+pub fn request_host(config: &AppConfig, headers: &HeaderMap) -> Option<String> {
+    if config.public_base_url.is_some()
+        && let Some(forwarded) = headers
+            .get("x-forwarded-host")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    {
+        return Some(forwarded.to_string());
+    }
+    headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+/// Client address of the request. Behind a proxy (`public_base_url` set) this is the last
+/// `X-Forwarded-For` hop, which the proxy appended; otherwise the peer address.
+pub fn client_ip(
+    config: &AppConfig,
+    headers: &HeaderMap,
+    peer: Option<SocketAddr>,
+) -> Option<IpAddr> {
+    if config.public_base_url.is_some()
+        && let Some(forwarded) = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|v| v.trim().parse().ok())
+    {
+        return Some(forwarded);
+    }
+    peer.map(|peer| peer.ip())
+}
+
+/// Tenant resolved for the current request.
+#[derive(Debug, Clone)]
+pub struct RequestTenant {
+    pub tenant: String,
+    pub domain: String,
+    /// Path prefix stripped from the request URI for path-prefix tenants.
+    pub path_prefix: Option<String>,
+}
+
+impl RequestTenant {
+    /// Prefix an absolute in-app path with the tenant's path prefix, if any.
+    pub fn url_for(&self, path: &str) -> String {
+        match &self.path_prefix {
+            Some(prefix) => format!("{prefix}{path}"),
+            None => path.to_string(),
+        }
+    }
+
+    /// `<meta name="greentic-base">` naming the path prefix for the SDK; empty without one.
+    pub fn base_meta(&self) -> String {
+        match &self.path_prefix {
+            Some(prefix) => format!(
+                "<meta name=\"greentic-base\" content=\"{}\">",
+                escape_html(prefix)
+            ),
+            None => String::new(),
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for RequestTenant {
+    type Rejection = UnknownTenant;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        if let Some(tenant) = parts.extensions.get::<RequestTenant>() {
+            return Ok(tenant.clone());
+        }
+        let domain = request_host(&state.config, &parts.headers).unwrap_or_default();
+        match state.config.tenant_for_domain(&domain) {
+            Some(tenant) => Ok(RequestTenant {
+                tenant: tenant.to_string(),
+                domain,
+                path_prefix: None,
+            }),
+            None => Err(UnknownTenant {
+                domain,
+                api: parts.uri.path().starts_with("/api/"),
+            }),
+        }
+    }
+}
+
+/// Rejection returned when no tenant is mapped to the request host.
+#[derive(Debug)]
+pub struct UnknownTenant {
+    pub domain: String,
+    pub api: bool,
+}
+
+impl IntoResponse for UnknownTenant {
+    fn into_response(self) -> Response {
+        tracing::warn!(domain = %self.domain, "request for unknown tenant");
+        if self.api {
+            let body = json!({
+                "error": "unknown_tenant",
+                "domain": self.domain,
+            });
+            return (StatusCode::NOT_FOUND, Json(body)).into_response();
+        }
+        match std::fs::read_to_string("assets/unknown-tenant.html") {
+            Ok(html) => (StatusCode::NOT_FOUND, Html(html)).into_response(),
+            Err(_) => (StatusCode::NOT_FOUND, "unknown tenant").into_response(),
+        }
+    }
+}
+
+/// Outer middleware that resolves path-prefix tenants and strips the prefix before routing.
+pub async fn strip_tenant_prefix(
+    State(state): State<AppState>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let domains = &state.config.domains;
+    let host = request_host(&state.config, req.headers()).unwrap_or_default();
+    let matched = domains
+        .tenant_for_path(&host, req.uri().path())
+        .map(|(tenant, prefix)| (tenant.to_string(), prefix.to_string()));
+    if let Some((tenant, prefix)) = matched {
+        let rest = &req.uri().path()[prefix.len()..];
+        let rest = if rest.is_empty() { "/" } else { rest };
+        let rewritten = match req.uri().query() {
+            Some(query) => format!("{rest}?{query}"),
+            None => rest.to_string(),
+        };
+        match rewritten.parse::<Uri>() {
+            Ok(uri) => *req.uri_mut() = uri,
+            Err(err) => {
+                tracing::warn!(?err, "failed to strip tenant path prefix");
+                return StatusCode::BAD_REQUEST.into_response();
+            }
+        }
+        req.extensions_mut().insert(RequestTenant {
+            tenant,
+            domain: host,
+            path_prefix: Some(prefix),
+        });
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_map() -> DomainMap {
+        toml::from_str(
+            r#"
+            [[domain]]
+            host = "acme.example"
+            tenant = "acme-root"
+
+            [[domain]]
+            host = "*.acme.example"
+            tenant = "acme"
+
+            [[domain]]
+            host = "*.eu.acme.example"
+            tenant = "acme-eu"
+
+            [[domain]]
+            host = "localhost"
+            path_prefix = "/t/globex/"
+            tenant = "globex"
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn resolves_exact_and_wildcard_hosts() {
+        let map = sample_map();
+        assert_eq!(map.tenant_for_host("acme.example:8080"), Some("acme-root"));
+        assert_eq!(map.tenant_for_host("shop.acme.example"), Some("acme"));
+        assert_eq!(map.tenant_for_host("shop.eu.acme.example"), Some("acme-eu"));
+        assert_eq!(map.tenant_for_host("ACME.example"), Some("acme-root"));
+        assert_eq!(map.tenant_for_host("evilacme.example"), None);
+        assert_eq!(map.tenant_for_host("localhost"), None);
+    }
+
+    #[test]
+    fn resolves_path_prefix_tenants_on_segment_boundaries() {
+        let map = sample_map();
+        assert_eq!(
+            map.tenant_for_path("localhost:8080", "/t/globex/invoices"),
+            Some(("globex", "/t/globex"))
+        );
+        assert_eq!(
+            map.tenant_for_path("localhost", "/t/globex"),
+            Some(("globex", "/t/globex"))
+        );
+        assert_eq!(map.tenant_for_path("localhost", "/t/globexx"), None);
+        assert_eq!(map.tenant_for_path("acme.example", "/t/globex"), None);
+    }
+
+    #[test]
+    fn prefixed_tenants_name_their_base_for_the_sdk() {
+        let tenant = RequestTenant {
+            tenant: "globex".into(),
+            domain: "localhost".into(),
+            path_prefix: Some("/t/globex".into()),
+        };
+        assert_eq!(tenant.url_for("/auth/logout"), "/t/globex/auth/logout");
+        assert_eq!(
+            tenant.base_meta(),
+            r#"<meta name="greentic-base" content="/t/globex">"#
+        );
+        let root = RequestTenant {
+            path_prefix: None,
+            ..tenant
+        };
+        assert_eq!(root.base_meta(), "");
+    }
+
+    #[test]
+    fn client_ip_trusts_forwarded_for_only_behind_a_proxy() {
+        let mut config = crate::config::test_config();
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            "203.0.113.9, 198.51.100.7".parse().unwrap(),
+        );
+        let peer = Some("10.0.0.2:5000".parse().unwrap());
+        assert_eq!(
+            client_ip(&config, &headers, peer),
+            Some("10.0.0.2".parse().unwrap())
+        );
+        config.public_base_url = Some("https://gui.example".into());
+        assert_eq!(
+            client_ip(&config, &headers, peer),
+            Some("198.51.100.7".parse().unwrap())
+        );
+        assert_eq!(client_ip(&config, &HeaderMap::new(), None), None);
+    }
+}
@@ -0,0 +1,369 @@
+//! Host imports linked into gui-fragment components as `greentic:gui/fragment-host@1.0.0`
+//! (see `wit/fragment-host.wit`).
+//!
+//! Fragments can read their pack's config, check whether a declared secret is available (never
+//! its value), look up translations, read the route's params, log through tracing and call
+//! workers. Everything except route params and logging is gated by the binding's
+//! `capabilities`; denied calls answer as if the data were absent.
+
+use crate::integration::build_tenant_ctx;
+use crate::otlp::TraceContext;
+use crate::packs::FragmentCapabilities;
+use crate::route_pattern::RouteParams;
+use crate::tenant::FeaturePack;
+use crate::worker::WorkerHost;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+use wasmtime::component::Linker;
+
+pub const HOST_INTERFACE: &str = "greentic:gui/fragment-host@1.0.0";
+
+/// Pack-scoped data a fragment's host imports can read.
+#[derive(Debug, Clone, Default)]
+pub struct PackScope {
+    pub pack: String,
+    pub config: Map<String, Value>,
+    /// Locale to key to text.
+    pub i18n: HashMap<String, HashMap<String, String>>,
+    /// Secret keys the pack declares.
+    pub secrets: Vec<String>,
+}
+
+impl PackScope {
+    pub fn for_feature(feature: &FeaturePack) -> Self {
+        Self {
+            pack: feature.location.pack_name(),
+            config: feature.manifest.config.clone(),
+            i18n: feature.manifest.i18n.clone(),
+            secrets: feature
+                .secret_requirements
+                .iter()
+                .map(|req| req.key.as_str().to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Answers whether a secret is available to a tenant's pack without revealing its value.
+pub trait SecretsProbe: Send + Sync {
+    fn is_available(&self, tenant: &str, pack: &str, key: &str) -> bool;
+}
+
+/// Treats a secret as available when an env var scoped to the tenant and pack is set, so packs
+/// cannot probe the server's own environment: `stripe/api_key` of pack `billing` for tenant
+/// `acme` is `GREENTIC_SECRET_ACME__BILLING__STRIPE_API_KEY`.
+pub struct EnvSecretsProbe;
+
+impl SecretsProbe for EnvSecretsProbe {
+    fn is_available(&self, tenant: &str, pack: &str, key: &str) -> bool {
+        secret_env_var(tenant, pack, key)
+            .and_then(std::env::var_os)
+            .is_some_and(|value| !value.is_empty())
+    }
+}
+
+/// Env var for a secret: parts are upper-cased, other characters become `_`, and parts are
+/// joined with `__`. Parts that would be empty, contain `__` or start or end with `_` have
+/// no variable, so no two (tenant, pack, key) triples share one.
+fn secret_env_var(tenant: &str, pack: &str, key: &str) -> Option<String> {
+    let parts = [tenant, pack, key]
+        .iter()
+        .map(|part| {
+            let part: String = part
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let ambiguous = part.is_empty()
+                || part.contains("__")
+                || part.starts_with('_')
+                || part.ends_with('_');
+            (!ambiguous).then_some(part)
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(format!("GREENTIC_SECRET_{}", parts.join("__")))
+}
+
+/// Process-wide services behind the host imports.
+#[derive(Clone)]
+pub struct FragmentHost {
+    pub worker_host: Arc<WorkerHost>,
+    pub secrets: Arc<dyn SecretsProbe>,
+    pub env_id: String,
+    pub team: String,
+}
+
+/// Per-render host state: who is rendering what, and what it may access.
+pub struct HostState {
+    pub host: Option<FragmentHost>,
+    pub pack: Arc<PackScope>,
+    pub capabilities: FragmentCapabilities,
+    pub tenant: String,
+    pub user: Option<String>,
+    /// Params captured by the route being rendered.
+    pub params: RouteParams,
+    /// Trace context of the request being rendered; worker calls continue it.
+    pub trace: Option<TraceContext>,
+    pub fragment_id: String,
+}
+
+/// A worker call that passed the binding's checks.
+pub struct WorkerCall {
+    worker_host: Arc<WorkerHost>,
+    tenant_ctx: greentic_types::TenantCtx,
+    payload: Value,
+    trace: Option<TraceContext>,
+}
+
+impl HostState {
+    fn denied(&self, capability: &str) {
+        warn!(
+            tenant = %self.tenant,
+            pack = %self.pack.pack,
+            fragment = %self.fragment_id,
+            capability,
+            "fragment host call denied"
+        );
+    }
+
+    /// JSON-encoded value of a pack config key.
+    pub fn config(&self, key: &str) -> Option<String> {
+        if !self.capabilities.config {
+            self.denied("config");
+            return None;
+        }
+        self.pack.config.get(key).map(Value::to_string)
+    }
+
+    /// Whether a secret the pack declares is available; undeclared keys never are.
+    pub fn has_secret(&self, key: &str) -> bool {
+        if !self.capabilities.secrets {
+            self.denied("secrets");
+            return false;
+        }
+        let Some(host) = &self.host else {
+            return false;
+        };
+        self.pack.secrets.iter().any(|declared| declared == key)
+            && host
+                .secrets
+                .is_available(&self.tenant, &self.pack.pack, key)
+    }
+
+    /// Text for `key` in `locale`, falling back to the locale's language (`de-CH` to `de`).
+    pub fn translate(&self, locale: &str, key: &str) -> Option<String> {
+        if !self.capabilities.i18n {
+            self.denied("i18n");
+            return None;
+        }
+        let language = locale.split(['-', '_']).next().unwrap_or(locale);
+        [locale, language]
+            .into_iter()
+            .find_map(|locale| self.pack.i18n.get(locale)?.get(key).cloned())
+    }
+
+    /// Value of a param captured by the route pattern, e.g. `id` for `/invoices/:id`.
+    pub fn route_param(&self, name: &str) -> Option<String> {
+        self.params.get(name).cloned()
+    }
+
+    pub fn log(&self, level: &str, message: &str) {
+        let (tenant, pack, fragment) = (&self.tenant, &self.pack.pack, &self.fragment_id);
+        match level {
+            "error" => error!(%tenant, %pack, %fragment, "{message}"),
+            "warn" => warn!(%tenant, %pack, %fragment, "{message}"),
+            "debug" | "trace" => debug!(%tenant, %pack, %fragment, "{message}"),
+            _ => info!(%tenant, %pack, %fragment, "{message}"),
+        }
+    }
+
+    /// Check a worker call against the binding's allowlist and build what it needs, so the
+    /// call itself does not borrow the store.
+    fn worker_call(&self, worker_id: &str, payload: &str) -> Result<WorkerCall, String> {
+        if !self.capabilities.workers.iter().any(|id| id == worker_id) {
+            self.denied("workers");
+            return Err(format!(
+                "worker {worker_id} is not allowed for this fragment"
+            ));
+        }
+        let host = self
+            .host
+            .as_ref()
+            .ok_or_else(|| "worker calls are not available".to_string())?;
+        let payload = serde_json::from_str(payload).map_err(|e| format!("invalid payload: {e}"))?;
+        let tenant_ctx = build_tenant_ctx(
+            &host.env_id,
+            &self.tenant,
+            Some(&host.team),
+            self.user.as_deref(),
+        );
+        Ok(WorkerCall {
+            worker_host: host.worker_host.clone(),
+            tenant_ctx,
+            payload,
+            trace: self.trace.clone(),
+        })
+    }
+}
+
+/// Define the `fragment-host` interface on `linker`; `state` finds the host state in the store.
+pub fn add_to_linker<T: Send + 'static>(
+    linker: &mut Linker<T>,
+    state: fn(&mut T) -> &mut HostState,
+) -> anyhow::Result<()> {
+    let mut host = linker.instance(HOST_INTERFACE)?;
+    host.func_wrap("get-config", move |mut store, (key,): (String,)| {
+        Ok((state(store.data_mut()).config(&key),))
+    })?;
+    host.func_wrap("has-secret", move |mut store, (key,): (String,)| {
+        Ok((state(store.data_mut()).has_secret(&key),))
+    })?;
+    host.func_wrap(
+        "translate",
+        move |mut store, (locale, key): (String, String)| {
+            Ok((state(store.data_mut()).translate(&locale, &key),))
+        },
+    )?;
+    host.func_wrap("route-param", move |mut store, (name,): (String,)| {
+        Ok((state(store.data_mut()).route_param(&name),))
+    })?;
+    host.func_wrap(
+        "log",
+        move |mut store, (level, message): (String, String)| {
+            state(store.data_mut()).log(&level, &message);
+            Ok(())
+        },
+    )?;
+    host.func_wrap_async(
+        "call-worker",
+        move |mut store, (worker_id, payload): (String, String)| {
+            let call = state(store.data_mut()).worker_call(&worker_id, &payload);
+            Box::new(async move {
+                let call = match call {
+                    Ok(call) => call,
+                    Err(err) => return Ok((Err(err),)),
+                };
+                let response = call
+                    .worker_host
+                    .invoke_worker(call.tenant_ctx, &worker_id, call.payload, call.trace)
+                    .await
+                    .map(|value| value.to_string())
+                    .map_err(|err| err.to_string());
+                Ok((response,))
+            })
+        },
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::worker::StubWorkerBackend;
+
+    struct AllowAll;
+
+    impl SecretsProbe for AllowAll {
+        fn is_available(&self, _tenant: &str, _pack: &str, _key: &str) -> bool {
+            true
+        }
+    }
+
+    fn state(capabilities: FragmentCapabilities) -> HostState {
+        let mut config = Map::new();
+        config.insert("title".into(), Value::String("Invoices".into()));
+        HostState {
+            host: Some(FragmentHost {
+                worker_host: Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+                secrets: Arc::new(AllowAll),
+                env_id: "dev".into(),
+                team: "gui".into(),
+            }),
+            pack: Arc::new(PackScope {
+                pack: "billing".into(),
+                config,
+                i18n: HashMap::from([(
+                    "de".to_string(),
+                    HashMap::from([("greeting".to_string(), "Hallo".to_string())]),
+                )]),
+                secrets: vec!["stripe/api_key".into()],
+            }),
+            capabilities,
+            tenant: "tenant".into(),
+            user: None,
+            params: RouteParams::from([("id".to_string(), "7".to_string())]),
+            trace: Some(TraceContext::new_root()),
+            fragment_id: "summary".into(),
+        }
+    }
+
+    #[test]
+    fn host_calls_are_gated_by_capabilities() {
+        let denied = state(FragmentCapabilities::default());
+        assert_eq!(denied.config("title"), None);
+        assert!(!denied.has_secret("stripe/api_key"));
+        assert_eq!(denied.translate("de", "greeting"), None);
+        assert!(denied.worker_call("worker.echo", "{}").is_err());
+        // Route params describe the request, not pack data, so they need no capability.
+        assert_eq!(denied.route_param("id").as_deref(), Some("7"));
+        assert_eq!(denied.route_param("missing"), None);
+
+        let granted = state(FragmentCapabilities {
+            config: true,
+            secrets: true,
+            i18n: true,
+            workers: vec!["worker.echo".into()],
+        });
+        assert_eq!(granted.config("title").as_deref(), Some("\"Invoices\""));
+        assert!(granted.has_secret("stripe/api_key"));
+        assert!(!granted.has_secret("undeclared"));
+        assert_eq!(
+            granted.translate("de-CH", "greeting").as_deref(),
+            Some("Hallo")
+        );
+        assert_eq!(granted.translate("fr", "greeting"), None);
+        let call = granted.worker_call("worker.echo", "{\"x\":1}").unwrap();
+        // Worker calls continue the trace of the request rendering the fragment.
+        assert_eq!(call.trace, granted.trace);
+        assert!(granted.worker_call("worker.other", "{}").is_err());
+    }
+
+    #[test]
+    fn env_secrets_are_scoped_to_tenant_and_pack() {
+        assert_eq!(
+            secret_env_var("acme", "billing", "stripe/api_key").as_deref(),
+            Some("GREENTIC_SECRET_ACME__BILLING__STRIPE_API_KEY")
+        );
+        assert_ne!(
+            secret_env_var("acme", "billing", "stripe/api_key"),
+            secret_env_var("other", "billing", "stripe/api_key")
+        );
+        assert!(!EnvSecretsProbe.is_available("acme", "billing", "path"));
+    }
+
+    #[test]
+    fn env_secret_names_do_not_collide_across_parts() {
+        // With a single `_` separator these all named `GREENTIC_SECRET_A_B_C_D`.
+        assert_ne!(
+            secret_env_var("a_b", "c", "d"),
+            secret_env_var("a", "b_c", "d")
+        );
+        assert_ne!(
+            secret_env_var("a", "b_c", "d"),
+            secret_env_var("a", "b", "c_d")
+        );
+        // Parts that could fake a separator have no variable at all.
+        assert_eq!(secret_env_var("a__b", "c", "d"), None);
+        assert_eq!(secret_env_var("a", "b/", "d"), None);
+        assert_eq!(secret_env_var("a", "_b", "d"), None);
+        assert_eq!(secret_env_var("a", "b", ""), None);
+        assert!(!EnvSecretsProbe.is_available("a", "b", "c__PATH"));
+    }
+}
@@ -1,22 +1,29 @@
+use crate::fragment_host::{self, FragmentHost, HostState, PackScope};
 use crate::integration::SessionInfo;
+use crate::otlp::TraceContext;
 use crate::packs::FragmentBinding;
+use crate::route_pattern::RouteParams;
 use crate::tenant::FragmentTarget;
+use crate::vfs;
 use async_trait::async_trait;
-use greentic_interfaces_guest::gui_fragment as api;
-use greentic_interfaces_wasmtime::gui_gui_fragment_v1_0::{
-    Component as GuiFragmentComponent, GuiFragment,
-    exports::greentic::gui::fragment_api::FragmentContext as WasmtimeFragmentContext,
-};
+use greentic_interfaces_wasmtime::gui_gui_fragment_v1_0::exports::greentic::gui::fragment_api as fragment_api_v1;
 use kuchiki::NodeRef;
 use kuchiki::traits::*;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::sync::RwLock;
-use tracing::{debug, error, warn};
-use wasmtime::{Engine, Store, component::Linker};
+use tokio::sync::{RwLock, Semaphore};
+use tokio::task::JoinSet;
+use tracing::{Instrument, Span, debug, error, info, warn};
+use wasmtime::component::{Component, Linker};
+use wasmtime::{
+    Engine, Precompiled, ResourceLimiter, Store, StoreLimits, StoreLimitsBuilder, Trap,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FragmentContext {
@@ -24,17 +31,34 @@ pub struct FragmentContext {
     pub user_ctx: String,
     pub route: String,
     pub session_id: String,
+    /// Signed-in user, if any; host imports act on their behalf.
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// Params captured by the matched route pattern; Wasm components read them through the
+    /// `route-param` host import.
+    #[serde(default)]
+    pub params: RouteParams,
+    /// Pack data behind the WIT host imports.
+    #[serde(skip)]
+    pub pack: Arc<PackScope>,
+    /// Trace context of the request; worker calls made by the fragment continue it.
+    #[serde(skip)]
+    pub trace: Option<TraceContext>,
 }
 
 #[derive(Debug, Error)]
 pub enum FragmentError {
-    #[allow(dead_code)]
     #[error("renderer error: {0}")]
     Renderer(String),
     #[error("html manipulation failed: {0}")]
     Html(String),
     #[error("missing secrets: {0}")]
     MissingSecrets(String),
+    /// The fragment component ran out of fuel, memory, table space or time.
+    #[error("fragment resource limit exceeded: {0}")]
+    ResourceExhausted(String),
+    #[error("unsupported component world {0}")]
+    UnsupportedWorld(String),
 }
 
 #[async_trait]
@@ -45,9 +69,15 @@ pub trait FragmentRenderer: Send + Sync {
         assets_root: &Path,
         ctx: FragmentContext,
     ) -> Result<Option<String>, FragmentError>;
+
+    /// Get `binding` ready ahead of its first render, e.g. by compiling its component.
+    async fn prepare(&self, _binding: &FragmentBinding, _assets_root: &Path) {}
+
+    /// Forget anything prepared for fragments under `assets_root`, e.g. after a pack reload.
+    async fn evict(&self, _assets_root: &Path) {}
 }
 
-/// Simple renderer that looks for `fragments/{id}.html` under the pack assets root.
+/// Simple renderer that looks for `fragments/{id}.html` under the pack assets root (directory or `.gtpack`).
 pub struct FileFragmentRenderer;
 
 #[async_trait]
@@ -61,7 +91,7 @@ impl FragmentRenderer for FileFragmentRenderer {
         let path = assets_root
             .join("fragments")
             .join(format!("{}.html", binding.id));
-        match tokio::fs::read_to_string(&path).await {
+        match vfs::read_to_string(&path).await {
             Ok(content) => Ok(Some(content)),
             Err(err) => {
                 warn!(?path, ?err, "fragment file not found; falling back");
@@ -75,12 +105,22 @@ impl FragmentRenderer for FileFragmentRenderer {
 pub trait FragmentInvoker: Send + Sync {
     async fn render(
         &self,
-        component_world: &str,
-        component_name: &str,
-        fragment_id: &str,
+        binding: &FragmentBinding,
         assets_root: &Path,
-        ctx: api::FragmentContext,
-    ) -> Result<String, String>;
+        ctx: FragmentContext,
+    ) -> Result<String, FragmentError>;
+
+    /// Compile `binding`'s component ahead of its first render.
+    async fn precompile(
+        &self,
+        _binding: &FragmentBinding,
+        _assets_root: &Path,
+    ) -> Result<(), FragmentError> {
+        Ok(())
+    }
+
+    /// Drop compiled components under `assets_root`.
+    async fn evict(&self, _assets_root: &Path) {}
 }
 
 /// Renderer that invokes WIT gui-fragment components via a pluggable invoker.
@@ -99,36 +139,31 @@ impl FragmentRenderer for WitFragmentRenderer {
     async fn render_fragment(
         &self,
         binding: &FragmentBinding,
-        _assets_root: &Path,
+        assets_root: &Path,
         ctx: FragmentContext,
     ) -> Result<Option<String>, FragmentError> {
-        let api_ctx = api::FragmentContext {
-            tenant_ctx: ctx.tenant_ctx,
-            user_ctx: ctx.user_ctx,
-            route: ctx.route,
-            session_id: ctx.session_id,
-        };
-        match self
-            .invoker
-            .render(
-                &binding.component_world,
-                &binding.component_name,
-                &binding.id,
-                _assets_root,
-                api_ctx,
-            )
-            .await
-        {
+        match self.invoker.render(binding, assets_root, ctx).await {
             Ok(html) => Ok(Some(html)),
+            Err(FragmentError::Renderer(err)) if err.contains("missing_secrets") => {
+                Err(FragmentError::MissingSecrets(err))
+            }
+            Err(err @ FragmentError::ResourceExhausted(_)) => Err(err),
             Err(err) => {
-                if err.contains("missing_secrets") {
-                    return Err(FragmentError::MissingSecrets(err));
-                }
                 warn!(id = %binding.id, %err, "wit fragment render failed");
                 Ok(None)
             }
         }
     }
+
+    async fn prepare(&self, binding: &FragmentBinding, assets_root: &Path) {
+        if let Err(err) = self.invoker.precompile(binding, assets_root).await {
+            debug!(id = %binding.id, %err, "fragment component not precompiled");
+        }
+    }
+
+    async fn evict(&self, assets_root: &Path) {
+        self.invoker.evict(assets_root).await;
+    }
 }
 
 /// Composite renderer that tries WIT components first, then falls back to file fragments.
@@ -171,6 +206,18 @@ impl FragmentRenderer for CompositeFragmentRenderer {
         }
         self.file.render_fragment(binding, assets_root, ctx).await
     }
+
+    async fn prepare(&self, binding: &FragmentBinding, assets_root: &Path) {
+        if let Some(wit) = &self.wit {
+            wit.prepare(binding, assets_root).await;
+        }
+    }
+
+    async fn evict(&self, assets_root: &Path) {
+        if let Some(wit) = &self.wit {
+            wit.evict(assets_root).await;
+        }
+    }
 }
 
 /// In-memory invoker placeholder; returns Err to trigger fallback.
@@ -180,52 +227,428 @@ pub struct NoopFragmentInvoker;
 impl FragmentInvoker for NoopFragmentInvoker {
     async fn render(
         &self,
-        _component_world: &str,
-        _component_name: &str,
-        _fragment_id: &str,
+        _binding: &FragmentBinding,
         _assets_root: &Path,
-        _ctx: api::FragmentContext,
-    ) -> Result<String, String> {
-        Err("wit fragment invoker not configured".to_string())
+        _ctx: FragmentContext,
+    ) -> Result<String, FragmentError> {
+        Err(FragmentError::Renderer(
+            "wit fragment invoker not configured".to_string(),
+        ))
+    }
+}
+
+/// Per-render resource limits for Wasmtime fragment components.
+#[derive(Debug, Clone)]
+pub struct FragmentLimits {
+    /// Wall-clock budget for instantiating and running one render.
+    pub timeout: Duration,
+    /// Fuel units per render; `None` disables fuel metering.
+    pub fuel: Option<u64>,
+    pub max_memory_bytes: usize,
+    pub max_table_elements: usize,
+}
+
+impl Default for FragmentLimits {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(2),
+            fuel: Some(1_000_000_000),
+            max_memory_bytes: 64 << 20,
+            max_table_elements: 10_000,
+        }
+    }
+}
+
+const GUI_FRAGMENT_WORLD: &str = "greentic:gui/gui-fragment";
+const RENDER_FRAGMENT_EXPORT: &str = "render-fragment";
+/// Interval at which running fragments yield, so the wall-clock timeout can cancel them.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+/// A binding's `component_world`, e.g. `greentic:gui/gui-fragment@1.0.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentWorld {
+    pub version: semver::Version,
+}
+
+/// Host bindings for a gui-fragment WIT major version. Minor versions only add to a world, so
+/// one set of bindings serves every `1.x` component.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FragmentApi {
+    V1,
+}
+
+impl FragmentWorld {
+    /// Parse a declared world, rejecting other worlds and versions without bindings.
+    pub fn parse(world: &str) -> Result<Self, FragmentError> {
+        let unsupported =
+            |reason: String| FragmentError::UnsupportedWorld(format!("{world}: {reason}"));
+        let (name, version) = world
+            .split_once('@')
+            .ok_or_else(|| unsupported("missing @version".to_string()))?;
+        if name != GUI_FRAGMENT_WORLD {
+            return Err(unsupported(format!("expected {GUI_FRAGMENT_WORLD}")));
+        }
+        let version = semver::Version::parse(version).map_err(|e| unsupported(e.to_string()))?;
+        let world = Self { version };
+        if world.api().is_none() {
+            return Err(unsupported(format!(
+                "no bindings for version {}.x",
+                world.version.major
+            )));
+        }
+        Ok(world)
+    }
+
+    fn api(&self) -> Option<FragmentApi> {
+        match self.version.major {
+            1 => Some(FragmentApi::V1),
+            _ => None,
+        }
+    }
+
+    /// Name of the `fragment-api` instance a component of this world exports.
+    fn api_export(&self) -> String {
+        format!("greentic:gui/fragment-api@{}", self.version)
+    }
+}
+
+/// Store data for one render; records which limit denied a memory or table growth.
+struct FragmentStore {
+    limits: StoreLimits,
+    exhausted: Option<String>,
+    host: HostState,
+}
+
+impl ResourceLimiter for FragmentStore {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let allowed = self.limits.memory_growing(current, desired, maximum);
+        if !matches!(allowed, Ok(true)) {
+            self.exhausted = Some(format!("memory growth to {desired} bytes denied"));
+        }
+        allowed
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> anyhow::Result<bool> {
+        let allowed = self.limits.table_growing(current, desired, maximum);
+        if !matches!(allowed, Ok(true)) {
+            self.exhausted = Some(format!("table growth to {desired} elements denied"));
+        }
+        allowed
     }
 }
 
 /// Wasmtime-based invoker that loads wasm components from `assets_root/fragments/{component_name}.wasm`.
+///
+/// Every render gets a fresh store bounded by [`FragmentLimits`] and runs on the engine's async
+/// support, yielding each epoch tick so a spinning component cannot hold the request task.
 pub struct WasmtimeFragmentInvoker {
     engine: Engine,
-    cache: RwLock<HashMap<PathBuf, Arc<wasmtime::component::Component>>>,
+    linker: Linker<FragmentStore>,
+    limits: FragmentLimits,
+    host: Option<FragmentHost>,
+    cache_dir: Option<PathBuf>,
+    cache: RwLock<ComponentCache>,
+}
+
+/// Compiled components by content hash, and the hash last read from each component path
+/// together with the file stamp it was read at.
+#[derive(Default)]
+struct ComponentCache {
+    by_path: HashMap<PathBuf, (vfs::Stamp, String)>,
+    by_hash: HashMap<String, Arc<Component>>,
+}
+
+impl ComponentCache {
+    /// Forget components no path uses any more and return their hashes.
+    fn drop_unused(&mut self) -> Vec<String> {
+        let Self { by_path, by_hash } = self;
+        let unused: Vec<String> = by_hash
+            .keys()
+            .filter(|hash| !by_path.values().any(|(_, used)| used == *hash))
+            .cloned()
+            .collect();
+        for hash in &unused {
+            by_hash.remove(hash);
+        }
+        unused
+    }
 }
 
 impl WasmtimeFragmentInvoker {
-    pub fn new() -> anyhow::Result<Self> {
+    pub fn new(limits: FragmentLimits) -> anyhow::Result<Self> {
         let mut config = wasmtime::Config::new();
         config.wasm_component_model(true);
         config.async_support(true);
+        config.consume_fuel(limits.fuel.is_some());
+        config.epoch_interruption(true);
         let engine = Engine::new(&config)?;
+        spawn_epoch_ticker(&engine)?;
+        let mut linker = Linker::new(&engine);
+        fragment_host::add_to_linker(&mut linker, |store: &mut FragmentStore| &mut store.host)?;
         Ok(Self {
             engine,
-            cache: RwLock::new(HashMap::new()),
+            linker,
+            limits,
+            host: None,
+            cache_dir: None,
+            cache: RwLock::default(),
         })
     }
 
-    async fn instantiate(&self, component_path: &Path) -> anyhow::Result<(GuiFragment, Store<()>)> {
-        let component = if let Some(cached) = self.cache.read().await.get(component_path).cloned() {
-            cached
-        } else {
-            let wasm_bytes = tokio::fs::read(component_path).await?;
-            let compiled = GuiFragmentComponent::instantiate(&self.engine, &wasm_bytes)?;
-            let arc = Arc::new(compiled);
-            self.cache
-                .write()
-                .await
-                .insert(component_path.to_path_buf(), arc.clone());
-            arc
+    /// Back the `fragment-host` imports with `host`; without it worker calls and secret checks
+    /// always fail.
+    pub fn with_host(mut self, host: FragmentHost) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// Keep precompiled components under `cache_dir` so restarts skip compilation. Artifacts
+    /// of other engines can never be loaded, so their directories are removed.
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = Some(cache_dir);
+        if let Some(engine_dir) = self.engine_dir() {
+            prune_engine_dirs(&engine_dir);
+        }
+        self
+    }
+
+    /// Compiled component for `component_path`; the file is re-read and re-hashed whenever its
+    /// stamp changed since it was last read.
+    async fn component(&self, component_path: &Path) -> anyhow::Result<Arc<Component>> {
+        let stamp = vfs::stamp(component_path).await?;
+        {
+            let cache = self.cache.read().await;
+            if let Some(cached) = cache
+                .by_path
+                .get(component_path)
+                .filter(|(cached_stamp, _)| *cached_stamp == stamp)
+                .and_then(|(_, hash)| cache.by_hash.get(hash))
+            {
+                return Ok(cached.clone());
+            }
+        }
+        let wasm_bytes = vfs::read(component_path).await?;
+        let hash = hex::encode(Sha256::digest(&wasm_bytes));
+        let cached = self.cache.read().await.by_hash.get(&hash).cloned();
+        let component = match cached {
+            Some(component) => component,
+            None => {
+                let engine = self.engine.clone();
+                let artifact = self.artifact_path(&hash);
+                let compiled = tokio::task::spawn_blocking(move || {
+                    load_or_compile(&engine, &wasm_bytes, artifact.as_deref())
+                })
+                .await??;
+                Arc::new(compiled)
+            }
         };
+        let unused = {
+            let mut cache = self.cache.write().await;
+            cache
+                .by_path
+                .insert(component_path.to_path_buf(), (stamp, hash.clone()));
+            cache.by_hash.insert(hash, component.clone());
+            cache.drop_unused()
+        };
+        self.remove_artifacts(unused).await;
+        Ok(component)
+    }
+
+    /// Directory for this engine's artifacts: one per engine compatibility hash, so artifacts
+    /// from other Wasmtime versions or settings are never loaded.
+    fn engine_dir(&self) -> Option<PathBuf> {
+        let cache_dir = self.cache_dir.as_ref()?;
+        let mut engine_hash = EngineHasher::default();
+        self.engine
+            .precompile_compatibility_hash()
+            .hash(&mut engine_hash);
+        Some(cache_dir.join(format!("{:016x}", engine_hash.finish())))
+    }
+
+    /// Where the precompiled artifact for `hash` lives.
+    fn artifact_path(&self, hash: &str) -> Option<PathBuf> {
+        Some(self.engine_dir()?.join(format!("{hash}.cwasm")))
+    }
+
+    /// Delete the artifacts of components no longer cached for any path.
+    async fn remove_artifacts(&self, hashes: Vec<String>) {
+        for artifact in hashes.iter().filter_map(|hash| self.artifact_path(hash)) {
+            match tokio::fs::remove_file(&artifact).await {
+                Ok(()) => debug!(?artifact, "removed unused fragment artifact"),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => warn!(?artifact, %err, "failed to remove fragment artifact"),
+            }
+        }
+    }
+
+    fn store(&self, host: HostState) -> anyhow::Result<Store<FragmentStore>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.max_memory_bytes)
+            .table_elements(self.limits.max_table_elements)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(
+            &self.engine,
+            FragmentStore {
+                limits,
+                exhausted: None,
+                host,
+            },
+        );
+        store.limiter(|state| state);
+        if let Some(fuel) = self.limits.fuel {
+            store.set_fuel(fuel)?;
+        }
+        store.epoch_deadline_async_yield_and_update(1);
+        Ok(store)
+    }
+
+    async fn call(
+        &self,
+        store: &mut Store<FragmentStore>,
+        component: &Component,
+        world: &FragmentWorld,
+        fragment_id: &str,
+        ctx: FragmentContext,
+    ) -> anyhow::Result<Result<String, String>> {
+        let instance = self
+            .linker
+            .instantiate_async(&mut *store, component)
+            .await?;
+        let api_export = world.api_export();
+        let render = instance
+            .get_export_index(&mut *store, None, &api_export)
+            .and_then(|api| {
+                instance.get_export_index(&mut *store, Some(&api), RENDER_FRAGMENT_EXPORT)
+            })
+            .ok_or_else(|| anyhow::anyhow!("component does not export {api_export}"))?;
+        match world.api() {
+            Some(FragmentApi::V1) => {
+                let ctx = fragment_api_v1::FragmentContext {
+                    tenant_ctx: ctx.tenant_ctx,
+                    user_ctx: ctx.user_ctx,
+                    route: ctx.route,
+                    session_id: ctx.session_id,
+                };
+                let render = instance.get_typed_func::<
+                    (&str, &fragment_api_v1::FragmentContext),
+                    (Result<String, String>,),
+                >(&mut *store, &render)?;
+                let (html,) = render.call_async(&mut *store, (fragment_id, &ctx)).await?;
+                render.post_return_async(&mut *store).await?;
+                Ok(html)
+            }
+            None => anyhow::bail!("no bindings for {}", world.api_export()),
+        }
+    }
+}
+
+/// Feeds [`Hash`] output into sha256, giving engine directory names that stay the same across
+/// Rust releases (unlike `DefaultHasher`).
+#[derive(Default)]
+struct EngineHasher(Sha256);
+
+impl Hasher for EngineHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        let digest = self.0.clone().finalize();
+        u64::from_be_bytes(digest[..8].try_into().expect("sha256 digest has 32 bytes"))
+    }
+}
+
+/// Deserialize the artifact for a component if it is compatible with `engine`, else compile the
+/// component and store a fresh artifact.
+fn load_or_compile(
+    engine: &Engine,
+    wasm_bytes: &[u8],
+    artifact: Option<&Path>,
+) -> anyhow::Result<Component> {
+    let Some(artifact) = artifact else {
+        return Component::from_binary(engine, wasm_bytes);
+    };
+    if let Ok(Some(Precompiled::Component)) = Engine::detect_precompiled_file(artifact) {
+        // SAFETY: artifacts are only ever written by `store_artifact` below, into the
+        // server's own cache dir; deserialization rejects other engine versions and settings.
+        match unsafe { Component::deserialize_file(engine, artifact) } {
+            Ok(component) => return Ok(component),
+            Err(err) => warn!(?artifact, %err, "discarding incompatible fragment artifact"),
+        }
+    }
+    let component = Component::from_binary(engine, wasm_bytes)?;
+    if let Err(err) = store_artifact(&component, artifact) {
+        warn!(?artifact, %err, "failed to cache precompiled fragment");
+    }
+    Ok(component)
+}
+
+/// Remove the directories next to `engine_dir`, which hold artifacts of other engines.
+fn prune_engine_dirs(engine_dir: &Path) {
+    let (Some(cache_dir), Some(current)) = (engine_dir.parent(), engine_dir.file_name()) else {
+        return;
+    };
+    let Ok(entries) = std::fs::read_dir(cache_dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if entry.file_name() != current && path.is_dir() {
+            match std::fs::remove_dir_all(&path) {
+                Ok(()) => info!(?path, "removed fragment artifacts of another engine"),
+                Err(err) => warn!(?path, %err, "failed to remove stale fragment artifacts"),
+            }
+        }
+    }
+}
+
+fn store_artifact(component: &Component, artifact: &Path) -> anyhow::Result<()> {
+    let dir = artifact
+        .parent()
+        .ok_or_else(|| anyhow::anyhow!("artifact path has no parent"))?;
+    std::fs::create_dir_all(dir)?;
+    let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+    std::io::Write::write_all(&mut tmp, &component.serialize()?)?;
+    tmp.persist(artifact)?;
+    Ok(())
+}
+
+/// Advance the engine epoch every [`EPOCH_TICK`] until the engine is dropped.
+fn spawn_epoch_ticker(engine: &Engine) -> std::io::Result<()> {
+    let engine = engine.weak();
+    std::thread::Builder::new()
+        .name("fragment-epoch".into())
+        .spawn(move || {
+            loop {
+                std::thread::sleep(EPOCH_TICK);
+                let Some(engine) = engine.upgrade() else {
+                    break;
+                };
+                engine.increment_epoch();
+            }
+        })?;
+    Ok(())
+}
 
-        let linker = Linker::new(&self.engine);
-        let mut store = Store::new(&self.engine, ());
-        let bindings = GuiFragment::instantiate(&mut store, &component, &linker)?;
-        Ok((bindings, store))
+/// Map a failed render to [`FragmentError::ResourceExhausted`] when a limit caused it.
+fn classify_failure(store: &Store<FragmentStore>, err: anyhow::Error) -> FragmentError {
+    if let Some(reason) = &store.data().exhausted {
+        return FragmentError::ResourceExhausted(reason.clone());
+    }
+    match err.downcast_ref::<Trap>() {
+        Some(Trap::OutOfFuel) => FragmentError::ResourceExhausted("fuel exhausted".to_string()),
+        _ => FragmentError::Renderer(format!("{err:#}")),
     }
 }
 
@@ -233,65 +656,220 @@ impl WasmtimeFragmentInvoker {
 impl FragmentInvoker for WasmtimeFragmentInvoker {
     async fn render(
         &self,
-        _component_world: &str,
-        component_name: &str,
-        fragment_id: &str,
+        binding: &FragmentBinding,
         assets_root: &Path,
-        ctx: api::FragmentContext,
-    ) -> Result<String, String> {
-        let component_path = assets_root
-            .join("fragments")
-            .join(format!("{component_name}.wasm"));
-        let (bindings, mut store) = self
-            .instantiate(&component_path)
+        ctx: FragmentContext,
+    ) -> Result<String, FragmentError> {
+        let world = FragmentWorld::parse(&binding.component_world)?;
+        let component = self
+            .component(&component_path(binding, assets_root))
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| FragmentError::Renderer(e.to_string()))?;
+        let host = HostState {
+            host: self.host.clone(),
+            pack: ctx.pack.clone(),
+            capabilities: binding.capabilities.clone(),
+            tenant: ctx.tenant_ctx.clone(),
+            user: ctx.user_id.clone(),
+            params: ctx.params.clone(),
+            trace: ctx.trace.clone(),
+            fragment_id: binding.id.clone(),
+        };
+        let mut store = self
+            .store(host)
+            .map_err(|e| FragmentError::Renderer(e.to_string()))?;
 
-        let ctx_bindgen = WasmtimeFragmentContext {
-            tenant_ctx: ctx.tenant_ctx,
-            user_ctx: ctx.user_ctx,
-            route: ctx.route,
-            session_id: ctx.session_id,
+        let call = self.call(&mut store, &component, &world, &binding.id, ctx);
+        match tokio::time::timeout(self.limits.timeout, call).await {
+            Ok(Ok(html)) => html.map_err(FragmentError::Renderer),
+            Ok(Err(err)) => Err(classify_failure(&store, err)),
+            Err(_) => Err(FragmentError::ResourceExhausted(format!(
+                "render timed out after {}ms",
+                self.limits.timeout.as_millis()
+            ))),
+        }
+    }
+
+    async fn precompile(
+        &self,
+        binding: &FragmentBinding,
+        assets_root: &Path,
+    ) -> Result<(), FragmentError> {
+        FragmentWorld::parse(&binding.component_world)?;
+        self.component(&component_path(binding, assets_root))
+            .await
+            .map_err(|e| FragmentError::Renderer(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn evict(&self, assets_root: &Path) {
+        let unused = {
+            let mut cache = self.cache.write().await;
+            cache
+                .by_path
+                .retain(|path, _| !path.starts_with(assets_root));
+            cache.drop_unused()
         };
+        self.remove_artifacts(unused).await;
+    }
+}
 
-        let html = bindings
-            .greentic_gui_fragment_api()
-            .call_render_fragment(&mut store, fragment_id, &ctx_bindgen)
-            .map_err(|e| e.to_string())??;
+/// `assets_root/fragments/{component_name}.wasm`
+fn component_path(binding: &FragmentBinding, assets_root: &Path) -> PathBuf {
+    assets_root
+        .join("fragments")
+        .join(format!("{}.wasm", binding.component_name))
+}
+
+/// Fragment html ready to be placed into its binding's selector.
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedFragment {
+    pub id: String,
+    pub selector: String,
+    pub html: String,
+}
 
-        Ok(html)
+/// How one page's fragments are rendered: at most `concurrency` at a time, and any fragment
+/// still rendering after `page_timeout` is replaced by a placeholder.
+#[derive(Debug, Clone)]
+pub struct FragmentBudget {
+    pub concurrency: usize,
+    pub page_timeout: Duration,
+}
+
+impl Default for FragmentBudget {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            page_timeout: Duration::from_secs(5),
+        }
     }
 }
 
+/// The request a page's fragments are rendered for.
+#[derive(Debug, Clone, Copy)]
+pub struct FragmentRequest<'a> {
+    pub session: Option<&'a SessionInfo>,
+    pub tenant_did: &'a str,
+    pub route: &'a str,
+    pub params: &'a RouteParams,
+    pub trace: Option<&'a TraceContext>,
+}
+
 pub async fn inject_fragments(
     html: String,
     bindings: &[FragmentTarget],
-    session: Option<&SessionInfo>,
-    tenant_did: &str,
-    route: &str,
+    request: FragmentRequest<'_>,
     renderer: Arc<dyn FragmentRenderer>,
+    budget: &FragmentBudget,
 ) -> Result<String, FragmentError> {
     if bindings.is_empty() {
         return Ok(html);
     }
 
-    let mut rendered: Vec<(FragmentBinding, String, PathBuf)> = Vec::new();
-    for target in bindings {
-        let binding = &target.binding;
+    let rendered = render_fragments(bindings, request, renderer, budget).await;
+    let mut document = kuchiki::parse_html().one(html);
+    for fragment in rendered {
+        if let Err(err) =
+            replace_selector_inner_html(&mut document, &fragment.selector, &fragment.html)
+        {
+            warn!(id = %fragment.id, selector = %fragment.selector, ?err, "failed to inject fragment html");
+        }
+    }
+
+    Ok(document.to_string())
+}
+
+/// A fragment render result and how long it took.
+type RenderOutcome = (Result<Option<String>, FragmentError>, Duration);
+
+/// Render every binding for a route concurrently within `budget`, returned in binding order;
+/// failures and fragments past the page deadline become inline `fragment-error` placeholders.
+///
+/// Each fragment's duration is logged as a `fragment rendered` event on the current span.
+pub async fn render_fragments(
+    bindings: &[FragmentTarget],
+    request: FragmentRequest<'_>,
+    renderer: Arc<dyn FragmentRenderer>,
+    budget: &FragmentBudget,
+) -> Vec<RenderedFragment> {
+    let FragmentRequest {
+        session,
+        tenant_did,
+        route,
+        params,
+        trace,
+    } = request;
+    let deadline = tokio::time::Instant::now() + budget.page_timeout;
+    let permits = Arc::new(Semaphore::new(budget.concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    let mut task_index = HashMap::new();
+    for (index, target) in bindings.iter().enumerate() {
+        let user_id = session.and_then(|s| s.user_id.clone());
         let ctx = FragmentContext {
             tenant_ctx: tenant_did.to_string(),
-            user_ctx: session
-                .and_then(|s| s.user_id.clone())
-                .unwrap_or_else(|| "{}".to_string()),
+            user_ctx: user_id.clone().unwrap_or_else(|| "{}".to_string()),
             route: route.to_string(),
             session_id: session.map(|s| s.session_id.clone()).unwrap_or_default(),
+            user_id,
+            params: params.clone(),
+            pack: target.pack.clone(),
+            trace: trace.cloned(),
         };
-        match renderer
-            .render_fragment(binding, &target.assets_root, ctx)
-            .await
-        {
+        let target = target.clone();
+        let renderer = renderer.clone();
+        let permits = permits.clone();
+        let task = tasks.spawn(
+            async move {
+                let _permit = permits.acquire_owned().await;
+                let started = Instant::now();
+                let result = renderer
+                    .render_fragment(&target.binding, &target.assets_root, ctx)
+                    .await;
+                (index, result, started.elapsed())
+            }
+            .instrument(Span::current()),
+        );
+        task_index.insert(task.id(), index);
+    }
+
+    let mut outcomes: Vec<Option<RenderOutcome>> = bindings.iter().map(|_| None).collect();
+    loop {
+        match tokio::time::timeout_at(deadline, tasks.join_next()).await {
+            Ok(Some(Ok((index, result, elapsed)))) => outcomes[index] = Some((result, elapsed)),
+            Ok(Some(Err(err))) => {
+                let index = task_index[&err.id()];
+                let failure = FragmentError::Renderer(format!("render task failed: {err}"));
+                outcomes[index] = Some((Err(failure), Duration::ZERO));
+            }
+            Ok(None) => break,
+            Err(_) => {
+                tasks.abort_all();
+                break;
+            }
+        }
+    }
+
+    let mut rendered = Vec::new();
+    for (target, outcome) in bindings.iter().zip(outcomes) {
+        let binding = &target.binding;
+        let Some((result, elapsed)) = outcome else {
+            warn!(
+                id = %binding.id,
+                timeout_ms = budget.page_timeout.as_millis() as u64,
+                "fragment missed the page deadline"
+            );
+            let fallback = format!(
+                "<div class=\"fragment-error\" data-fragment-id=\"{}\">fragment timed out</div>",
+                binding.id
+            );
+            rendered.push(RenderedFragment::new(binding, fallback));
+            continue;
+        };
+        info!(id = %binding.id, duration_ms = elapsed.as_millis() as u64, "fragment rendered");
+        match result {
             Ok(Some(fragment_html)) => {
-                rendered.push((binding.clone(), fragment_html, target.assets_root.clone()));
+                rendered.push(RenderedFragment::new(binding, fragment_html));
             }
             Ok(None) => {
                 debug!(id = %binding.id, "fragment renderer returned None");
@@ -308,7 +886,7 @@ pub async fn inject_fragments(
                         "<div class=\"fragment-error\" data-fragment-id=\"{}\">missing secrets for fragment</div>",
                         binding.id
                     );
-                    rendered.push((binding.clone(), fallback, target.assets_root.clone()));
+                    rendered.push(RenderedFragment::new(binding, fallback));
                     continue;
                 }
                 error!(
@@ -322,28 +900,79 @@ pub async fn inject_fragments(
                     "<div class=\"fragment-error\" data-fragment-id=\"{}\">fragment render failed</div>",
                     binding.id
                 );
-                rendered.push((binding.clone(), fallback, target.assets_root.clone()));
+                rendered.push(RenderedFragment::new(binding, fallback));
             }
         }
     }
+    rendered
+}
 
-    let mut document = kuchiki::parse_html().one(html);
-    for (binding, fragment_html, _assets_root) in rendered {
-        if let Err(err) =
-            replace_selector_inner_html(&mut document, &binding.selector, &fragment_html)
-        {
-            warn!(?binding, ?err, "failed to inject fragment html");
+impl RenderedFragment {
+    fn new(binding: &FragmentBinding, html: String) -> Self {
+        Self {
+            id: binding.id.clone(),
+            selector: binding.selector.clone(),
+            html,
+        }
+    }
+}
+
+/// Place a feature page into the layout's main slot and `contributions` (selector, html) into
+/// theirs.
+///
+/// The page's `<body>` content replaces the main slot's content and its `<head>` children are
+/// appended to the layout head. Fails when the layout has no element for the main slot.
+pub fn compose_page(
+    layout_html: &str,
+    main_selector: &str,
+    page_html: &str,
+    contributions: &[(String, String)],
+) -> Result<String, FragmentError> {
+    let mut document = kuchiki::parse_html().one(layout_html);
+    let main = document
+        .select_first(main_selector)
+        .map_err(|_| FragmentError::Html(format!("layout has no element for {main_selector}")))?;
+    let page = kuchiki::parse_html().one(page_html);
+    let existing: Vec<_> = main.as_node().children().collect();
+    for child in existing {
+        child.detach();
+    }
+    if let Ok(body) = page.select_first("body") {
+        let children: Vec<_> = body.as_node().children().collect();
+        for child in children {
+            main.as_node().append(child);
+        }
+    }
+    if let (Ok(head), Ok(page_head)) = (document.select_first("head"), page.select_first("head")) {
+        let children: Vec<_> = page_head.as_node().children().collect();
+        for child in children {
+            head.as_node().append(child);
         }
     }
 
+    for (selector, html) in contributions {
+        match replace_selector_inner_html(&mut document, selector, html) {
+            Ok(true) => {}
+            Ok(false) => debug!(%selector, "layout has no element for slot contribution"),
+            Err(err) => warn!(%selector, ?err, "failed to inject slot contribution"),
+        }
+    }
+    Ok(document.to_string())
+}
+
+/// Replace the inner html of the first element matching `selector`; unchanged if none matches.
+pub fn fill_slot(html: &str, selector: &str, inner_html: &str) -> Result<String, FragmentError> {
+    let mut document = kuchiki::parse_html().one(html);
+    replace_selector_inner_html(&mut document, selector, inner_html)?;
     Ok(document.to_string())
 }
 
+/// Replace the inner html of the first node matching `selector`; `Ok(false)` if none matches.
 fn replace_selector_inner_html(
     document: &mut NodeRef,
     selector: &str,
     new_html: &str,
-) -> Result<(), FragmentError> {
+) -> Result<bool, FragmentError> {
     let mut nodes = document
         .select(selector)
         .map_err(|e| FragmentError::Html(format!("query selector {selector} failed: {e:?}")))?;
@@ -367,13 +996,17 @@ fn replace_selector_inner_html(
                 node.append(child);
             }
         }
+        return Ok(true);
     }
-    Ok(())
+    Ok(false)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fragment_host::EnvSecretsProbe;
+    use crate::packs::FragmentCapabilities;
+    use crate::worker::{StubWorkerBackend, WorkerHost};
     use std::sync::Arc;
 
     struct DummyRenderer;
@@ -384,9 +1017,11 @@ mod tests {
             &self,
             _binding: &FragmentBinding,
             _assets_root: &Path,
-            _ctx: FragmentContext,
+            ctx: FragmentContext,
         ) -> Result<Option<String>, FragmentError> {
-            Ok(Some("<span class=\"injected\">ok</span>".to_string()))
+            let id = ctx.params.get("id").map_or("ok", String::as_str);
+            let user = ctx.user_id.as_deref().unwrap_or("anonymous");
+            Ok(Some(format!("<span class=\"injected\">{id}:{user}</span>")))
         }
     }
 
@@ -399,20 +1034,708 @@ mod tests {
                 selector: "#target".into(),
                 component_world: "greentic:gui/gui-fragment@1.0.0".into(),
                 component_name: "fragment".into(),
+                capabilities: FragmentCapabilities::default(),
             },
             assets_root: PathBuf::from("/tmp"),
+            pack: Arc::default(),
         }];
+        let session = SessionInfo {
+            session_id: "s1".into(),
+            tenant_ctx: crate::integration::build_tenant_ctx("dev", "tenant", None, Some("ann")),
+            user_id: Some("ann".into()),
+            scopes: vec![],
+        };
         let rendered = inject_fragments(
             html,
             &bindings,
-            None,
-            "tenant",
-            "/",
+            FragmentRequest {
+                session: Some(&session),
+                tenant_did: "tenant",
+                route: "/invoices/42",
+                params: &RouteParams::from([("id".to_string(), "42".to_string())]),
+                trace: None,
+            },
             Arc::new(DummyRenderer),
+            &FragmentBudget::default(),
         )
         .await
         .unwrap();
-        assert!(rendered.contains("class=\"injected\""));
+        assert!(rendered.contains("class=\"injected\">42:ann<"));
         assert!(!rendered.contains("old"));
     }
+
+    fn root_request(params: &RouteParams) -> FragmentRequest<'_> {
+        FragmentRequest {
+            session: None,
+            tenant_did: "tenant",
+            route: "/",
+            params,
+            trace: None,
+        }
+    }
+
+    /// Sleeps for the millis in the binding's component name, tracking concurrent renders.
+    #[derive(Default)]
+    struct SleepyRenderer {
+        in_flight: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait]
+    impl FragmentRenderer for SleepyRenderer {
+        async fn render_fragment(
+            &self,
+            binding: &FragmentBinding,
+            _assets_root: &Path,
+            _ctx: FragmentContext,
+        ) -> Result<Option<String>, FragmentError> {
+            use std::sync::atomic::Ordering;
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(now, Ordering::SeqCst);
+            let millis = binding.component_name.parse().unwrap();
+            tokio::time::sleep(Duration::from_millis(millis)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(Some(binding.id.clone()))
+        }
+    }
+
+    fn sleepy_target(id: &str, selector: &str, millis: u64) -> FragmentTarget {
+        FragmentTarget {
+            binding: FragmentBinding {
+                id: id.into(),
+                selector: selector.into(),
+                component_world: "greentic:gui/gui-fragment@1.0.0".into(),
+                component_name: millis.to_string(),
+                capabilities: FragmentCapabilities::default(),
+            },
+            assets_root: PathBuf::from("/tmp"),
+            pack: Arc::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn renders_fragments_concurrently_in_binding_order() {
+        let bindings = vec![
+            sleepy_target("a", "#one", 80),
+            sleepy_target("b", "#two", 10),
+            sleepy_target("c", "#one", 40),
+            sleepy_target("d", "#three", 10),
+        ];
+        let renderer = Arc::new(SleepyRenderer::default());
+        let budget = FragmentBudget {
+            concurrency: 2,
+            ..FragmentBudget::default()
+        };
+        let rendered = render_fragments(
+            &bindings,
+            root_request(&RouteParams::default()),
+            renderer.clone(),
+            &budget,
+        )
+        .await;
+        let ids: Vec<_> = rendered.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c", "d"]);
+        assert_eq!(renderer.peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // Later bindings for the same selector still win.
+        let html = inject_fragments(
+            "<div id=\"one\"></div><div id=\"two\"></div><div id=\"three\"></div>".into(),
+            &bindings,
+            root_request(&RouteParams::default()),
+            renderer,
+            &budget,
+        )
+        .await
+        .unwrap();
+        assert!(html.contains("<div id=\"one\">c</div>"), "{html}");
+    }
+
+    #[tokio::test]
+    async fn fragments_past_the_page_deadline_get_placeholders() {
+        let bindings = vec![
+            sleepy_target("fast", "#fast", 5),
+            sleepy_target("slow", "#slow", 10_000),
+        ];
+        let budget = FragmentBudget {
+            page_timeout: Duration::from_millis(100),
+            ..FragmentBudget::default()
+        };
+        let (exporter, _guard) = crate::otlp::record_spans();
+        let started = Instant::now();
+        let rendered = render_fragments(
+            &bindings,
+            root_request(&RouteParams::default()),
+            Arc::new(SleepyRenderer::default()),
+            &budget,
+        )
+        .instrument(tracing::info_span!("gui_request"))
+        .await;
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert_eq!(rendered[0].html, "fast");
+        assert_eq!(
+            rendered[1].html,
+            "<div class=\"fragment-error\" data-fragment-id=\"slow\">fragment timed out</div>"
+        );
+
+        // Each fragment logs its own event on the request span, which closes once the aborted
+        // render task has been dropped.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let spans = exporter.get_finished_spans().unwrap();
+        let page = spans
+            .iter()
+            .find(|span| span.name == "gui_request")
+            .unwrap();
+        let events: Vec<_> = page
+            .events
+            .iter()
+            .map(|event| {
+                let id = event
+                    .attributes
+                    .iter()
+                    .find(|kv| kv.key.as_str() == "id")
+                    .map(|kv| kv.value.to_string());
+                let timed = event
+                    .attributes
+                    .iter()
+                    .any(|kv| kv.key.as_str() == "duration_ms");
+                (event.name.to_string(), id, timed)
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                ("fragment rendered".into(), Some("fast".into()), true),
+                (
+                    "fragment missed the page deadline".into(),
+                    Some("slow".into()),
+                    false
+                ),
+            ]
+        );
+    }
+
+    /// A gui-fragment@1.0.0 component around `core`, which must define a core instance `$i`
+    /// exporting `memory`, `realloc` and the flattened `render` function.
+    fn fragment_component(core: &str) -> Vec<u8> {
+        versioned_component(core, "1.0.0")
+    }
+
+    fn versioned_component(core: &str, version: &str) -> Vec<u8> {
+        wat::parse_str(format!(
+            r#"(component
+                {core}
+                (type $ctx (record
+                    (field "tenant-ctx" string) (field "user-ctx" string)
+                    (field "route" string) (field "session-id" string)))
+                (func $render
+                    (param "fragment-id" string) (param "ctx" $ctx)
+                    (result (result string (error string)))
+                    (canon lift (core func $i "render")
+                        (memory $i "memory") (realloc (func $i "realloc"))))
+                (component $api
+                    (type $record (record
+                        (field "tenant-ctx" string) (field "user-ctx" string)
+                        (field "route" string) (field "session-id" string)))
+                    (import "import-type-fragment-context" (type $ctx (eq $record)))
+                    (import "import-func-render-fragment" (func $render
+                        (param "fragment-id" string) (param "ctx" $ctx)
+                        (result (result string (error string)))))
+                    (export $exported "fragment-context" (type $ctx))
+                    (export "render-fragment" (func $render) (func
+                        (param "fragment-id" string) (param "ctx" $exported)
+                        (result (result string (error string))))))
+                (instance $fragment_api (instantiate $api
+                    (with "import-type-fragment-context" (type $ctx))
+                    (with "import-func-render-fragment" (func $render))))
+                (export "greentic:gui/fragment-api@{version}" (instance $fragment_api)))"#
+        ))
+        .unwrap()
+    }
+
+    /// Core instance whose `render` has the given body; its result area at offset 0 holds
+    /// `ok("<p>hi</p>")`.
+    fn pure_core(render_body: &str) -> String {
+        format!(
+            r#"(core module $m
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\00\00\00\00\10\00\00\00\09\00\00\00")
+                (data (i32.const 16) "<p>hi</p>")
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32) i32.const 1024)
+                (func (export "render")
+                    (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32) (result i32)
+                    {render_body}))
+            (core instance $i (instantiate $m))"#
+        )
+    }
+
+    /// Core instance whose `render` returns `call-worker("worker.echo", get-config("payload"))`,
+    /// or an empty error when the config lookup yields nothing.
+    const HOST_CORE: &str = r#"
+        (import "greentic:gui/fragment-host@1.0.0" (instance $host
+            (export "get-config" (func (param "key" string) (result (option string))))
+            (export "call-worker" (func
+                (param "worker-id" string) (param "payload" string)
+                (result (result string (error string)))))))
+        (core module $mem
+            (memory (export "memory") 1)
+            (global $heap (mut i32) (i32.const 1024))
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                (global.get $heap)
+                (global.set $heap (i32.add (global.get $heap) (local.get 3)))))
+        (core instance $mem (instantiate $mem))
+        (alias export $host "get-config" (func $get_config))
+        (alias export $host "call-worker" (func $call_worker))
+        (core func $get_config_lowered (canon lower (func $get_config)
+            (memory $mem "memory") (realloc (func $mem "realloc"))))
+        (core func $call_worker_lowered (canon lower (func $call_worker)
+            (memory $mem "memory") (realloc (func $mem "realloc"))))
+        (core module $m
+            (import "mem" "memory" (memory 1))
+            (import "mem" "realloc" (func $realloc (param i32 i32 i32 i32) (result i32)))
+            (import "host" "get-config" (func $get_config (param i32 i32 i32)))
+            (import "host" "call-worker" (func $call_worker (param i32 i32 i32 i32 i32)))
+            (export "memory" (memory 0))
+            (export "realloc" (func $realloc))
+            (data (i32.const 100) "payload")
+            (data (i32.const 120) "worker.echo")
+            (func (export "render")
+                (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32) (result i32)
+                (call $get_config (i32.const 100) (i32.const 7) (i32.const 200))
+                (if (i32.eqz (i32.load8_u (i32.const 200)))
+                    (then
+                        (i32.store (i32.const 300) (i32.const 1))
+                        (i32.store (i32.const 304) (i32.const 0))
+                        (i32.store (i32.const 308) (i32.const 0))
+                        (return (i32.const 300))))
+                (call $call_worker
+                    (i32.const 120) (i32.const 11)
+                    (i32.load (i32.const 204)) (i32.load (i32.const 208))
+                    (i32.const 300))
+                (i32.const 300)))
+        (core instance $i (instantiate $m
+            (with "mem" (instance $mem))
+            (with "host" (instance
+                (export "get-config" (func $get_config_lowered))
+                (export "call-worker" (func $call_worker_lowered))))))"#;
+
+    /// Core instance whose `render` returns `route-param("id")`, or an empty error without one.
+    const ROUTE_PARAM_CORE: &str = r#"
+        (import "greentic:gui/fragment-host@1.0.0" (instance $host
+            (export "route-param" (func (param "name" string) (result (option string))))))
+        (core module $mem
+            (memory (export "memory") 1)
+            (global $heap (mut i32) (i32.const 1024))
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                (global.get $heap)
+                (global.set $heap (i32.add (global.get $heap) (local.get 3)))))
+        (core instance $mem (instantiate $mem))
+        (alias export $host "route-param" (func $route_param))
+        (core func $route_param_lowered (canon lower (func $route_param)
+            (memory $mem "memory") (realloc (func $mem "realloc"))))
+        (core module $m
+            (import "mem" "memory" (memory 1))
+            (import "mem" "realloc" (func $realloc (param i32 i32 i32 i32) (result i32)))
+            (import "host" "route-param" (func $route_param (param i32 i32 i32)))
+            (export "memory" (memory 0))
+            (export "realloc" (func $realloc))
+            (data (i32.const 100) "id")
+            (func (export "render")
+                (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32) (result i32)
+                (call $route_param (i32.const 100) (i32.const 2) (i32.const 200))
+                (i32.store (i32.const 300) (i32.eqz (i32.load8_u (i32.const 200))))
+                (i32.store (i32.const 304) (i32.load (i32.const 204)))
+                (i32.store (i32.const 308) (i32.load (i32.const 208)))
+                (i32.const 300)))
+        (core instance $i (instantiate $m
+            (with "mem" (instance $mem))
+            (with "host" (instance (export "route-param" (func $route_param_lowered))))))"#;
+
+    fn fragment_assets(core: &str) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("fragments")).unwrap();
+        std::fs::write(
+            dir.path().join("fragments/widget.wasm"),
+            fragment_component(core),
+        )
+        .unwrap();
+        dir
+    }
+
+    fn widget(capabilities: FragmentCapabilities) -> FragmentBinding {
+        FragmentBinding {
+            id: "widget".into(),
+            selector: "#widget".into(),
+            component_world: "greentic:gui/gui-fragment@1.0.0".into(),
+            component_name: "widget".into(),
+            capabilities,
+        }
+    }
+
+    async fn render_widget(
+        invoker: &WasmtimeFragmentInvoker,
+        assets: &Path,
+        capabilities: FragmentCapabilities,
+        pack: PackScope,
+    ) -> Result<String, FragmentError> {
+        let ctx = FragmentContext {
+            pack: Arc::new(pack),
+            ..widget_ctx()
+        };
+        invoker.render(&widget(capabilities), assets, ctx).await
+    }
+
+    fn widget_ctx() -> FragmentContext {
+        FragmentContext {
+            tenant_ctx: "tenant".into(),
+            user_ctx: "{}".into(),
+            route: "/invoices/7".into(),
+            session_id: String::new(),
+            user_id: None,
+            params: RouteParams::from([("id".to_string(), "7".to_string())]),
+            pack: Arc::default(),
+            trace: None,
+        }
+    }
+
+    async fn invoke(limits: FragmentLimits, render_body: &str) -> Result<String, FragmentError> {
+        let assets = fragment_assets(&pure_core(render_body));
+        let invoker = WasmtimeFragmentInvoker::new(limits).unwrap();
+        render_widget(
+            &invoker,
+            assets.path(),
+            FragmentCapabilities::default(),
+            PackScope::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn wasmtime_invoker_renders_within_limits() {
+        let html = invoke(FragmentLimits::default(), "i32.const 0")
+            .await
+            .unwrap();
+        assert_eq!(html, "<p>hi</p>");
+    }
+
+    #[tokio::test]
+    async fn wasmtime_invoker_stops_runaway_fragments() {
+        let spin = "(loop $spin (br $spin)) i32.const 0";
+        let fuel = FragmentLimits {
+            fuel: Some(100_000),
+            ..FragmentLimits::default()
+        };
+        let err = invoke(fuel, spin).await.unwrap_err();
+        assert!(
+            matches!(&err, FragmentError::ResourceExhausted(msg) if msg.contains("fuel")),
+            "{err}"
+        );
+
+        let wall_clock = FragmentLimits {
+            fuel: None,
+            timeout: Duration::from_millis(100),
+            ..FragmentLimits::default()
+        };
+        let err = invoke(wall_clock, spin).await.unwrap_err();
+        assert!(
+            matches!(&err, FragmentError::ResourceExhausted(msg) if msg.contains("timed out")),
+            "{err}"
+        );
+
+        let memory = FragmentLimits {
+            max_memory_bytes: 1 << 20,
+            ..FragmentLimits::default()
+        };
+        let grow = "(drop (memory.grow (i32.const 32))) i32.const 0";
+        let err = invoke(memory, grow).await.unwrap_err();
+        assert!(
+            matches!(&err, FragmentError::ResourceExhausted(msg) if msg.contains("memory")),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn exhausted_fragments_render_error_placeholder() {
+        let assets = fragment_assets(&pure_core("(loop $spin (br $spin)) i32.const 0"));
+        let limits = FragmentLimits {
+            fuel: Some(100_000),
+            ..FragmentLimits::default()
+        };
+        let invoker = Arc::new(WasmtimeFragmentInvoker::new(limits).unwrap());
+        let bindings = vec![FragmentTarget {
+            binding: widget(FragmentCapabilities::default()),
+            assets_root: assets.path().to_path_buf(),
+            pack: Arc::default(),
+        }];
+        let rendered = render_fragments(
+            &bindings,
+            root_request(&RouteParams::default()),
+            Arc::new(CompositeFragmentRenderer::with_wit(invoker)),
+            &FragmentBudget::default(),
+        )
+        .await;
+        assert_eq!(rendered.len(), 1);
+        assert!(
+            rendered[0]
+                .html
+                .contains("class=\"fragment-error\" data-fragment-id=\"widget\""),
+            "{}",
+            rendered[0].html
+        );
+    }
+
+    #[test]
+    fn parses_supported_component_worlds() {
+        let world = FragmentWorld::parse("greentic:gui/gui-fragment@1.2.0").unwrap();
+        assert_eq!(world.api(), Some(FragmentApi::V1));
+        assert_eq!(world.api_export(), "greentic:gui/fragment-api@1.2.0");
+        for rejected in [
+            "greentic:gui/gui-fragment@2.0.0",
+            "greentic:gui/gui-fragment",
+            "greentic:gui/gui-fragment@latest",
+            "greentic:worker/worker@1.0.0",
+        ] {
+            assert!(
+                matches!(
+                    FragmentWorld::parse(rejected),
+                    Err(FragmentError::UnsupportedWorld(_))
+                ),
+                "{rejected}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn components_of_different_world_versions_coexist() {
+        let assets = fragment_assets(&pure_core("i32.const 0"));
+        std::fs::write(
+            assets.path().join("fragments/newer.wasm"),
+            versioned_component(&pure_core("i32.const 0"), "1.2.0"),
+        )
+        .unwrap();
+        let invoker = WasmtimeFragmentInvoker::new(FragmentLimits::default()).unwrap();
+        let binding_for = |name: &str, world: &str| {
+            let mut binding = widget(FragmentCapabilities::default());
+            binding.component_name = name.into();
+            binding.component_world = world.into();
+            binding
+        };
+
+        let older = binding_for("widget", "greentic:gui/gui-fragment@1.0.0");
+        let newer = binding_for("newer", "greentic:gui/gui-fragment@1.2.0");
+        for binding in [&older, &newer] {
+            let html = invoker
+                .render(binding, assets.path(), widget_ctx())
+                .await
+                .unwrap();
+            assert_eq!(html, "<p>hi</p>");
+        }
+    }
+
+    #[tokio::test]
+    async fn host_imports_follow_binding_capabilities() {
+        let assets = fragment_assets(HOST_CORE);
+        let host = FragmentHost {
+            worker_host: Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+            secrets: Arc::new(EnvSecretsProbe),
+            env_id: "dev".into(),
+            team: "gui".into(),
+        };
+        let invoker = WasmtimeFragmentInvoker::new(FragmentLimits::default())
+            .unwrap()
+            .with_host(host);
+        let mut config = serde_json::Map::new();
+        config.insert("payload".into(), serde_json::json!({ "greeting": "hi" }));
+        let pack = PackScope {
+            pack: "billing".into(),
+            config,
+            ..PackScope::default()
+        };
+
+        let granted = FragmentCapabilities {
+            config: true,
+            workers: vec!["worker.echo".into()],
+            ..FragmentCapabilities::default()
+        };
+        let html = render_widget(&invoker, assets.path(), granted, pack.clone())
+            .await
+            .unwrap();
+        let response: serde_json::Value = serde_json::from_str(&html).unwrap();
+        assert_eq!(response["worker_id"], "worker.echo");
+        assert_eq!(response["messages"][0]["payload"]["greeting"], "hi");
+
+        let no_worker = FragmentCapabilities {
+            config: true,
+            ..FragmentCapabilities::default()
+        };
+        let err = render_widget(&invoker, assets.path(), no_worker, pack.clone())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not allowed"), "{err}");
+
+        let nothing = FragmentCapabilities::default();
+        assert!(
+            render_widget(&invoker, assets.path(), nothing, pack)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn components_read_route_params() {
+        let assets = fragment_assets(ROUTE_PARAM_CORE);
+        let invoker = WasmtimeFragmentInvoker::new(FragmentLimits::default()).unwrap();
+        let binding = widget(FragmentCapabilities::default());
+        let html = invoker
+            .render(&binding, assets.path(), widget_ctx())
+            .await
+            .unwrap();
+        assert_eq!(html, "7");
+
+        let no_params = FragmentContext {
+            params: RouteParams::default(),
+            ..widget_ctx()
+        };
+        assert!(
+            invoker
+                .render(&binding, assets.path(), no_params)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn composes_page_into_layout_slots() {
+        let layout = "<html><head><title>L</title></head><body>\
+            <header data-slot=\"header\">old</header>\
+            <div data-slot=\"main\">placeholder</div></body></html>";
+        let page = "<html><head><link href=\"/x.css\" rel=\"stylesheet\"></head>\
+            <body><h1>Invoices</h1></body></html>";
+        let contributions = vec![(
+            "[data-slot=\"header\"]".to_string(),
+            "<nav>billing</nav>".to_string(),
+        )];
+        let html = compose_page(layout, "[data-slot=\"main\"]", page, &contributions).unwrap();
+        assert!(
+            html.contains("<div data-slot=\"main\"><h1>Invoices</h1></div>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<link href=\"/x.css\" rel=\"stylesheet\"></head>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<header data-slot=\"header\"><nav>billing</nav></header>"),
+            "{html}"
+        );
+
+        assert!(compose_page(layout, "#missing", page, &[]).is_err());
+    }
+
+    #[tokio::test]
+    async fn precompiled_components_are_cached_by_content_hash() {
+        let assets = fragment_assets(&pure_core("i32.const 0"));
+        let cache_dir = tempfile::tempdir().unwrap();
+        let cached_invoker = || {
+            WasmtimeFragmentInvoker::new(FragmentLimits::default())
+                .unwrap()
+                .with_cache_dir(cache_dir.path().to_path_buf())
+        };
+        let artifacts = || {
+            std::fs::read_dir(cache_dir.path())
+                .unwrap()
+                .flat_map(|engine_dir| std::fs::read_dir(engine_dir.unwrap().path()).unwrap())
+                .map(|entry| entry.unwrap().path())
+                .collect::<Vec<_>>()
+        };
+
+        // Artifacts of other engines are removed when the cache dir is configured.
+        let stale = cache_dir.path().join("0123456789abcdef");
+        std::fs::create_dir_all(&stale).unwrap();
+        std::fs::write(stale.join("old.cwasm"), b"old").unwrap();
+
+        let binding = widget(FragmentCapabilities::default());
+        cached_invoker()
+            .precompile(&binding, assets.path())
+            .await
+            .unwrap();
+        assert!(!stale.exists());
+        let artifact = match artifacts().as_slice() {
+            [artifact] => artifact.clone(),
+            other => panic!("expected one artifact, got {other:?}"),
+        };
+
+        std::fs::write(&artifact, b"not a component").unwrap();
+        let invoker = cached_invoker();
+        let html = render_widget(
+            &invoker,
+            assets.path(),
+            FragmentCapabilities::default(),
+            PackScope::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(html, "<p>hi</p>");
+        assert!(matches!(
+            Engine::detect_precompiled_file(&artifact),
+            Ok(Some(Precompiled::Component))
+        ));
+
+        let fails = "(i32.store8 (i32.const 0) (i32.const 1)) i32.const 0";
+        std::fs::write(
+            assets.path().join("fragments/widget.wasm"),
+            fragment_component(&pure_core(fails)),
+        )
+        .unwrap();
+        let rebuilt = render_widget(
+            &invoker,
+            assets.path(),
+            FragmentCapabilities::default(),
+            PackScope::default(),
+        )
+        .await;
+        assert!(rebuilt.is_err(), "a rebuilt component must be recompiled");
+        // The replaced component's artifact is deleted with it.
+        let rebuilt_artifact = match artifacts().as_slice() {
+            [rebuilt_artifact] => rebuilt_artifact.clone(),
+            other => panic!("expected one artifact, got {other:?}"),
+        };
+        assert_ne!(rebuilt_artifact, artifact);
+
+        invoker.evict(assets.path()).await;
+        assert!(artifacts().is_empty());
+    }
+
+    #[tokio::test]
+    async fn evict_forces_a_recompile() {
+        let assets = fragment_assets(&pure_core("i32.const 0"));
+        let invoker = WasmtimeFragmentInvoker::new(FragmentLimits::default()).unwrap();
+        let render = || {
+            render_widget(
+                &invoker,
+                assets.path(),
+                FragmentCapabilities::default(),
+                PackScope::default(),
+            )
+        };
+        assert_eq!(render().await.unwrap(), "<p>hi</p>");
+
+        // Same size and modification time, so only an eviction notices the new content.
+        let path = assets.path().join("fragments/widget.wasm");
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let wasm = std::fs::read(&path).unwrap();
+        let at = wasm.windows(9).position(|w| w == b"<p>hi</p>").unwrap();
+        let mut changed = wasm.clone();
+        changed[at..at + 9].copy_from_slice(b"<p>ho</p>");
+        std::fs::write(&path, changed).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert_eq!(render().await.unwrap(), "<p>hi</p>");
+
+        invoker.evict(assets.path()).await;
+        assert_eq!(render().await.unwrap(), "<p>ho</p>");
+    }
 }
@@ -6,6 +6,10 @@ type InitOptions = {
   configUrl?: string;
   eventsUrl?: string;
   workerMessageUrl?: string;
+  /** Queued events that trigger an immediate flush (default 20). */
+  batchSize?: number;
+  /** Longest time an event waits in the queue, in ms (default 5000). */
+  flushIntervalMs?: number;
 };
 
 type AttachWorkerOptions = {
@@ -26,16 +30,101 @@ type EventOptions = {
 };
 
 type StartSessionOptions = {
-  userId: string;
+  /** Dev only; outside dev the user comes from `token`. */
+  userId?: string;
   team?: string;
+  /** Signed bearer token used to establish the session outside dev. */
+  token?: string;
 };
 
 type GuiConfig = {
   tenant: string;
   domain: string;
   routes: any[];
+  route_conflicts?: any[];
+  /** Navigation entries visible to the current session. */
+  menu?: MenuItem[];
   workers: any[];
   skin?: string;
+  /** Resolved skin: CSS custom properties and asset URLs. */
+  theme?: Theme | null;
+  /** Client telemetry policy from the tenant's telemetry pack. */
+  telemetry?: TelemetryPolicy;
+  live_reload?: string | null;
+};
+
+type TelemetryPolicy = {
+  sample_rate: number;
+  sampling: Record<string, number>;
+  /** Event types the server accepts; a trailing `*` matches a prefix. Empty allows all. */
+  allowed_events: string[];
+  auto_capture: {
+    page_views: boolean;
+    clicks: boolean;
+    errors: boolean;
+    web_vitals: boolean;
+  };
+};
+
+type Theme = {
+  variables: Record<string, string>;
+  logo?: string;
+  favicon?: string;
+  stylesheets: string[];
+};
+
+type RouteInfo = {
+  path: string;
+  /** Manifest pattern that matched, e.g. `/invoices/:id`; null for the layout fallback. */
+  pattern: string | null;
+  params: Record<string, string>;
+};
+
+type MenuItem = {
+  label: string;
+  href: string;
+  icon?: string;
+  group?: string;
+  order: number;
+};
+
+type RouteManifestEntry = {
+  path: string;
+  authenticated: boolean;
+  /** Fragment ids rendered for the route. */
+  fragments: string[];
+  pack: string;
+};
+
+type RenderedFragment = {
+  id: string;
+  selector: string;
+  html: string;
+};
+
+type NavigateOptions = {
+  /** Replace the current history entry instead of pushing a new one. */
+  replace?: boolean;
+};
+
+type QueuedEvent = {
+  event_type: string;
+  path: string;
+  timestamp: number;
+  /** Trace context of the server request that rendered the page. */
+  traceparent?: string;
+  metadata: any;
+};
+
+type FlushOptions = {
+  /** Send with `navigator.sendBeacon` so the batch survives page unload. */
+  beacon?: boolean;
+};
+
+type PackChange = {
+  tenant: string;
+  packs: string[];
+  reload: "css" | "full";
 };
 
 type InternalConfig = InitOptions & { guiConfig?: GuiConfig };
@@ -48,13 +137,24 @@ declare global {
 
 const version = "0.3.0";
 let config: InternalConfig | null = null;
+let liveReload: EventSource | null = null;
+let spaNavigation = false;
+let autoCapture = false;
+let eventQueue: QueuedEvent[] = [];
+let flushTimer: ReturnType<typeof setTimeout> | null = null;
+let unloadFlush = false;
+
+// The server accepts at most this many events per request.
+const MAX_BATCH = 100;
 
 async function init(opts: InitOptions = {}): Promise<InternalConfig> {
   config = {
     tenantDomain: opts.tenantDomain || window.location.host,
-    configUrl: opts.configUrl || "/api/gui/config",
-    eventsUrl: opts.eventsUrl || "/api/gui/events",
-    workerMessageUrl: opts.workerMessageUrl || "/api/gui/worker/message",
+    configUrl: opts.configUrl || serverUrl("/api/gui/config"),
+    eventsUrl: opts.eventsUrl || serverUrl("/api/gui/events"),
+    workerMessageUrl: opts.workerMessageUrl || serverUrl("/api/gui/worker/message"),
+    batchSize: opts.batchSize || 20,
+    flushIntervalMs: opts.flushIntervalMs || 5000,
   };
   try {
     const res = await fetch(config.configUrl!);
@@ -62,9 +162,41 @@ async function init(opts: InitOptions = {}): Promise<InternalConfig> {
   } catch (err) {
     console.warn("GreenticGUI: failed to load GUI config", err);
   }
+  startLiveReload(config.guiConfig?.live_reload);
+  startSpaNavigation();
+  startAutoCapture(config.guiConfig?.telemetry);
+  startUnloadFlush();
   return config;
 }
 
+// Dev servers advertise a live-reload stream; skin CSS edits swap stylesheets, anything else reloads.
+function startLiveReload(url?: string | null) {
+  if (!url || liveReload || typeof EventSource === "undefined") return;
+  liveReload = new EventSource(url);
+  liveReload.addEventListener("pack-change", (evt) => {
+    let change: PackChange;
+    try {
+      change = JSON.parse((evt as MessageEvent).data);
+    } catch (err) {
+      return;
+    }
+    if (change.reload === "css") {
+      swapStylesheets();
+    } else {
+      window.location.reload();
+    }
+  });
+}
+
+function swapStylesheets() {
+  document.querySelectorAll<HTMLLinkElement>('link[rel="stylesheet"]').forEach((link) => {
+    const url = new URL(link.href, window.location.href);
+    if (url.origin !== window.location.origin) return;
+    url.searchParams.set("greentic-reload", String(Date.now()));
+    link.href = url.toString();
+  });
+}
+
 function attachWorker({ workerId, selector, routes = [] }: AttachWorkerOptions): Element | null {
   const el = document.querySelector(selector);
   if (!el) {
@@ -81,39 +213,295 @@ async function sendWorkerMessage({ workerId, payload = {}, context = {} }: Worke
   const body = {
     worker_id: workerId,
     payload,
-    context: Object.assign({ path: window.location.pathname }, context),
+    context: Object.assign({ path: appPath(window.location.pathname) }, context),
   };
   const res = await fetch(config!.workerMessageUrl!, {
     method: "POST",
-    headers: { "Content-Type": "application/json" },
+    headers: Object.assign({ "Content-Type": "application/json" }, traceHeaders()),
     body: JSON.stringify(body),
   });
   return res.json();
 }
 
+// Events are queued and sent in batches; see `flush`.
 async function sendEvent({ eventType, metadata = {} }: EventOptions) {
   if (!config) await init();
-  try {
-    await fetch(config!.eventsUrl!, {
-      method: "POST",
-      headers: { "Content-Type": "application/json" },
-      body: JSON.stringify({
-        event_type: eventType,
-        path: window.location.pathname,
-        timestamp: Date.now(),
-        metadata,
-      }),
+  if (!shouldSend(eventType)) return;
+  eventQueue.push({
+    event_type: eventType,
+    path: appPath(window.location.pathname),
+    timestamp: Date.now(),
+    traceparent: pageTraceparent(),
+    metadata,
+  });
+  if (eventQueue.length >= config!.batchSize!) {
+    await flush();
+  } else {
+    scheduleFlush();
+  }
+}
+
+// Served pages carry `<meta name="traceparent">` (and `tracestate`) for the request that rendered them.
+function pageMeta(name: string): string | undefined {
+  if (typeof document === "undefined") return undefined;
+  const meta = document.querySelector?.(`meta[name="${name}"]`);
+  return meta?.getAttribute("content") || undefined;
+}
+
+// Path-prefix tenants (`/t/acme/...`) get `<meta name="greentic-base">`; server URLs live under it
+// and routes are matched without it.
+function basePath(): string {
+  return (pageMeta("greentic-base") || "").replace(/\/+$/, "");
+}
+
+function serverUrl(path: string): string {
+  return basePath() + path;
+}
+
+function appPath(pathname: string): string {
+  const base = basePath();
+  if (!base || (pathname !== base && !pathname.startsWith(`${base}/`))) return pathname;
+  return pathname.slice(base.length) || "/";
+}
+
+function pageTraceparent(): string | undefined {
+  return pageMeta("traceparent");
+}
+
+// W3C trace headers for a request made from this page: a new span in the page's trace.
+function traceHeaders(): Record<string, string> {
+  const parts = pageTraceparent()?.split("-");
+  if (!parts || parts.length !== 4) return {};
+  const headers: Record<string, string> = { traceparent: `00-${parts[1]}-${randomHex(8)}-${parts[3]}` };
+  const state = pageMeta("tracestate");
+  if (state) headers.tracestate = state;
+  return headers;
+}
+
+function randomHex(bytes: number): string {
+  const buf = new Uint8Array(bytes);
+  if (typeof crypto !== "undefined" && crypto.getRandomValues) {
+    crypto.getRandomValues(buf);
+  } else {
+    for (let i = 0; i < bytes; i++) buf[i] = Math.floor(Math.random() * 256);
+  }
+  if (buf.every((b) => b === 0)) buf[bytes - 1] = 1;
+  return Array.from(buf, (b) => b.toString(16).padStart(2, "0")).join("");
+}
+
+function scheduleFlush() {
+  if (flushTimer || typeof setTimeout === "undefined") return;
+  flushTimer = setTimeout(() => {
+    flushTimer = null;
+    flush();
+  }, config!.flushIntervalMs);
+}
+
+// Send every queued event. `sent_at` lets the server correct event timestamps for clock skew.
+async function flush({ beacon = false }: FlushOptions = {}) {
+  if (flushTimer) {
+    clearTimeout(flushTimer);
+    flushTimer = null;
+  }
+  if (!config) return;
+  while (eventQueue.length) {
+    const events = eventQueue.splice(0, MAX_BATCH);
+    const body = JSON.stringify({ sent_at: Date.now(), events });
+    // A string beacon goes out as text/plain, which the events endpoint accepts.
+    if (beacon && typeof navigator !== "undefined" && navigator.sendBeacon?.(config.eventsUrl!, body)) {
+      continue;
+    }
+    try {
+      await fetch(config.eventsUrl!, {
+        method: "POST",
+        headers: Object.assign({ "Content-Type": "application/json" }, traceHeaders()),
+        body,
+        keepalive: beacon,
+      });
+    } catch (err) {
+      console.warn("GreenticGUI: failed to send events", err);
+    }
+  }
+}
+
+// Hidden pages may never come back, so whatever is queued goes out as a beacon. Registered after
+// auto-capture so web vitals reported on the same event make it into the batch.
+function startUnloadFlush() {
+  if (unloadFlush || typeof document === "undefined") return;
+  unloadFlush = true;
+  document.addEventListener("visibilitychange", () => {
+    if (document.visibilityState === "hidden") flush({ beacon: true });
+  });
+}
+
+// Mirrors the server allowlist and applies the telemetry pack's sampling rates.
+function shouldSend(eventType: string): boolean {
+  const policy = config?.guiConfig?.telemetry;
+  if (!policy) return true;
+  const allowed = policy.allowed_events || [];
+  const listed = allowed.some((entry) =>
+    entry.endsWith("*") ? eventType.startsWith(entry.slice(0, -1)) : entry === eventType
+  );
+  if (allowed.length && !listed) return false;
+  const rate = policy.sampling?.[eventType] ?? policy.sample_rate ?? 1;
+  return Math.random() < rate;
+}
+
+// Telemetry packs opt into page views, `[data-track]` clicks, uncaught errors and web vitals.
+function startAutoCapture(policy?: TelemetryPolicy) {
+  const capture = policy?.auto_capture;
+  if (!capture || autoCapture || typeof document === "undefined") return;
+  autoCapture = true;
+  if (capture.page_views) {
+    const pageView = (route: RouteInfo) =>
+      sendEvent({ eventType: "page_view", metadata: { title: document.title, pattern: route.pattern } });
+    pageView(getRoute());
+    window.addEventListener("greentic:navigate", (evt) => pageView((evt as CustomEvent<RouteInfo>).detail));
+  }
+  if (capture.clicks) {
+    document.addEventListener(
+      "click",
+      (evt) => {
+        const el = (evt.target as Element | null)?.closest?.("[data-track]");
+        if (el) sendEvent({ eventType: "click", metadata: { track: el.getAttribute("data-track") } });
+      },
+      true
+    );
+  }
+  if (capture.errors) {
+    window.addEventListener("error", (evt) => {
+      const err = evt as ErrorEvent;
+      sendEvent({
+        eventType: "js_error",
+        metadata: { message: err.message, source: err.filename, line: err.lineno, column: err.colno },
+      });
+    });
+    window.addEventListener("unhandledrejection", (evt) => {
+      const reason = (evt as PromiseRejectionEvent).reason;
+      sendEvent({ eventType: "js_error", metadata: { message: String(reason?.message ?? reason), unhandled: true } });
     });
+  }
+  if (capture.web_vitals) startWebVitals();
+}
+
+// LCP, CLS and INP are reported once, when the page is first hidden.
+function startWebVitals() {
+  if (typeof PerformanceObserver === "undefined") return;
+  const vitals: Record<string, number> = {};
+  const observe = (type: string, onEntry: (entry: any) => void, opts: Record<string, any> = {}) => {
+    try {
+      new PerformanceObserver((list) => list.getEntries().forEach(onEntry)).observe({
+        type,
+        buffered: true,
+        ...opts,
+      } as PerformanceObserverInit);
+    } catch (err) {
+      // Entry type not supported by this browser.
+    }
+  };
+  observe("largest-contentful-paint", (entry) => (vitals.LCP = entry.startTime));
+  observe("layout-shift", (entry) => {
+    if (!entry.hadRecentInput) vitals.CLS = (vitals.CLS || 0) + entry.value;
+  });
+  observe("event", (entry) => (vitals.INP = Math.max(vitals.INP || 0, entry.duration)), { durationThreshold: 40 });
+  let reported = false;
+  document.addEventListener("visibilitychange", () => {
+    if (reported || document.visibilityState !== "hidden") return;
+    reported = true;
+    Object.keys(vitals).forEach((name) => sendEvent({ eventType: "web_vital", metadata: { name, value: vitals[name] } }));
+  });
+}
+
+function readJsonScript(id: string): any {
+  if (typeof document === "undefined") return null;
+  const el = document.getElementById(id);
+  if (!el || !el.textContent) return null;
+  try {
+    return JSON.parse(el.textContent);
   } catch (err) {
-    console.warn("GreenticGUI: failed to send event", err);
+    return null;
   }
 }
 
-async function startSession({ userId, team }: StartSessionOptions) {
+// The server embeds the matched route and its captured params in every page it renders.
+function getRoute(): RouteInfo {
+  const fallback: RouteInfo = { path: appPath(window.location.pathname), pattern: null, params: {} };
+  return Object.assign(fallback, readJsonScript("greentic-route"));
+}
+
+// SPA layouts embed the tenant's route table in their entrypoint; other pages have none.
+function getRoutes(): RouteManifestEntry[] {
+  return readJsonScript("greentic-routes")?.routes || [];
+}
+
+function isSpa(): boolean {
+  return readJsonScript("greentic-routes") !== null;
+}
+
+// Swap in the target route's fragments and update history. Falls back to a full page load outside
+// SPA layouts and whenever the server declines (login required, unknown route).
+async function navigate(path: string, { replace = false }: NavigateOptions = {}): Promise<boolean> {
+  const url = new URL(path, window.location.href);
+  if (!isSpa() || url.origin !== window.location.origin) {
+    window.location.assign(url.toString());
+    return false;
+  }
+  let data: RouteInfo & { fragments: RenderedFragment[] };
+  try {
+    const res = await fetch(serverUrl(`/api/gui/fragments?path=${encodeURIComponent(appPath(url.pathname))}`));
+    if (!res.ok) throw new Error(`status ${res.status}`);
+    data = await res.json();
+  } catch (err) {
+    window.location.assign(url.toString());
+    return false;
+  }
+  data.fragments.forEach((fragment) => {
+    const el = document.querySelector(fragment.selector);
+    if (el) el.innerHTML = fragment.html;
+  });
+  const target = url.pathname + url.search + url.hash;
+  if (replace) {
+    window.history.replaceState({ greentic: true }, "", target);
+  } else {
+    window.history.pushState({ greentic: true }, "", target);
+  }
+  const route: RouteInfo = { path: data.path, pattern: data.pattern, params: data.params };
+  let routeEl = document.getElementById("greentic-route");
+  if (!routeEl) {
+    routeEl = document.createElement("script");
+    routeEl.id = "greentic-route";
+    routeEl.setAttribute("type", "application/json");
+    document.head.appendChild(routeEl);
+  }
+  routeEl.textContent = JSON.stringify(route);
+  window.dispatchEvent(new CustomEvent("greentic:navigate", { detail: route }));
+  return true;
+}
+
+// Back/forward and `<a data-greentic-nav>` clicks navigate client-side in SPA layouts.
+function startSpaNavigation() {
+  if (spaNavigation || !isSpa()) return;
+  spaNavigation = true;
+  window.addEventListener("popstate", () => {
+    navigate(window.location.pathname + window.location.search, { replace: true });
+  });
+  document.addEventListener("click", (evt) => {
+    const link = (evt.target as Element | null)?.closest?.("a[data-greentic-nav]") as HTMLAnchorElement | null;
+    if (!link || evt.defaultPrevented || (evt as MouseEvent).button !== 0) return;
+    const mouse = evt as MouseEvent;
+    if (mouse.metaKey || mouse.ctrlKey || mouse.shiftKey || mouse.altKey) return;
+    evt.preventDefault();
+    navigate(link.href);
+  });
+}
+
+async function startSession({ userId, team, token }: StartSessionOptions) {
   if (!config) await init();
-  const res = await fetch("/api/gui/session", {
+  const headers: Record<string, string> = { "Content-Type": "application/json" };
+  if (token) headers.Authorization = `Bearer ${token}`;
+  const res = await fetch(serverUrl("/api/gui/session"), {
     method: "POST",
-    headers: { "Content-Type": "application/json" },
+    headers,
     body: JSON.stringify({ user_id: userId, team }),
   });
   if (!res.ok) {
@@ -122,6 +510,6 @@ async function startSession({ userId, team }: StartSessionOptions) {
   return res.json();
 }
 
-window.GreenticGUI = { version, init, attachWorker, sendWorkerMessage, sendEvent, startSession };
+window.GreenticGUI = { version, init, attachWorker, sendWorkerMessage, sendEvent, flush, startSession, getRoute, getRoutes, navigate };
 
 export {};
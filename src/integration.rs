@@ -1,4 +1,7 @@
+use crate::otlp::{OtlpExporter, TraceParent};
 use async_trait::async_trait;
+use greentic_session::ReplyScope;
+use greentic_session::error::ErrorCode;
 use greentic_session::store::SessionStore;
 use greentic_telemetry::init::TelemetryConfig;
 use greentic_telemetry::init_telemetry;
@@ -25,12 +28,24 @@ pub struct SessionInfo {
     pub session_id: String,
     pub tenant_ctx: TenantCtx,
     pub user_id: Option<String>,
+    /// OAuth scopes granted at login (from validated token claims).
+    #[serde(default)]
+    pub scopes: Vec<String>,
 }
 
 #[async_trait]
 pub trait SessionManager: Send + Sync {
     async fn validate(&self, token: Option<String>) -> Result<Option<SessionInfo>, SessionError>;
-    async fn issue(&self, ctx: TenantCtx, flow_id: FlowId) -> Result<SessionInfo, SessionError>;
+    async fn issue(
+        &self,
+        ctx: TenantCtx,
+        flow_id: FlowId,
+        scopes: Vec<String>,
+    ) -> Result<SessionInfo, SessionError>;
+    /// Delete a session from the store; unknown sessions are not an error.
+    async fn revoke(&self, session_id: &str) -> Result<(), SessionError>;
+    /// Delete every session of the user in `ctx` (tenant, team and user), returning the count.
+    async fn revoke_all_for_user(&self, ctx: &TenantCtx) -> Result<usize, SessionError>;
 }
 
 #[allow(dead_code)]
@@ -44,16 +59,31 @@ impl SessionManager for StubSessionManager {
             session_id: id.clone(),
             tenant_ctx: TenantCtx::new(EnvId::new("dev").unwrap(), TenantId::new("stub").unwrap()),
             user_id: Some("user-stub".to_string()),
+            scopes: vec![],
         }))
     }
 
-    async fn issue(&self, ctx: TenantCtx, _flow_id: FlowId) -> Result<SessionInfo, SessionError> {
+    async fn issue(
+        &self,
+        ctx: TenantCtx,
+        _flow_id: FlowId,
+        scopes: Vec<String>,
+    ) -> Result<SessionInfo, SessionError> {
         Ok(SessionInfo {
             session_id: SessionKey::new(uuid::Uuid::new_v4().to_string()).to_string(),
             user_id: ctx.user_id.as_ref().map(|u| u.to_string()),
             tenant_ctx: ctx,
+            scopes,
         })
     }
+
+    async fn revoke(&self, _session_id: &str) -> Result<(), SessionError> {
+        Ok(())
+    }
+
+    async fn revoke_all_for_user(&self, _ctx: &TenantCtx) -> Result<usize, SessionError> {
+        Ok(0)
+    }
 }
 
 #[allow(dead_code)]
@@ -87,34 +117,108 @@ impl SessionManager for RealSessionManager {
                 .map(|u| u.to_string())
                 .or_else(|| session.tenant_ctx.user.as_ref().map(|u| u.to_string()));
             tracing::debug!(session_id = %key, user = ?user, "validated session");
+            let context: SessionContext =
+                serde_json::from_str(&session.context_json).unwrap_or_default();
             SessionInfo {
                 session_id: key.to_string(),
                 tenant_ctx: session.tenant_ctx.clone(),
                 user_id: user,
+                scopes: context.scopes,
             }
         }))
     }
 
-    async fn issue(&self, ctx: TenantCtx, flow_id: FlowId) -> Result<SessionInfo, SessionError> {
+    async fn issue(
+        &self,
+        ctx: TenantCtx,
+        flow_id: FlowId,
+        scopes: Vec<String>,
+    ) -> Result<SessionInfo, SessionError> {
         let cursor = SessionCursor::new("gui-root");
+        let context = SessionContext {
+            scopes: scopes.clone(),
+        };
         let data = SessionData {
             tenant_ctx: ctx.clone(),
             flow_id,
             pack_id: None,
             cursor,
-            context_json: "{}".to_string(),
+            context_json: serde_json::to_string(&context)
+                .map_err(|e| SessionError::Provider(e.to_string()))?,
         };
         let key = self
             .store
-            .create_session(&ctx, data)
+            .create_session(&ctx, data.clone())
             .map_err(|e| SessionError::Provider(e.to_string()))?;
+        if let Some(user) = ctx.user_id.as_ref() {
+            // Registering the session as a wait gives the store a per-user index to revoke from.
+            self.store
+                .register_wait(&ctx, user, &session_scope(&key), &key, data, None)
+                .map_err(|e| SessionError::Provider(e.to_string()))?;
+        }
         tracing::info!(session_id = %key, user = ?ctx.user_id, tenant = %ctx.tenant_id, "issued session");
         Ok(SessionInfo {
             session_id: key.to_string(),
             tenant_ctx: ctx.clone(),
             user_id: ctx.user_id.as_ref().map(|u| u.to_string()),
+            scopes,
         })
     }
+
+    async fn revoke(&self, session_id: &str) -> Result<(), SessionError> {
+        let key = SessionKey::from(session_id);
+        match self.store.remove_session(&key) {
+            Ok(()) => {
+                tracing::info!(session_id = %key, "revoked session");
+                Ok(())
+            }
+            Err(err) if err.code == ErrorCode::NotFound => Ok(()),
+            Err(err) => Err(SessionError::Provider(err.to_string())),
+        }
+    }
+
+    async fn revoke_all_for_user(&self, ctx: &TenantCtx) -> Result<usize, SessionError> {
+        let Some(user) = ctx.user_id.as_ref().or(ctx.user.as_ref()) else {
+            return Ok(0);
+        };
+        let waits = self
+            .store
+            .list_waits_for_user(ctx, user)
+            .map_err(|e| SessionError::Provider(e.to_string()))?;
+        // Other components park flow waits for the same user; only GUI sessions are ours to revoke.
+        let mut keys = Vec::new();
+        for key in waits {
+            let scope = session_scope(&key);
+            let registered = self
+                .store
+                .find_wait_by_scope(ctx, user, &scope)
+                .map_err(|e| SessionError::Provider(e.to_string()))?;
+            if registered.as_ref() == Some(&key) {
+                keys.push(key);
+            }
+        }
+        for key in &keys {
+            self.revoke(key.as_str()).await?;
+        }
+        tracing::info!(user = %user, tenant = %ctx.tenant_id, count = keys.len(), "revoked user sessions");
+        Ok(keys.len())
+    }
+}
+
+fn session_scope(key: &SessionKey) -> ReplyScope {
+    ReplyScope {
+        conversation: format!("gui-session:{key}"),
+        thread: None,
+        reply_to: None,
+        correlation: None,
+    }
+}
+
+/// GUI-specific state persisted in the session's `context_json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SessionContext {
+    #[serde(default)]
+    scopes: Vec<String>,
 }
 
 #[allow(dead_code)]
@@ -123,43 +227,32 @@ pub trait TelemetrySink: Send + Sync {
     async fn record_event(&self, event: TelemetryEvent);
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TelemetryEvent {
     pub event_type: String,
     pub path: String,
     pub timestamp_ms: i64,
     pub metadata: serde_json::Value,
+    /// Tenant, session and provider the event is attributed to.
+    #[serde(skip)]
+    pub ctx: TelemetryCtx,
+    /// Route pattern matching `path` and the pack serving it.
+    #[serde(default)]
+    pub route: Option<String>,
+    #[serde(default)]
+    pub pack: Option<String>,
+    /// The `gui_request` span that rendered the page the event came from.
+    #[serde(skip)]
+    pub trace: Option<TraceParent>,
 }
 
+/// Discards events; for tests and embedders without telemetry.
 #[allow(dead_code)]
 pub struct NullTelemetrySink;
 
 #[async_trait]
 impl TelemetrySink for NullTelemetrySink {
-    async fn record_event(&self, event: TelemetryEvent) {
-        tracing::info!(?event, "telemetry event (stub)");
-    }
-}
-
-/// Telemetry sink that tags events with TelemetryCtx and emits via tracing.
-#[derive(Clone, Default)]
-#[allow(dead_code)]
-pub struct TracingTelemetrySink;
-
-#[async_trait]
-impl TelemetrySink for TracingTelemetrySink {
-    async fn record_event(&self, event: TelemetryEvent) {
-        greentic_telemetry::tasklocal::with_task_local(async {
-            tracing::info!(
-                event_type = %event.event_type,
-                path = %event.path,
-                timestamp_ms = event.timestamp_ms,
-                metadata = %event.metadata,
-                "gui.telemetry.event"
-            );
-        })
-        .await;
-    }
+    async fn record_event(&self, _event: TelemetryEvent) {}
 }
 
 /// Build a telemetry context from tenant + optional session/provider info.
@@ -184,8 +277,11 @@ pub fn set_request_telemetry_ctx(tenant: &str, session: Option<&str>, provider:
     set_current_telemetry_ctx(ctx);
 }
 
-#[allow(dead_code)]
-pub struct GreenticTelemetrySink;
+/// Logs events through tracing and, with an OTLP endpoint, exports them as OTLP logs and spans.
+#[derive(Clone, Default)]
+pub struct GreenticTelemetrySink {
+    exporter: Option<OtlpExporter>,
+}
 
 impl GreenticTelemetrySink {
     pub fn init() {
@@ -193,6 +289,13 @@ impl GreenticTelemetrySink {
             service_name: "greentic-gui".into(),
         });
     }
+
+    /// Sink exporting to the OTLP/HTTP collector at `otlp_endpoint`, if any.
+    pub fn new(otlp_endpoint: Option<&str>) -> Self {
+        Self {
+            exporter: otlp_endpoint.map(|endpoint| OtlpExporter::spawn(endpoint, "greentic-gui")),
+        }
+    }
 }
 
 #[async_trait]
@@ -203,9 +306,17 @@ impl TelemetrySink for GreenticTelemetrySink {
             event_type = %event.event_type,
             path = %event.path,
             timestamp = event.timestamp_ms,
+            tenant = %event.ctx.tenant,
+            session = event.ctx.session.as_deref(),
+            route = event.route.as_deref(),
+            pack = event.pack.as_deref(),
+            trace_id = event.trace.as_ref().map(|t| t.trace_id.as_str()),
             metadata = %event.metadata,
             "gui telemetry event"
         );
+        if let Some(exporter) = &self.exporter {
+            exporter.export(event);
+        }
     }
 }
 
@@ -259,3 +370,88 @@ pub mod real {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use greentic_session::inmemory::InMemorySessionStore;
+
+    fn flow() -> FlowId {
+        FlowId::new("gui").unwrap()
+    }
+
+    #[tokio::test]
+    async fn revokes_single_and_all_user_sessions() {
+        let manager = RealSessionManager::new(Arc::new(InMemorySessionStore::new()));
+        let alice = build_tenant_ctx("dev", "tenant", Some("team"), Some("alice"));
+        let bob = build_tenant_ctx("dev", "tenant", Some("team"), Some("bob"));
+        let first = manager.issue(alice.clone(), flow(), vec![]).await.unwrap();
+        let second = manager.issue(alice.clone(), flow(), vec![]).await.unwrap();
+        let third = manager.issue(alice.clone(), flow(), vec![]).await.unwrap();
+        let other = manager.issue(bob, flow(), vec![]).await.unwrap();
+
+        manager.revoke(&first.session_id).await.unwrap();
+        assert!(
+            manager
+                .validate(Some(first.session_id.clone()))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        // Revoking twice (or an unknown id) is not an error.
+        manager.revoke(&first.session_id).await.unwrap();
+
+        assert_eq!(manager.revoke_all_for_user(&alice).await.unwrap(), 2);
+        for id in [second.session_id, third.session_id] {
+            assert!(manager.validate(Some(id)).await.unwrap().is_none());
+        }
+        assert!(
+            manager
+                .validate(Some(other.session_id))
+                .await
+                .unwrap()
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn revoking_all_sessions_keeps_other_flow_waits() {
+        let store = Arc::new(InMemorySessionStore::new());
+        let manager = RealSessionManager::new(store.clone());
+        let alice = build_tenant_ctx("dev", "tenant", Some("team"), Some("alice"));
+        let session = manager.issue(alice.clone(), flow(), vec![]).await.unwrap();
+
+        let data = SessionData {
+            tenant_ctx: alice.clone(),
+            flow_id: FlowId::new("approvals").unwrap(),
+            pack_id: None,
+            cursor: SessionCursor::new("await-approval"),
+            context_json: "{}".into(),
+        };
+        let flow_key = store.create_session(&alice, data.clone()).unwrap();
+        let scope = ReplyScope {
+            conversation: "approvals:42".into(),
+            thread: None,
+            reply_to: None,
+            correlation: None,
+        };
+        let user = alice.user_id.clone().unwrap();
+        store
+            .register_wait(&alice, &user, &scope, &flow_key, data, None)
+            .unwrap();
+
+        assert_eq!(manager.revoke_all_for_user(&alice).await.unwrap(), 1);
+        assert!(
+            manager
+                .validate(Some(session.session_id))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(store.get_session(&flow_key).unwrap().is_some());
+        assert_eq!(
+            store.find_wait_by_scope(&alice, &user, &scope).unwrap(),
+            Some(flow_key)
+        );
+    }
+}
@@ -1,16 +1,28 @@
+mod admin;
 mod api;
+mod assets;
 mod auth;
 mod config;
+mod domains;
+mod fragment_host;
 mod fragments;
 mod integration;
+mod oauth_flow;
+mod otlp;
 mod packs;
+mod route_pattern;
 mod routing;
 mod sdk;
 mod server;
+mod skin;
+mod telemetry;
 mod tenant;
+mod vfs;
+mod watch;
 mod worker;
 
 use crate::config::LoadedConfig;
+use crate::fragment_host::{EnvSecretsProbe, FragmentHost};
 use crate::fragments::{CompositeFragmentRenderer, NoopFragmentInvoker, WasmtimeFragmentInvoker};
 use crate::integration::{GreenticTelemetrySink, RealSessionManager};
 use crate::packs::FsPackProvider;
@@ -24,7 +36,10 @@ use greentic_distributor_client::{
 use greentic_session::{SessionBackendConfig, create_session_store};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tracing_subscriber::EnvFilter;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Greentic GUI server")]
@@ -49,6 +64,43 @@ pub struct CliArgs {
     #[arg(long)]
     pub project_root: Option<std::path::PathBuf>,
 
+    /// Watch the filesystem pack root and hot-reload tenants when packs change.
+    #[arg(long, default_value_t = false)]
+    pub watch_packs: bool,
+
+    /// Fail tenant loads when pack routes duplicate or overlap each other.
+    #[arg(long, default_value_t = false)]
+    pub strict_routes: bool,
+
+    /// Telemetry events accepted per session and minute (0 disables the limit).
+    #[arg(long, default_value_t = 600)]
+    pub telemetry_rate_limit: u32,
+
+    /// OTLP/HTTP collector base URL (e.g. http://localhost:4318) to export GUI telemetry events to
+    /// as logs and page-view spans.
+    #[arg(long)]
+    pub otlp_events_endpoint: Option<String>,
+
+    /// Wall-clock limit for one Wasmtime fragment render, in milliseconds.
+    #[arg(long, default_value_t = 2000)]
+    pub fragment_timeout_ms: u64,
+
+    /// Fuel budget for one Wasmtime fragment render (0 disables fuel metering).
+    #[arg(long, default_value_t = 1_000_000_000)]
+    pub fragment_fuel: u64,
+
+    /// Linear memory limit for one Wasmtime fragment instance, in MiB.
+    #[arg(long, default_value_t = 64)]
+    pub fragment_memory_mb: usize,
+
+    /// Fragments of one page rendered at the same time.
+    #[arg(long, default_value_t = 4)]
+    pub fragment_concurrency: usize,
+
+    /// Deadline for all fragments of one page, in milliseconds; later ones get a placeholder.
+    #[arg(long, default_value_t = 5000)]
+    pub fragment_page_timeout_ms: u64,
+
     /// Print resolved config explain and exit.
     #[arg(long, default_value_t = false)]
     pub explain_config: bool,
@@ -56,8 +108,8 @@ pub struct CliArgs {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    init_tracing();
     GreenticTelemetrySink::init();
+    init_tracing();
     let cli = CliArgs::parse();
     let cli_layer = load_cli_config_layer(cli.config.clone())?;
     let LoadedConfig {
@@ -107,9 +159,20 @@ async fn main() -> anyhow::Result<()> {
     } else {
         Arc::new(FsPackProvider::new(config.pack_root.clone()))
     };
+    let worker_backend = worker_backend_from_env();
+    let worker_host = Arc::new(WorkerHost::new(worker_backend));
+    let fragment_host = FragmentHost {
+        worker_host: worker_host.clone(),
+        secrets: Arc::new(EnvSecretsProbe),
+        env_id: config.env_id.clone(),
+        team: config.default_team.clone(),
+    };
     let wit_invoker: Arc<dyn crate::fragments::FragmentInvoker> =
-        match WasmtimeFragmentInvoker::new() {
-            Ok(inv) => Arc::new(inv),
+        match WasmtimeFragmentInvoker::new(config.fragment_limits.clone()) {
+            Ok(inv) => Arc::new(
+                inv.with_host(fragment_host)
+                    .with_cache_dir(config.resolved.paths.cache_dir.join("fragments")),
+            ),
             Err(err) => {
                 tracing::warn!(
                     ?err,
@@ -141,10 +204,9 @@ async fn main() -> anyhow::Result<()> {
         };
     let session_manager: Arc<dyn crate::integration::SessionManager> =
         Arc::new(RealSessionManager::new(session_store));
-    let telemetry: Arc<dyn crate::integration::TelemetrySink> = Arc::new(GreenticTelemetrySink);
-    let worker_backend = worker_backend_from_env();
-    let worker_host = Arc::new(WorkerHost::new(worker_backend));
-
+    let telemetry: Arc<dyn crate::integration::TelemetrySink> = Arc::new(
+        GreenticTelemetrySink::new(config.otlp_events_endpoint.as_deref()),
+    );
     let state = AppState::new(
         config.clone(),
         pack_provider,
@@ -154,6 +216,27 @@ async fn main() -> anyhow::Result<()> {
         worker_host,
     );
 
+    let fs_packs = config
+        .distributor
+        .as_ref()
+        .and_then(|d| d.packs_json.as_ref())
+        .is_none();
+    let _pack_watcher = if config.watch_packs && fs_packs {
+        match crate::watch::watch_packs(
+            state.clone(),
+            config.pack_root.clone(),
+            std::time::Duration::from_millis(250),
+        ) {
+            Ok(watcher) => Some(watcher),
+            Err(err) => {
+                tracing::warn!(?err, "failed to start pack watcher; hot reload disabled");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     let addr: SocketAddr = config.bind_addr;
     if let Some(url) = &config.public_base_url {
         tracing::info!(%url, "public base url configured");
@@ -163,9 +246,21 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Log through `fmt` and record spans through OpenTelemetry, so trace context from browsers
+/// reaches the spans greentic-telemetry exports. Without `OTEL_EXPORTER_OTLP_ENDPOINT` a local
+/// provider still assigns span ids, keeping browser, page and worker traces joined.
 fn init_tracing() {
-    let _ = tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
+    if std::env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() {
+        opentelemetry::global::set_tracer_provider(
+            opentelemetry_sdk::trace::SdkTracerProvider::builder().build(),
+        );
+    }
+    let otel = tracing_opentelemetry::layer()
+        .with_tracer(opentelemetry::global::tracer("greentic-gui"))
+        .with_filter(LevelFilter::INFO);
+    let _ = tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(EnvFilter::from_default_env()))
+        .with(otel)
         .try_init();
 }
 
@@ -0,0 +1,261 @@
+//! Per-login OAuth flow state.
+//!
+//! `/auth/{provider}/start` mints a CSRF `state`, an id_token `nonce` and a PKCE verifier and
+//! hands them to the browser in a signed, short-lived cookie. The callback checks the cookie
+//! signature, the echoed `state` and the id_token `nonce`, and marks the flow consumed so a
+//! captured callback URL cannot be replayed.
+
+use crate::auth::constant_time_eq;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const FLOW_COOKIE: &str = "greentic_oauth_flow";
+pub const FLOW_TTL: Duration = Duration::from_secs(600);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// HMAC key used to sign flow cookies.
+#[derive(Clone)]
+pub struct FlowKey(Arc<[u8]>);
+
+impl FlowKey {
+    pub fn new(bytes: impl AsRef<[u8]>) -> Self {
+        Self(Arc::from(bytes.as_ref()))
+    }
+
+    /// Per-process key; flows then cannot complete on another instance or after a restart.
+    pub fn random() -> Self {
+        Self::new(random_bytes::<32>())
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.0).expect("hmac accepts any key length")
+    }
+}
+
+impl fmt::Debug for FlowKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FlowKey(..)")
+    }
+}
+
+/// Login flow awaiting its callback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingFlow {
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+    pub provider: String,
+    pub tenant: String,
+    /// Validated in-app path to restore once the session is issued.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub return_to: Option<String>,
+    /// Unix seconds after which the callback is rejected.
+    pub expires_at: i64,
+}
+
+impl PendingFlow {
+    pub fn new(provider: &str, tenant: &str, now: i64) -> Self {
+        Self {
+            state: random_token(),
+            nonce: random_token(),
+            code_verifier: random_token(),
+            provider: provider.to_string(),
+            tenant: tenant.to_string(),
+            return_to: None,
+            expires_at: now + FLOW_TTL.as_secs() as i64,
+        }
+    }
+
+    /// RFC 7636 S256 challenge for `code_verifier`.
+    pub fn code_challenge(&self) -> String {
+        pkce_challenge(&self.code_verifier)
+    }
+
+    /// Parameters forwarded to the broker in `StartRequest::extra_params`.
+    pub fn start_params(&self) -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("state".to_string(), self.state.clone()),
+            ("nonce".to_string(), self.nonce.clone()),
+            ("code_challenge".to_string(), self.code_challenge()),
+            ("code_challenge_method".to_string(), "S256".to_string()),
+        ])
+    }
+
+    /// Signed cookie value: `base64url(json).base64url(hmac)`.
+    pub fn encode(&self, key: &FlowKey) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("flow serializes"));
+        let mut mac = key.mac();
+        mac.update(payload.as_bytes());
+        let sig = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+        format!("{payload}.{sig}")
+    }
+
+    pub fn decode(value: &str, key: &FlowKey, now: i64) -> Result<Self, FlowError> {
+        let (payload, sig) = value.split_once('.').ok_or(FlowError::Malformed)?;
+        let sig = URL_SAFE_NO_PAD
+            .decode(sig)
+            .map_err(|_| FlowError::Malformed)?;
+        let mut mac = key.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&sig)
+            .map_err(|_| FlowError::BadSignature)?;
+        let json = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| FlowError::Malformed)?;
+        let flow: PendingFlow = serde_json::from_slice(&json).map_err(|_| FlowError::Malformed)?;
+        if flow.expires_at <= now {
+            return Err(FlowError::Expired);
+        }
+        Ok(flow)
+    }
+
+    /// Check the callback's echoed `state` and target against this flow.
+    pub fn verify_callback(
+        &self,
+        state: &str,
+        provider: &str,
+        tenant: &str,
+    ) -> Result<(), FlowError> {
+        if !constant_time_eq(self.state.as_bytes(), state.as_bytes()) {
+            return Err(FlowError::StateMismatch);
+        }
+        if self.provider != provider || self.tenant != tenant {
+            return Err(FlowError::TargetMismatch);
+        }
+        Ok(())
+    }
+
+    /// Check the `nonce` claim of an id_token whose signature was already validated.
+    pub fn verify_nonce(&self, id_token: &str) -> Result<(), FlowError> {
+        match id_token_nonce(id_token) {
+            Some(nonce) if constant_time_eq(nonce.as_bytes(), self.nonce.as_bytes()) => Ok(()),
+            _ => Err(FlowError::NonceMismatch),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum FlowError {
+    #[error("no pending login flow")]
+    Missing,
+    #[error("malformed login flow cookie")]
+    Malformed,
+    #[error("login flow cookie signature is invalid")]
+    BadSignature,
+    #[error("login flow expired")]
+    Expired,
+    #[error("state does not match the login flow")]
+    StateMismatch,
+    #[error("callback does not match the login flow provider or tenant")]
+    TargetMismatch,
+    #[error("id_token nonce does not match the login flow")]
+    NonceMismatch,
+    #[error("login flow was already completed")]
+    Replayed,
+}
+
+impl FlowError {
+    pub fn reason(self) -> &'static str {
+        match self {
+            FlowError::Missing => "missing_flow",
+            FlowError::Malformed => "malformed_flow",
+            FlowError::BadSignature => "invalid_flow_signature",
+            FlowError::Expired => "flow_expired",
+            FlowError::StateMismatch => "state_mismatch",
+            FlowError::TargetMismatch => "flow_target_mismatch",
+            FlowError::NonceMismatch => "nonce_mismatch",
+            FlowError::Replayed => "flow_replayed",
+        }
+    }
+}
+
+/// States of completed flows, kept until their cookie would have expired.
+#[derive(Debug, Default)]
+pub struct ConsumedFlows(Mutex<HashMap<String, i64>>);
+
+impl ConsumedFlows {
+    /// Mark `flow` consumed; fails if it already was.
+    pub fn consume(&self, flow: &PendingFlow, now: i64) -> Result<(), FlowError> {
+        let mut seen = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        seen.retain(|_, expires_at| *expires_at > now);
+        if seen.insert(flow.state.clone(), flow.expires_at).is_some() {
+            return Err(FlowError::Replayed);
+        }
+        Ok(())
+    }
+}
+
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Read the `nonce` claim from a JWT payload without verifying it.
+fn id_token_nonce(token: &str) -> Option<String> {
+    let payload = token.split('.').nth(1)?;
+    let json = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&json).ok()?;
+    claims.get("nonce")?.as_str().map(str::to_string)
+}
+
+fn random_token() -> String {
+    URL_SAFE_NO_PAD.encode(random_bytes::<32>())
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut buf = [0u8; N];
+    getrandom::fill(&mut buf).expect("os randomness available");
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_cookie_round_trips_and_rejects_tampering() {
+        let key = FlowKey::new(b"test-key");
+        let flow = PendingFlow::new("google", "tenant", 1_000);
+        let cookie = flow.encode(&key);
+        assert_eq!(PendingFlow::decode(&cookie, &key, 1_001), Ok(flow.clone()));
+
+        let other = FlowKey::new(b"other-key");
+        assert_eq!(
+            PendingFlow::decode(&cookie, &other, 1_001),
+            Err(FlowError::BadSignature)
+        );
+        let (payload, sig) = cookie.split_once('.').unwrap();
+        let forged = format!("{}A.{sig}", payload);
+        assert_eq!(
+            PendingFlow::decode(&forged, &key, 1_001),
+            Err(FlowError::BadSignature)
+        );
+        assert_eq!(
+            PendingFlow::decode(&cookie, &key, flow.expires_at),
+            Err(FlowError::Expired)
+        );
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc_7636_example() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn flows_can_only_be_consumed_once() {
+        let consumed = ConsumedFlows::default();
+        let flow = PendingFlow::new("google", "tenant", 1_000);
+        assert_eq!(consumed.consume(&flow, 1_001), Ok(()));
+        assert_eq!(consumed.consume(&flow, 1_002), Err(FlowError::Replayed));
+    }
+}
@@ -0,0 +1,605 @@
+//! OTLP/HTTP JSON export of GUI telemetry events: every event becomes a log record and page views
+//! also become spans, parented to the `gui_request` span that rendered the page. Also the W3C
+//! trace context carried between the browser, the server and the worker gateway.
+
+use crate::integration::TelemetryEvent;
+use http::HeaderMap;
+use opentelemetry::trace::{SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState};
+use serde_json::{Value, json};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{Span, debug, warn};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+const QUEUE_CAPACITY: usize = 4096;
+const MAX_EXPORT_BATCH: usize = 512;
+const SCOPE_NAME: &str = "greentic-gui.telemetry";
+const SEVERITY_INFO: u8 = 9;
+const SPAN_KIND_INTERNAL: u8 = 1;
+const FLAG_SAMPLED: u8 = 0x01;
+
+/// W3C trace context of a server span, as carried by a `traceparent` header or meta tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    pub trace_id: String,
+    pub span_id: String,
+    /// Trace flags (bit 0 is `sampled`), forwarded as received.
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// A new span starting a new trace.
+    pub fn new_root() -> Self {
+        Self {
+            trace_id: random_id::<16>(),
+            span_id: random_id::<8>(),
+            flags: FLAG_SAMPLED,
+        }
+    }
+
+    /// Parse `00-<trace id>-<span id>-<flags>`; all-zero ids are invalid.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let (version, trace_id, span_id, flags) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        let valid = |id: &str, len: usize| {
+            id.len() == len
+                && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+                && id.bytes().any(|b| b != b'0')
+        };
+        if version != "00" || parts.next().is_some() || flags.len() != 2 {
+            return None;
+        }
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        (valid(trace_id, 32) && valid(span_id, 16)).then(|| Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            flags,
+        })
+    }
+
+    /// A new span in the same trace, parented to this one.
+    pub fn child(&self) -> Self {
+        Self {
+            trace_id: self.trace_id.clone(),
+            span_id: random_id::<8>(),
+            flags: self.flags,
+        }
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+/// A span's `traceparent` together with the vendor `tracestate` that travels with it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceContext {
+    pub parent: TraceParent,
+    pub state: Option<String>,
+}
+
+impl TraceContext {
+    pub fn new_root() -> Self {
+        Self {
+            parent: TraceParent::new_root(),
+            state: None,
+        }
+    }
+
+    /// Trace context sent by the caller, if its `traceparent` header is valid.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        Some(Self {
+            parent: TraceParent::parse(header("traceparent")?)?,
+            state: header("tracestate")
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        })
+    }
+
+    /// Trace context of a new `span`, continuing `incoming` when given. With an OpenTelemetry
+    /// layer these are the ids the exported span gets; without one, fresh ids are generated.
+    pub fn for_span(span: &Span, incoming: Option<&TraceContext>) -> Self {
+        if let Some(incoming) = incoming {
+            incoming.attach(span);
+        }
+        Self::of_span(span).unwrap_or_else(|| match incoming {
+            Some(incoming) => incoming.child(),
+            None => Self::new_root(),
+        })
+    }
+
+    /// Parent `span`'s OpenTelemetry span to this context; must happen before `span` is entered.
+    fn attach(&self, span: &Span) {
+        let (Ok(trace_id), Ok(span_id)) = (
+            TraceId::from_hex(&self.parent.trace_id),
+            SpanId::from_hex(&self.parent.span_id),
+        ) else {
+            return;
+        };
+        let state = self
+            .state
+            .as_deref()
+            .and_then(|state| TraceState::from_str(state).ok())
+            .unwrap_or_default();
+        let remote = SpanContext::new(
+            trace_id,
+            span_id,
+            TraceFlags::new(self.parent.flags),
+            true,
+            state,
+        );
+        let context = opentelemetry::Context::new().with_remote_span_context(remote);
+        if let Err(err) = span.set_parent(context) {
+            debug!(?err, "trace context not attached to span");
+        }
+    }
+
+    /// Trace context of `span`'s OpenTelemetry span, if an OpenTelemetry layer records it.
+    fn of_span(span: &Span) -> Option<Self> {
+        let context = span.context();
+        let span_context = context.span().span_context().clone();
+        if !span_context.is_valid() {
+            return None;
+        }
+        let state = span_context.trace_state().header();
+        Some(Self {
+            parent: TraceParent {
+                trace_id: span_context.trace_id().to_string(),
+                span_id: span_context.span_id().to_string(),
+                flags: span_context.trace_flags().to_u8(),
+            },
+            state: (!state.is_empty()).then_some(state),
+        })
+    }
+
+    /// Context for a new span continuing this trace.
+    pub fn child(&self) -> Self {
+        Self {
+            parent: self.parent.child(),
+            state: self.state.clone(),
+        }
+    }
+
+    /// `traceparent` and `tracestate` headers for outgoing requests made within this span.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![("traceparent", self.parent.to_string())];
+        if let Some(state) = &self.state {
+            headers.push(("tracestate", state.clone()));
+        }
+        headers
+    }
+
+    /// `<meta name="traceparent">` (and `tracestate`) for served pages, read by the SDK.
+    pub fn meta_tags(&self) -> String {
+        let mut tags = format!("<meta name=\"traceparent\" content=\"{}\">", self.parent);
+        if let Some(state) = &self.state {
+            let state = state.replace('&', "&amp;").replace('"', "&quot;");
+            tags.push_str(&format!("<meta name=\"tracestate\" content=\"{state}\">"));
+        }
+        tags
+    }
+}
+
+/// Background exporter posting batches of events to an OTLP/HTTP collector.
+#[derive(Clone)]
+pub struct OtlpExporter {
+    tx: mpsc::Sender<TelemetryEvent>,
+}
+
+impl OtlpExporter {
+    /// Start exporting to `endpoint`, the collector's base URL (`/v1/logs` and `/v1/traces` are
+    /// appended). Must be called inside a Tokio runtime.
+    pub fn spawn(endpoint: &str, service_name: &str) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let base = endpoint.trim_end_matches('/');
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap_or_default();
+        tokio::spawn(run(
+            rx,
+            client,
+            format!("{base}/v1/logs"),
+            format!("{base}/v1/traces"),
+            service_name.to_string(),
+        ));
+        Self { tx }
+    }
+
+    /// Queue `event` for export, dropping it when the collector falls too far behind.
+    pub fn export(&self, event: TelemetryEvent) {
+        if self.tx.try_send(event).is_err() {
+            warn!("otlp export queue full; dropping telemetry event");
+        }
+    }
+}
+
+async fn run(
+    mut rx: mpsc::Receiver<TelemetryEvent>,
+    client: reqwest::Client,
+    logs_url: String,
+    traces_url: String,
+    service_name: String,
+) {
+    while let Some(first) = rx.recv().await {
+        // Events queued while the previous export was in flight go out together.
+        let mut batch = vec![first];
+        while batch.len() < MAX_EXPORT_BATCH {
+            match rx.try_recv() {
+                Ok(event) => batch.push(event),
+                Err(_) => break,
+            }
+        }
+        post(&client, &logs_url, &logs_request(&service_name, &batch)).await;
+        if let Some(spans) = spans_request(&service_name, &batch) {
+            post(&client, &traces_url, &spans).await;
+        }
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, body: &Value) {
+    match client.post(url).json(body).send().await {
+        Ok(resp) if resp.status().is_success() => {}
+        Ok(resp) => warn!(status = %resp.status(), %url, "otlp export rejected"),
+        Err(err) => warn!(?err, %url, "otlp export failed"),
+    }
+}
+
+/// `ExportLogsServiceRequest` with one log record per event.
+pub fn logs_request(service_name: &str, events: &[TelemetryEvent]) -> Value {
+    let observed = unix_nanos(chrono::Utc::now().timestamp_millis());
+    let records: Vec<Value> = events
+        .iter()
+        .map(|event| {
+            let mut record = json!({
+                "timeUnixNano": unix_nanos(event.timestamp_ms),
+                "observedTimeUnixNano": observed,
+                "severityNumber": SEVERITY_INFO,
+                "severityText": "INFO",
+                "body": { "stringValue": event.event_type },
+                "attributes": attributes(event),
+            });
+            if let Some(trace) = &event.trace {
+                record["traceId"] = json!(trace.trace_id);
+                record["spanId"] = json!(trace.span_id);
+            }
+            record
+        })
+        .collect();
+    json!({
+        "resourceLogs": [{
+            "resource": resource(service_name),
+            "scopeLogs": [{ "scope": { "name": SCOPE_NAME }, "logRecords": records }],
+        }]
+    })
+}
+
+/// `ExportTraceServiceRequest` with a span per `page_view`, or `None` without page views.
+/// Page views in traces sampled out upstream are skipped, like the spans they would hang off.
+pub fn spans_request(service_name: &str, events: &[TelemetryEvent]) -> Option<Value> {
+    let spans: Vec<Value> = events
+        .iter()
+        .filter(|event| event.event_type == "page_view")
+        .filter(|event| {
+            event
+                .trace
+                .as_ref()
+                .is_none_or(|trace| trace.flags & FLAG_SAMPLED != 0)
+        })
+        .map(|event| {
+            let time = unix_nanos(event.timestamp_ms);
+            let mut span = json!({
+                "spanId": random_id::<8>(),
+                "name": "gui.page_view",
+                "kind": SPAN_KIND_INTERNAL,
+                "startTimeUnixNano": time,
+                "endTimeUnixNano": time,
+                "attributes": attributes(event),
+            });
+            match &event.trace {
+                Some(trace) => {
+                    span["traceId"] = json!(trace.trace_id);
+                    span["parentSpanId"] = json!(trace.span_id);
+                }
+                None => span["traceId"] = json!(random_id::<16>()),
+            }
+            span
+        })
+        .collect();
+    (!spans.is_empty()).then(|| {
+        json!({
+            "resourceSpans": [{
+                "resource": resource(service_name),
+                "scopeSpans": [{ "scope": { "name": SCOPE_NAME }, "spans": spans }],
+            }]
+        })
+    })
+}
+
+fn resource(service_name: &str) -> Value {
+    json!({ "attributes": [string_attr("service.name", service_name)] })
+}
+
+fn attributes(event: &TelemetryEvent) -> Vec<Value> {
+    let mut attrs = vec![
+        string_attr("gui.event.type", &event.event_type),
+        string_attr("url.path", &event.path),
+    ];
+    attrs.extend(
+        event.ctx.kv().into_iter().filter_map(|(key, value)| {
+            value.filter(|v| !v.is_empty()).map(|v| string_attr(key, v))
+        }),
+    );
+    if let Some(route) = &event.route {
+        attrs.push(string_attr("gui.route", route));
+    }
+    if let Some(pack) = &event.pack {
+        attrs.push(string_attr("gui.pack", pack));
+    }
+    if !event.metadata.is_null() {
+        attrs.push(string_attr("gui.metadata", &event.metadata.to_string()));
+    }
+    attrs
+}
+
+fn string_attr(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// OTLP/JSON encodes 64-bit integers as strings.
+fn unix_nanos(ms: i64) -> String {
+    (ms.max(0) as u128 * 1_000_000).to_string()
+}
+
+fn random_id<const N: usize>() -> String {
+    let mut buf = [0u8; N];
+    getrandom::fill(&mut buf).expect("os randomness available");
+    hex::encode(buf)
+}
+
+/// Record the OpenTelemetry spans of tracing spans created on this thread until the guard drops.
+#[cfg(test)]
+pub fn record_spans() -> (
+    opentelemetry_sdk::trace::InMemorySpanExporter,
+    tracing::subscriber::DefaultGuard,
+) {
+    use opentelemetry::trace::TracerProvider;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let exporter = opentelemetry_sdk::trace::InMemorySpanExporter::default();
+    let provider = opentelemetry_sdk::trace::SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+    (exporter, tracing::subscriber::set_default(subscriber))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integration::make_telemetry_ctx;
+    use axum::extract::{Path, State};
+    use axum::routing::post;
+    use std::sync::{Arc, Mutex};
+
+    type Received = Arc<Mutex<Vec<(String, Value)>>>;
+
+    #[test]
+    fn traceparent_round_trips_and_rejects_invalid_values() {
+        let trace = TraceParent::new_root();
+        assert_eq!(TraceParent::parse(&trace.to_string()), Some(trace));
+        let unsampled = "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-00";
+        let parsed = TraceParent::parse(unsampled).unwrap();
+        assert_eq!(parsed.flags, 0);
+        assert_eq!(parsed.to_string(), unsampled);
+        assert!(parsed.child().to_string().ends_with("-00"));
+        for invalid in [
+            "",
+            "00-00000000000000000000000000000000-0102030405060708-01",
+            "01-0102030405060708090a0b0c0d0e0f10-0102030405060708-01",
+            "00-0102030405060708090A0B0C0D0E0F10-0102030405060708-01",
+            "00-0102030405060708090a0b0c0d0e0f10-01020304-01",
+            "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-zz",
+        ] {
+            assert_eq!(TraceParent::parse(invalid), None, "{invalid}");
+        }
+    }
+
+    #[test]
+    fn trace_context_continues_incoming_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-01"
+                .parse()
+                .unwrap(),
+        );
+        headers.insert("tracestate", "vendor=a\"b".parse().unwrap());
+        let incoming = TraceContext::from_headers(&headers).unwrap();
+        let child = incoming.child();
+        assert_eq!(child.parent.trace_id, incoming.parent.trace_id);
+        assert_ne!(child.parent.span_id, incoming.parent.span_id);
+        assert_eq!(
+            child.headers()[1],
+            ("tracestate", "vendor=a\"b".to_string())
+        );
+        assert!(child.meta_tags().contains("content=\"vendor=a&quot;b\""));
+
+        headers.insert("traceparent", "garbage".parse().unwrap());
+        assert_eq!(TraceContext::from_headers(&headers), None);
+    }
+
+    #[test]
+    fn spans_continue_incoming_trace_context_in_opentelemetry() {
+        let (exporter, _guard) = record_spans();
+        let incoming = |flags: &str| TraceContext {
+            parent: TraceParent::parse(&format!(
+                "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-{flags}"
+            ))
+            .unwrap(),
+            state: Some("vendor=1".into()),
+        };
+
+        let sampled = incoming("01");
+        let span = tracing::info_span!("gui_request");
+        let trace = TraceContext::for_span(&span, Some(&sampled));
+        drop(span);
+        let spans = exporter.get_finished_spans().unwrap();
+        assert_eq!(spans.len(), 1);
+        let exported = &spans[0].span_context;
+        assert_eq!(exported.trace_id().to_string(), sampled.parent.trace_id);
+        assert_eq!(spans[0].parent_span_id.to_string(), sampled.parent.span_id);
+        assert_eq!(exported.span_id().to_string(), trace.parent.span_id);
+        assert_eq!(trace.parent.flags, 1);
+        assert_eq!(trace.state.as_deref(), Some("vendor=1"));
+
+        // An upstream "not sampled" decision is kept, so nothing is exported for it.
+        let unsampled = incoming("00");
+        let span = tracing::info_span!("gui_request");
+        let trace = TraceContext::for_span(&span, Some(&unsampled));
+        drop(span);
+        assert_eq!(trace.parent.trace_id, unsampled.parent.trace_id);
+        assert_eq!(trace.parent.flags, 0);
+        assert_eq!(exporter.get_finished_spans().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn exports_logs_and_page_view_spans_to_collector() {
+        let received = Received::default();
+        let collector = axum::Router::new()
+            .route(
+                "/v1/{signal}",
+                post(
+                    |State(received): State<Received>,
+                     Path(signal): Path<String>,
+                     axum::Json(body): axum::Json<Value>| async move {
+                        received.lock().unwrap().push((signal, body));
+                        "{}"
+                    },
+                ),
+            )
+            .with_state(received.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, collector).await });
+
+        // Events name the page's `gui_request` span, which the tracing layer exports.
+        let (exported, guard) = record_spans();
+        let page = tracing::info_span!("gui_request");
+        let trace = TraceContext::for_span(&page, None).parent;
+        drop(page);
+        let page = exported.get_finished_spans().unwrap().remove(0);
+        drop(guard);
+        let unsampled = TraceParent {
+            flags: 0,
+            ..TraceParent::new_root()
+        };
+        let event = |event_type: &str| TelemetryEvent {
+            event_type: event_type.into(),
+            path: "/invoices/7".into(),
+            timestamp_ms: 1_700_000_000_000,
+            metadata: json!({ "title": "Invoice" }),
+            ctx: make_telemetry_ctx("tenant", Some("sha256:abc"), Some("gui")),
+            route: Some("/invoices/:id".into()),
+            pack: Some("billing".into()),
+            trace: Some(trace.clone()),
+        };
+        let exporter = OtlpExporter::spawn(&format!("http://{addr}/"), "greentic-gui");
+        exporter.export(event("page_view"));
+        exporter.export(event("click"));
+        exporter.export(TelemetryEvent {
+            trace: Some(unsampled.clone()),
+            ..event("page_view")
+        });
+
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        let records_received = |received: &Received| {
+            received
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(signal, _)| signal == "logs")
+                .map(|(_, body)| {
+                    body["resourceLogs"][0]["scopeLogs"][0]["logRecords"]
+                        .as_array()
+                        .map_or(0, Vec::len)
+                })
+                .sum::<usize>()
+        };
+        while (records_received(&received) < 3 || received.lock().unwrap().len() < 2)
+            && tokio::time::Instant::now() < deadline
+        {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let received = received.lock().unwrap().clone();
+        let body = |signal: &str| {
+            received
+                .iter()
+                .filter(|(s, _)| s == signal)
+                .map(|(_, body)| body.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let logs = body("logs");
+        let records: Vec<&Value> = logs
+            .iter()
+            .flat_map(|b| {
+                b["resourceLogs"][0]["scopeLogs"][0]["logRecords"]
+                    .as_array()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2]["traceId"], unsampled.trace_id);
+        assert_eq!(records[0]["traceId"], trace.trace_id);
+        assert_eq!(records[0]["timeUnixNano"], "1700000000000000000");
+        let attrs = records[0]["attributes"].as_array().unwrap();
+        let attr = |key: &str| {
+            attrs
+                .iter()
+                .find(|a| a["key"] == key)
+                .map(|a| a["value"]["stringValue"].clone())
+        };
+        assert_eq!(attr("gt.tenant"), Some(json!("tenant")));
+        assert_eq!(attr("gt.session"), Some(json!("sha256:abc")));
+        assert_eq!(attr("gui.route"), Some(json!("/invoices/:id")));
+        assert_eq!(attr("gui.pack"), Some(json!("billing")));
+        assert_eq!(attr("gt.flow"), None);
+
+        let traces = body("traces");
+        let spans: Vec<&Value> = traces
+            .iter()
+            .flat_map(|b| {
+                b["resourceSpans"][0]["scopeSpans"][0]["spans"]
+                    .as_array()
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0]["name"], "gui.page_view");
+        assert_eq!(
+            spans[0]["traceId"],
+            page.span_context.trace_id().to_string()
+        );
+        assert_eq!(
+            spans[0]["parentSpanId"],
+            page.span_context.span_id().to_string()
+        );
+        assert_eq!(
+            traces[0]["resourceSpans"][0]["resource"]["attributes"][0]["value"]["stringValue"],
+            "greentic-gui"
+        );
+    }
+}
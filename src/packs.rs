@@ -1,3 +1,4 @@
+use crate::vfs;
 use anyhow::{Context, anyhow};
 use async_trait::async_trait;
 use greentic_distributor_client::{
@@ -7,12 +8,11 @@ use greentic_distributor_client::{
 use greentic_types::{PackManifest, SecretRequirement};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use tokio::fs as tokio_fs;
-use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
 use tracing::{debug, warn};
 use uuid::Uuid;
@@ -115,22 +115,11 @@ impl DistributorPackProvider {
         Ok(Some(resolved))
     }
 
-    async fn load_manifest_from_path(&self, root: PathBuf) -> anyhow::Result<serde_json::Value> {
-        let manifest_path = root.join("gui").join("manifest.json");
-        let mut file = tokio_fs::File::open(&manifest_path)
-            .await
-            .with_context(|| format!("opening manifest {:?}", manifest_path))?;
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf).await?;
-        let json: serde_json::Value = serde_json::from_slice(&buf)?;
-        Ok(json)
-    }
-
     async fn load_pack(&self, tenant: &str, kind: PackKind) -> anyhow::Result<Option<GuiPack>> {
         let Some(resolved) = self.resolve(tenant, kind.clone()).await? else {
             return Ok(None);
         };
-        let manifest_json = self.load_manifest_from_path(resolved.root.clone()).await?;
+        let manifest_json = read_gui_manifest(&resolved.root).await?;
         let gui_pack = match manifest_json.get("kind").and_then(|v| v.as_str()) {
             Some("gui-layout") if kind == PackKind::GuiLayout => {
                 let manifest: LayoutManifest = serde_json::from_value(manifest_json)?;
@@ -159,18 +148,24 @@ impl DistributorPackProvider {
                     pack_hint: resolved.pack_hint,
                 })
             }
-            Some("gui-skin") if kind == PackKind::GuiSkin => Some(GuiPack::Skin {
-                manifest: manifest_json,
-                root: resolved.root,
-                secret_requirements: resolved.secret_requirements,
-                pack_hint: resolved.pack_hint,
-            }),
-            Some("gui-telemetry") if kind == PackKind::GuiTelemetry => Some(GuiPack::Telemetry {
-                manifest: manifest_json,
-                root: resolved.root,
-                secret_requirements: resolved.secret_requirements,
-                pack_hint: resolved.pack_hint,
-            }),
+            Some("gui-skin") if kind == PackKind::GuiSkin => {
+                let manifest: SkinManifest = serde_json::from_value(manifest_json)?;
+                Some(GuiPack::Skin {
+                    manifest,
+                    root: resolved.root,
+                    secret_requirements: resolved.secret_requirements,
+                    pack_hint: resolved.pack_hint,
+                })
+            }
+            Some("gui-telemetry") if kind == PackKind::GuiTelemetry => {
+                let manifest: TelemetryManifest = serde_json::from_value(manifest_json)?;
+                Some(GuiPack::Telemetry {
+                    manifest,
+                    root: resolved.root,
+                    secret_requirements: resolved.secret_requirements,
+                    pack_hint: resolved.pack_hint,
+                })
+            }
             _ => None,
         };
         Ok(gui_pack)
@@ -254,6 +249,13 @@ impl DistributorPackProvider {
         cache.clear();
         tracing::info!("pack cache cleared");
     }
+
+    pub async fn reset_tenant_cache(&self, tenant: &str) {
+        let prefix = format!("{tenant}::");
+        let mut cache = self.cache.lock().await;
+        cache.retain(|key, _| !key.starts_with(&prefix));
+        tracing::info!(tenant, "tenant pack cache cleared");
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -270,6 +272,26 @@ pub struct LayoutConfig {
     pub slot_selectors: HashMap<String, String>,
 }
 
+/// Slot that receives a feature route's page when it is composed into the layout.
+pub const MAIN_SLOT: &str = "main";
+/// Slot that receives the generated navigation menu.
+pub const MENU_SLOT: &str = "menu";
+
+impl LayoutConfig {
+    /// CSS selector for `slot`: its `slot_selectors` entry, else `[data-slot="<slot>"]`.
+    pub fn slot_selector(&self, slot: &str) -> String {
+        self.slot_selectors
+            .get(slot)
+            .cloned()
+            .unwrap_or_else(|| format!("[data-slot=\"{slot}\"]"))
+    }
+
+    /// Feature pages are composed into the layout only when it declares a `main` slot.
+    pub fn composes_features(&self) -> bool {
+        self.slots.iter().any(|slot| slot == MAIN_SLOT)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthManifest {
     pub kind: String,
@@ -278,6 +300,16 @@ pub struct AuthManifest {
     pub ui_bindings: serde_json::Value,
 }
 
+impl AuthManifest {
+    /// Broker/IdP end-session endpoint declared as `oauth.end_session_endpoint`.
+    pub fn end_session_url(&self) -> Option<url::Url> {
+        let raw = self.oauth.get("end_session_endpoint")?.as_str()?;
+        url::Url::parse(raw)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "https" | "http"))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthRoute {
     pub path: String,
@@ -294,6 +326,15 @@ pub struct FeatureManifest {
     pub digital_workers: Vec<DigitalWorker>,
     #[serde(default)]
     pub fragments: Vec<FragmentBinding>,
+    /// Html (relative to the pack assets) contributed to other layout slots, e.g. `header`.
+    #[serde(default)]
+    pub slots: HashMap<String, String>,
+    /// Pack-scoped config readable by fragments granted the `config` capability.
+    #[serde(default)]
+    pub config: serde_json::Map<String, serde_json::Value>,
+    /// Translations by locale, then key, for fragments granted the `i18n` capability.
+    #[serde(default)]
+    pub i18n: HashMap<String, HashMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -302,6 +343,143 @@ pub struct FeatureRoute {
     #[serde(default)]
     pub authenticated: bool,
     pub html: String,
+    /// Adds the route to the generated navigation menu.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub menu: Option<RouteMenu>,
+}
+
+/// Menu metadata for a feature route.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteMenu {
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// Sort key within the group; ties sort by label.
+    #[serde(default)]
+    pub order: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Scope the session must carry to see the entry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
+/// `gui/manifest.json` of a `gui-skin` pack. File paths are relative to the pack assets.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkinManifest {
+    #[serde(default)]
+    pub kind: String,
+    /// Design tokens, emitted as CSS custom properties; nested groups join with `-`, so
+    /// `{ "color": { "primary": "#0a0" } }` becomes `--color-primary`.
+    #[serde(default)]
+    pub tokens: BTreeMap<String, DesignToken>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fonts: Vec<SkinFont>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    /// Extra stylesheets linked after the generated token stylesheet.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stylesheets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DesignToken {
+    Value(String),
+    Number(serde_json::Number),
+    Group(BTreeMap<String, DesignToken>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkinFont {
+    pub family: String,
+    pub src: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+}
+
+/// `gui/manifest.json` of a `gui-telemetry` pack.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryManifest {
+    #[serde(default)]
+    pub kind: String,
+    /// Fraction of client events sent, from 0 to 1.
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Per event type overrides of `sample_rate`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sampling: BTreeMap<String, f64>,
+    /// Event types accepted by `/api/gui/events`; a trailing `*` matches a prefix. Empty allows
+    /// every event type.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_events: Vec<String>,
+    /// Redaction applied to event `metadata` before it is recorded.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact: Vec<RedactionRule>,
+    #[serde(default)]
+    pub auto_capture: AutoCapture,
+}
+
+impl Default for TelemetryManifest {
+    fn default() -> Self {
+        Self {
+            kind: String::new(),
+            sample_rate: default_sample_rate(),
+            sampling: BTreeMap::new(),
+            allowed_events: Vec::new(),
+            redact: Vec::new(),
+            auto_capture: AutoCapture::default(),
+        }
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+/// Redacts the metadata `field` (a dot path, `*` matching any key or index), or every string
+/// value matching `pattern`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedactionRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    #[serde(default)]
+    pub action: RedactAction,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactAction {
+    /// Replace the value (or pattern match) with `[redacted]`.
+    #[default]
+    Mask,
+    /// Drop the field; pattern matches are masked instead.
+    Remove,
+    /// Replace the value (or pattern match) with a truncated SHA-256 digest.
+    Hash,
+}
+
+/// Events the SDK records on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoCapture {
+    /// `page_view` on load and client-side navigation.
+    #[serde(default)]
+    pub page_views: bool,
+    /// `click` for elements carrying `data-track`.
+    #[serde(default)]
+    pub clicks: bool,
+    /// `js_error` for uncaught errors and unhandled rejections.
+    #[serde(default)]
+    pub errors: bool,
+    /// `web_vital` for LCP, CLS and INP.
+    #[serde(default)]
+    pub web_vitals: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -326,6 +504,26 @@ pub struct FragmentBinding {
     pub component_world: String,
     #[serde(rename = "component_name")]
     pub component_name: String,
+    /// Host imports the component may use beyond logging.
+    #[serde(default)]
+    pub capabilities: FragmentCapabilities,
+}
+
+/// Host import capabilities granted to a fragment binding.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FragmentCapabilities {
+    /// Read the pack's `config`.
+    #[serde(default)]
+    pub config: bool,
+    /// Check whether the pack's declared secrets are available.
+    #[serde(default)]
+    pub secrets: bool,
+    /// Look up the pack's `i18n` translations.
+    #[serde(default)]
+    pub i18n: bool,
+    /// Worker ids the fragment may call.
+    #[serde(default)]
+    pub workers: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -360,7 +558,7 @@ pub enum GuiPack {
     },
     #[serde(rename = "gui-skin")]
     Skin {
-        manifest: serde_json::Value,
+        manifest: SkinManifest,
         root: PathBuf,
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         secret_requirements: Vec<SecretRequirement>,
@@ -369,7 +567,7 @@ pub enum GuiPack {
     },
     #[serde(rename = "gui-telemetry")]
     Telemetry {
-        manifest: serde_json::Value,
+        manifest: TelemetryManifest,
         root: PathBuf,
         #[serde(default, skip_serializing_if = "Vec::is_empty")]
         secret_requirements: Vec<SecretRequirement>,
@@ -404,9 +602,13 @@ pub trait PackProvider: Send + Sync {
     async fn load_telemetry(&self, tenant: &str) -> anyhow::Result<Option<GuiPack>>;
     async fn load_features(&self, tenant: &str) -> anyhow::Result<Vec<GuiPack>>;
     async fn clear_cache(&self);
+    /// Drop cached packs of one tenant only.
+    async fn clear_tenant_cache(&self, tenant: &str);
 }
 
 /// File-system backed pack provider for development and tests.
+///
+/// Packs under `PACK_ROOT/<tenant>` may be unpacked directories or `.gtpack` archives.
 pub struct FsPackProvider {
     root: PathBuf,
 }
@@ -416,17 +618,6 @@ impl FsPackProvider {
         Self { root }
     }
 
-    async fn load_manifest(&self, path: &Path) -> anyhow::Result<serde_json::Value> {
-        let manifest_path = path.join("gui").join("manifest.json");
-        let mut file = tokio_fs::File::open(&manifest_path)
-            .await
-            .with_context(|| format!("opening manifest {:?}", manifest_path))?;
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf).await?;
-        let json: serde_json::Value = serde_json::from_slice(&buf)?;
-        Ok(json)
-    }
-
     fn tenant_pack_root(&self, tenant: &str, pack_name: &str) -> PathBuf {
         self.root.join(tenant).join(pack_name)
     }
@@ -440,7 +631,8 @@ impl FsPackProvider {
         let mut packs = Vec::new();
         for entry in entries {
             let entry = entry?;
-            if entry.path().is_dir()
+            let path = entry.path();
+            if (path.is_dir() || (path.is_file() && vfs::is_gtpack(&path)))
                 && let Some(name) = entry.file_name().to_str()
             {
                 packs.push(name.to_string());
@@ -456,7 +648,7 @@ impl PackProvider for FsPackProvider {
         let candidates = self.discover_packs(tenant)?;
         for name in candidates {
             let pack_root = self.tenant_pack_root(tenant, &name);
-            let manifest_json = self.load_manifest(&pack_root).await?;
+            let manifest_json = read_gui_manifest(&pack_root).await?;
             if manifest_json.get("kind").and_then(|v| v.as_str()) == Some("gui-layout") {
                 let manifest: LayoutManifest = serde_json::from_value(manifest_json.clone())
                     .context("parse layout manifest")?;
@@ -477,7 +669,7 @@ impl PackProvider for FsPackProvider {
         let candidates = self.discover_packs(tenant)?;
         for name in candidates {
             let pack_root = self.tenant_pack_root(tenant, &name);
-            let manifest_json = self.load_manifest(&pack_root).await?;
+            let manifest_json = read_gui_manifest(&pack_root).await?;
             if manifest_json.get("kind").and_then(|v| v.as_str()) == Some("gui-auth") {
                 let manifest: AuthManifest =
                     serde_json::from_value(manifest_json.clone()).context("parse auth manifest")?;
@@ -498,12 +690,14 @@ impl PackProvider for FsPackProvider {
         let candidates = self.discover_packs(tenant)?;
         for name in candidates {
             let pack_root = self.tenant_pack_root(tenant, &name);
-            let manifest_json = self.load_manifest(&pack_root).await?;
+            let manifest_json = read_gui_manifest(&pack_root).await?;
             if manifest_json.get("kind").and_then(|v| v.as_str()) == Some("gui-skin") {
+                let manifest: SkinManifest =
+                    serde_json::from_value(manifest_json).context("parse skin manifest")?;
                 let secret_requirements =
                     load_secret_requirements_from_pack_root(&pack_root, vec![], true);
                 return Ok(Some(GuiPack::Skin {
-                    manifest: manifest_json,
+                    manifest,
                     root: pack_root.clone(),
                     secret_requirements,
                     pack_hint: pack_hint_from_root(&pack_root),
@@ -517,12 +711,14 @@ impl PackProvider for FsPackProvider {
         let candidates = self.discover_packs(tenant)?;
         for name in candidates {
             let pack_root = self.tenant_pack_root(tenant, &name);
-            let manifest_json = self.load_manifest(&pack_root).await?;
+            let manifest_json = read_gui_manifest(&pack_root).await?;
             if manifest_json.get("kind").and_then(|v| v.as_str()) == Some("gui-telemetry") {
+                let manifest: TelemetryManifest =
+                    serde_json::from_value(manifest_json).context("parse telemetry manifest")?;
                 let secret_requirements =
                     load_secret_requirements_from_pack_root(&pack_root, vec![], true);
                 return Ok(Some(GuiPack::Telemetry {
-                    manifest: manifest_json,
+                    manifest,
                     root: pack_root.clone(),
                     secret_requirements,
                     pack_hint: pack_hint_from_root(&pack_root),
@@ -537,7 +733,7 @@ impl PackProvider for FsPackProvider {
         let mut features = Vec::new();
         for name in candidates {
             let pack_root = self.tenant_pack_root(tenant, &name);
-            let manifest_json = self.load_manifest(&pack_root).await?;
+            let manifest_json = read_gui_manifest(&pack_root).await?;
             if manifest_json.get("kind").and_then(|v| v.as_str()) == Some("gui-feature") {
                 let manifest: FeatureManifest = serde_json::from_value(manifest_json.clone())
                     .context("parse feature manifest")?;
@@ -555,6 +751,8 @@ impl PackProvider for FsPackProvider {
     }
 
     async fn clear_cache(&self) {}
+
+    async fn clear_tenant_cache(&self, _tenant: &str) {}
 }
 
 #[async_trait]
@@ -588,6 +786,20 @@ impl PackProvider for DistributorPackProvider {
     async fn clear_cache(&self) {
         self.reset_cache().await;
     }
+
+    async fn clear_tenant_cache(&self, tenant: &str) {
+        self.reset_tenant_cache(tenant).await;
+    }
+}
+
+/// Read `gui/manifest.json` from a pack root (directory or `.gtpack`).
+async fn read_gui_manifest(root: &Path) -> anyhow::Result<serde_json::Value> {
+    let manifest_path = root.join("gui").join("manifest.json");
+    let buf = vfs::read(&manifest_path)
+        .await
+        .with_context(|| format!("opening manifest {:?}", manifest_path))?;
+    let json: serde_json::Value = serde_json::from_slice(&buf)?;
+    Ok(json)
 }
 
 pub fn normalize_route(path: &str) -> String {
@@ -619,8 +831,8 @@ fn load_secret_requirements_from_pack_root(
 
 fn read_secret_requirements_from_manifest_cbor(root: &Path) -> Option<Vec<SecretRequirement>> {
     let manifest_path = root.join("manifest.cbor");
-    let file = std::fs::File::open(&manifest_path).ok()?;
-    let manifest: PackManifest = match ciborium::de::from_reader(file) {
+    let bytes = vfs::read_blocking(&manifest_path).ok()?;
+    let manifest: PackManifest = match ciborium::de::from_reader(bytes.as_slice()) {
         Ok(m) => m,
         Err(err) => {
             debug!(?err, path = ?manifest_path, "failed to parse manifest.cbor for secrets");
@@ -632,11 +844,8 @@ fn read_secret_requirements_from_manifest_cbor(root: &Path) -> Option<Vec<Secret
 
 fn read_secret_requirements_from_manifest_json(root: &Path) -> Option<Vec<SecretRequirement>> {
     let manifest_path = root.join("manifest.json");
-    if !manifest_path.exists() {
-        return None;
-    }
-    let file = std::fs::File::open(&manifest_path).ok()?;
-    let manifest: PackManifest = match serde_json::from_reader(file) {
+    let bytes = vfs::read_blocking(&manifest_path).ok()?;
+    let manifest: PackManifest = match serde_json::from_slice(&bytes) {
         Ok(m) => m,
         Err(err) => {
             debug!(?err, path = ?manifest_path, "failed to parse manifest.json for secrets");
@@ -863,6 +1072,50 @@ mod tests {
         assert_eq!(reqs[0].description, req.description);
     }
 
+    #[tokio::test]
+    async fn fs_provider_loads_gtpack_archives() {
+        use zip::write::SimpleFileOptions;
+
+        let temp = tempfile::tempdir().unwrap();
+        let tenant_dir = temp.path().join("tenant");
+        std::fs::create_dir_all(&tenant_dir).unwrap();
+        let archive = tenant_dir.join("billing.gtpack");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        let manifest = serde_json::json!({
+            "kind": "gui-feature",
+            "routes": [{ "path": "/billing", "authenticated": false, "html": "billing.html" }],
+        });
+        writer
+            .start_file("gui/manifest.json", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(manifest.to_string().as_bytes()).unwrap();
+        writer
+            .start_file("gui/assets/billing.html", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"<h1>billing</h1>").unwrap();
+        writer.finish().unwrap();
+
+        let provider = FsPackProvider::new(temp.path().to_path_buf());
+        let features = provider.load_features("tenant").await.unwrap();
+        assert_eq!(features.len(), 1);
+        let GuiPack::Feature {
+            manifest,
+            root,
+            pack_hint,
+            ..
+        } = &features[0]
+        else {
+            panic!("expected feature pack");
+        };
+        assert_eq!(manifest.routes[0].path, "/billing");
+        assert_eq!(root, &archive);
+        assert_eq!(pack_hint.as_deref(), Some(archive.to_str().unwrap()));
+        let html = crate::vfs::read_to_string(&features[0].assets_root().join("billing.html"))
+            .await
+            .unwrap();
+        assert_eq!(html, "<h1>billing</h1>");
+    }
+
     #[test]
     fn creates_gtpack_hint_when_missing() {
         let temp = tempfile::tempdir().unwrap();
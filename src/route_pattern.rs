@@ -0,0 +1,348 @@
+//! Route patterns for feature and auth manifests.
+//!
+//! A pattern is a `/`-separated list of segments:
+//! - `invoices`: literal segment
+//! - `:id`: named segment, captured into the route params
+//! - `:tab?`: optional named segment (only at the end of a pattern)
+//! - `*` / `*rest`: wildcard matching zero or more trailing segments, optionally captured
+//!
+//! When several patterns match, the most specific one wins: segments are compared left to right
+//! with literal > named > optional > wildcard.
+
+use crate::packs::normalize_route;
+use percent_encoding::percent_decode_str;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+pub type RouteParams = BTreeMap<String, String>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Param(String),
+    Optional(String),
+    Wildcard(Option<String>),
+}
+
+impl Segment {
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Literal(_) => 3,
+            Segment::Param(_) => 2,
+            Segment::Optional(_) => 1,
+            Segment::Wildcard(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PatternError {
+    #[error("wildcard must be the last segment in {0}")]
+    WildcardNotLast(String),
+    #[error("optional segments must come last in {0}")]
+    OptionalNotTrailing(String),
+    #[error("empty parameter name in {0}")]
+    EmptyName(String),
+    #[error("duplicate parameter `{1}` in {0}")]
+    DuplicateParam(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutePattern {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+impl RoutePattern {
+    pub fn parse(raw: &str) -> Result<Self, PatternError> {
+        let mut normalized = normalize_route(raw);
+        if normalized.len() > 1 && normalized.ends_with('/') {
+            normalized.pop();
+        }
+        let parts: Vec<&str> = split_segments(&normalized).collect();
+        let mut segments = Vec::with_capacity(parts.len());
+        let mut names = Vec::new();
+        for (idx, part) in parts.iter().enumerate() {
+            let last = idx + 1 == parts.len();
+            let segment = if let Some(name) = part.strip_prefix('*') {
+                if !last {
+                    return Err(PatternError::WildcardNotLast(normalized));
+                }
+                Segment::Wildcard((!name.is_empty()).then(|| name.to_string()))
+            } else if let Some(name) = part.strip_prefix(':') {
+                match name.strip_suffix('?') {
+                    Some(name) => Segment::Optional(name.to_string()),
+                    None => Segment::Param(name.to_string()),
+                }
+            } else {
+                Segment::Literal(part.to_string())
+            };
+            if let Segment::Param(name) | Segment::Optional(name) | Segment::Wildcard(Some(name)) =
+                &segment
+            {
+                if name.is_empty() {
+                    return Err(PatternError::EmptyName(normalized));
+                }
+                if names.contains(name) {
+                    return Err(PatternError::DuplicateParam(normalized, name.clone()));
+                }
+                names.push(name.clone());
+            }
+            if !matches!(segment, Segment::Optional(_) | Segment::Wildcard(_))
+                && segments.iter().any(|s| matches!(s, Segment::Optional(_)))
+            {
+                return Err(PatternError::OptionalNotTrailing(normalized));
+            }
+            segments.push(segment);
+        }
+        Ok(Self {
+            raw: normalized,
+            segments,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Whether the pattern matches exactly one path (no params or wildcards).
+    pub fn is_static(&self) -> bool {
+        self.segments
+            .iter()
+            .all(|segment| matches!(segment, Segment::Literal(_)))
+    }
+
+    /// Specificity of the pattern itself: what it scores against a path with one segment per
+    /// pattern segment. Orders route tables; actual matches compare [`RouteMatch::specificity`].
+    pub fn specificity(&self) -> Specificity {
+        Specificity {
+            ranks: self.segments.iter().map(Segment::rank).collect(),
+            looseness: self.looseness(),
+        }
+    }
+
+    fn looseness(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|s| match s {
+                Segment::Optional(_) => 1,
+                Segment::Wildcard(_) => 2,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Match a normalized request path, returning the captured params and the match strength.
+    pub fn matches(&self, path: &str) -> Option<RouteMatch> {
+        let parts: Vec<&str> = split_segments(path).collect();
+        let mut params = RouteParams::new();
+        let mut ranks = Vec::with_capacity(parts.len());
+        let mut idx = 0;
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(literal) => {
+                    if parts.get(idx) != Some(&literal.as_str()) {
+                        return None;
+                    }
+                    ranks.push(segment.rank());
+                    idx += 1;
+                }
+                Segment::Param(name) => {
+                    params.insert(name.clone(), decode(parts.get(idx)?));
+                    ranks.push(segment.rank());
+                    idx += 1;
+                }
+                Segment::Optional(name) => {
+                    if let Some(part) = parts.get(idx) {
+                        params.insert(name.clone(), decode(part));
+                        ranks.push(segment.rank());
+                        idx += 1;
+                    }
+                }
+                Segment::Wildcard(name) => {
+                    let rest = &parts[idx.min(parts.len())..];
+                    if let Some(name) = name {
+                        let rest: Vec<String> = rest.iter().map(|p| decode(p)).collect();
+                        params.insert(name.clone(), rest.join("/"));
+                    }
+                    ranks.extend(rest.iter().map(|_| segment.rank()));
+                    idx = parts.len();
+                }
+            }
+        }
+        if idx != parts.len() {
+            return None;
+        }
+        Some(RouteMatch {
+            params,
+            specificity: Specificity {
+                ranks,
+                looseness: self.looseness(),
+            },
+        })
+    }
+}
+
+impl RoutePattern {
+    /// Whether both patterns match exactly the same paths (param names aside).
+    pub fn same_shape(&self, other: &Self) -> bool {
+        self.segments.len() == other.segments.len()
+            && self
+                .segments
+                .iter()
+                .zip(&other.segments)
+                .all(|(a, b)| match (a, b) {
+                    (Segment::Literal(a), Segment::Literal(b)) => a == b,
+                    (a, b) => !matches!(a, Segment::Literal(_)) && a.rank() == b.rank(),
+                })
+    }
+
+    /// Whether at least one path matches both patterns.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        segments_overlap(&self.segments, &other.segments)
+    }
+}
+
+fn segments_overlap(a: &[Segment], b: &[Segment]) -> bool {
+    match (a.first(), b.first()) {
+        (Some(Segment::Wildcard(_)), _) | (_, Some(Segment::Wildcard(_))) => true,
+        (None, None) => true,
+        (Some(Segment::Optional(_)), _) if segments_overlap(&a[1..], b) => true,
+        (_, Some(Segment::Optional(_))) if segments_overlap(a, &b[1..]) => true,
+        (Some(x), Some(y)) => {
+            let compatible = match (x, y) {
+                (Segment::Literal(x), Segment::Literal(y)) => x == y,
+                _ => true,
+            };
+            compatible && segments_overlap(&a[1..], &b[1..])
+        }
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteMatch {
+    pub params: RouteParams,
+    pub specificity: Specificity,
+}
+
+/// How strongly a pattern matched a path; greater is more specific.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Specificity {
+    /// Rank of the pattern segment that consumed each path segment.
+    ranks: Vec<u8>,
+    /// Optional (1) and wildcard (2) segments in the pattern; lower wins a tie.
+    looseness: usize,
+}
+
+impl Ord for Specificity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.ranks
+            .cmp(&other.ranks)
+            .then_with(|| other.looseness.cmp(&self.looseness))
+    }
+}
+
+impl PartialOrd for Specificity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn split_segments(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/').filter(|s| !s.is_empty())
+}
+
+fn decode(part: &str) -> String {
+    percent_decode_str(part).decode_utf8_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pattern: &str, path: &str) -> Option<RouteParams> {
+        RoutePattern::parse(pattern)
+            .unwrap()
+            .matches(path)
+            .map(|m| m.params)
+    }
+
+    #[test]
+    fn matches_named_optional_and_wildcard_segments() {
+        assert_eq!(
+            params("/invoices/:id", "/invoices/42%20a"),
+            Some(RouteParams::from([("id".into(), "42 a".into())]))
+        );
+        assert_eq!(params("/invoices/:id", "/invoices"), None);
+        assert_eq!(
+            params("/reports/:year/:month?", "/reports/2024"),
+            Some(RouteParams::from([("year".into(), "2024".into())]))
+        );
+        assert_eq!(
+            params("/docs/*path", "/docs/a/b"),
+            Some(RouteParams::from([("path".into(), "a/b".into())]))
+        );
+        assert_eq!(params("/invoices/*", "/invoices"), Some(RouteParams::new()));
+        assert_eq!(
+            params("/invoices/*", "/invoices/1/edit"),
+            Some(RouteParams::new())
+        );
+        // Wildcards are anchored at segment boundaries.
+        assert_eq!(params("/invoices/*", "/invoicesXYZ"), None);
+        assert_eq!(params("/invoices", "/invoices/1"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(matches!(
+            RoutePattern::parse("/a/*/b"),
+            Err(PatternError::WildcardNotLast(_))
+        ));
+        assert!(matches!(
+            RoutePattern::parse("/a/:b?/c"),
+            Err(PatternError::OptionalNotTrailing(_))
+        ));
+        assert!(matches!(
+            RoutePattern::parse("/a/:id/:id"),
+            Err(PatternError::DuplicateParam(_, _))
+        ));
+        assert!(matches!(
+            RoutePattern::parse("/a/:"),
+            Err(PatternError::EmptyName(_))
+        ));
+    }
+
+    #[test]
+    fn detects_overlapping_and_duplicate_patterns() {
+        let p = |raw: &str| RoutePattern::parse(raw).unwrap();
+        assert!(p("/billing").same_shape(&p("billing/")));
+        assert!(p("/invoices/:id").same_shape(&p("/invoices/:invoice")));
+        assert!(!p("/invoices/:id").same_shape(&p("/invoices/:id?")));
+
+        assert!(p("/invoices/*").overlaps(&p("/invoices/:id")));
+        assert!(p("/invoices/*").overlaps(&p("/invoices")));
+        assert!(p("/invoices/:id?").overlaps(&p("/invoices")));
+        assert!(p("/:section/new").overlaps(&p("/invoices/:id")));
+        assert!(!p("/invoices/*").overlaps(&p("/invoicesXYZ")));
+        assert!(!p("/invoices/:id").overlaps(&p("/invoices")));
+        assert!(!p("/invoices/new").overlaps(&p("/reports/new")));
+    }
+
+    #[test]
+    fn most_specific_pattern_wins() {
+        let score = |pattern: &str, path: &str| {
+            RoutePattern::parse(pattern)
+                .unwrap()
+                .matches(path)
+                .unwrap()
+                .specificity
+        };
+        assert!(score("/invoices/new", "/invoices/new") > score("/invoices/:id", "/invoices/new"));
+        assert!(score("/invoices/:id", "/invoices/new") > score("/invoices/*", "/invoices/new"));
+        assert!(score("/a", "/a") > score("/a/:b?", "/a"));
+        assert!(score("/a/:b?", "/a") > score("/a/*", "/a"));
+        assert!(score("/a/:b/*", "/a/b/c") > score("/a/*", "/a/b/c"));
+    }
+}
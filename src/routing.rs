@@ -1,7 +1,11 @@
+use crate::fragments::compose_page;
 use crate::integration::{SessionInfo, SessionManager};
-use crate::tenant::{AuthPack, ResolvedRoute, TenantGuiConfig};
+use crate::packs::{MAIN_SLOT, MENU_SLOT};
+use crate::route_pattern::RouteParams;
+use crate::tenant::{AuthPack, FeaturePack, MenuItem, ResolvedRoute, RouteSource, TenantGuiConfig};
+use crate::vfs;
 use anyhow::Context;
-use tokio::fs;
+use tracing::warn;
 
 #[derive(Debug)]
 pub enum RouteDecision {
@@ -15,41 +19,234 @@ pub struct RouteContent {
     pub html: String,
     pub fragments: Vec<crate::tenant::FragmentTarget>,
     pub session: Option<SessionInfo>,
+    /// Manifest pattern that matched, if any, and the params it captured.
+    pub pattern: Option<String>,
+    pub params: RouteParams,
+    /// Served the layout entrypoint of an SPA layout, which carries the route manifest.
+    pub spa_entrypoint: bool,
+    /// Selector of the layout's `menu` slot when the generated menu should fill it.
+    pub menu_slot: Option<String>,
+}
+
+/// Built-in pages served by the runtime when no pack route claims the path.
+const BUILTIN_PAGES: [&str; 3] = ["/login", "/logout", "/unauthorized"];
+
+/// Pack route for `path`, leaving built-in pages to the runtime unless a pack route claims them.
+pub fn route_for_path(tenant_cfg: &TenantGuiConfig, path: &str) -> Option<ResolvedRoute> {
+    tenant_cfg.resolve_route(path).filter(|route| {
+        !(matches!(route.source, RouteSource::Layout(_)) && BUILTIN_PAGES.contains(&path))
+    })
 }
 
 pub async fn resolve_route(
     tenant_cfg: &TenantGuiConfig,
     path: &str,
+    query: Option<&str>,
     session_token: Option<String>,
     session_manager: &dyn SessionManager,
 ) -> anyhow::Result<RouteDecision> {
-    let resolved = tenant_cfg.resolve_route(path);
-    let Some(resolved) = resolved else {
+    let Some(resolved) = route_for_path(tenant_cfg, path) else {
         return Ok(RouteDecision::NotFound);
     };
 
     let requires_auth = resolved.authenticated;
     let session = session_manager.validate(session_token).await?;
     if requires_auth && session.is_none() {
-        let login_target = login_path(tenant_cfg.auth.as_ref());
+        let requested = match query {
+            Some(query) => format!("{path}?{query}"),
+            None => path.to_string(),
+        };
+        let login_target = login_redirect(tenant_cfg.auth.as_ref(), &requested);
         return Ok(RouteDecision::Redirect(login_target));
     }
 
-    let html = load_html(&resolved).await?;
+    let layout = &tenant_cfg.layout.manifest.layout;
+    let mut html = load_html(&resolved).await?;
+    let in_layout = match &resolved.source {
+        RouteSource::Feature(feature) if layout.composes_features() => {
+            html = compose_feature_page(tenant_cfg, feature, html).await?;
+            // A feature's own menu contribution replaces the generated one.
+            !feature.manifest.slots.contains_key(MENU_SLOT)
+        }
+        RouteSource::Layout(_) => true,
+        _ => false,
+    };
+    let menu_slot = (in_layout && layout.slots.iter().any(|slot| slot == MENU_SLOT))
+        .then(|| layout.slot_selector(MENU_SLOT));
+    let spa_entrypoint = matches!(resolved.source, RouteSource::Layout(_)) && layout.spa;
     Ok(RouteDecision::Serve(Box::new(RouteContent {
         html,
         fragments: resolved.fragments,
         session,
+        pattern: resolved.pattern,
+        params: resolved.params,
+        spa_entrypoint,
+        menu_slot,
     })))
 }
 
 async fn load_html(resolved: &ResolvedRoute) -> anyhow::Result<String> {
-    let contents = fs::read_to_string(&resolved.html_path)
+    let contents = vfs::read_to_string(&resolved.html_path)
         .await
         .with_context(|| format!("reading html {:?}", resolved.html_path))?;
     Ok(contents)
 }
 
+/// Wrap a feature page in the layout chrome: the page fills the `main` slot and the feature's
+/// slot contributions fill theirs. Layouts without an element for `main` get the page as is.
+async fn compose_feature_page(
+    tenant_cfg: &TenantGuiConfig,
+    feature: &FeaturePack,
+    page: String,
+) -> anyhow::Result<String> {
+    let layout = &tenant_cfg.layout;
+    let entrypoint = layout
+        .location
+        .assets
+        .join(&layout.manifest.layout.entrypoint_html);
+    let layout_html = vfs::read_to_string(&entrypoint)
+        .await
+        .with_context(|| format!("reading layout entrypoint {entrypoint:?}"))?;
+
+    let mut contributions = Vec::new();
+    for (slot, file) in &feature.manifest.slots {
+        if slot == MAIN_SLOT {
+            continue;
+        }
+        let path = feature.location.assets.join(file);
+        let html = vfs::read_to_string(&path)
+            .await
+            .with_context(|| format!("reading {slot} slot html {path:?}"))?;
+        contributions.push((layout.manifest.layout.slot_selector(slot), html));
+    }
+
+    let main = layout.manifest.layout.slot_selector(MAIN_SLOT);
+    match compose_page(&layout_html, &main, &page, &contributions) {
+        Ok(html) => Ok(html),
+        Err(err) => {
+            warn!(
+                ?err,
+                "layout cannot host feature page; serving it standalone"
+            );
+            Ok(page)
+        }
+    }
+}
+
+/// Element id of the JSON route descriptor read by the SDK's `getRoute()`.
+pub const ROUTE_DATA_ID: &str = "greentic-route";
+/// Element id of the client-side route manifest in SPA entrypoints.
+pub const ROUTE_MANIFEST_ID: &str = "greentic-routes";
+
+/// `<script type="application/json">` describing the matched route for the SDK.
+pub fn route_data_script(path: &str, content: &RouteContent) -> String {
+    let data = serde_json::json!({
+        "path": path,
+        "pattern": content.pattern,
+        "params": content.params,
+    });
+    json_script(ROUTE_DATA_ID, &data)
+}
+
+/// `<script type="application/json">` listing the tenant's routes for SPA navigation.
+pub fn route_manifest_script(tenant_cfg: &TenantGuiConfig) -> String {
+    let data = serde_json::json!({ "routes": tenant_cfg.route_manifest() });
+    json_script(ROUTE_MANIFEST_ID, &data)
+}
+
+fn json_script(id: &str, data: &serde_json::Value) -> String {
+    // `</` inside JSON strings would otherwise close the script element.
+    let json = data.to_string().replace("</", "<\\/");
+    format!("<script type=\"application/json\" id=\"{id}\">{json}</script>")
+}
+
+/// Generated navigation menu; the entry for `current_path` gets `aria-current="page"`.
+pub fn render_menu(
+    items: &[MenuItem],
+    current_path: &str,
+    url_for: impl Fn(&str) -> String,
+) -> String {
+    let entry = |item: &MenuItem| {
+        let current = if item.href == current_path {
+            " aria-current=\"page\""
+        } else {
+            ""
+        };
+        let icon = item
+            .icon
+            .as_deref()
+            .map(|icon| {
+                format!(
+                    "<span class=\"greentic-menu-icon\" data-icon=\"{}\"></span>",
+                    escape_html(icon)
+                )
+            })
+            .unwrap_or_default();
+        format!(
+            "<li><a href=\"{}\"{current}>{icon}{}</a></li>",
+            escape_html(&url_for(&item.href)),
+            escape_html(&item.label)
+        )
+    };
+
+    let mut html = String::from("<nav class=\"greentic-menu\"><ul>");
+    let mut open_group: Option<&str> = None;
+    for item in items {
+        let group = item.group.as_deref();
+        if group != open_group {
+            if open_group.is_some() {
+                html.push_str("</ul></li>");
+            }
+            if let Some(group) = group {
+                html.push_str(&format!(
+                    "<li class=\"greentic-menu-group\"><span class=\"greentic-menu-group-label\">{}</span><ul>",
+                    escape_html(group)
+                ));
+            }
+            open_group = group;
+        }
+        html.push_str(&entry(item));
+    }
+    if open_group.is_some() {
+        html.push_str("</ul></li>");
+    }
+    html.push_str("</ul></nav>");
+    html
+}
+
+pub fn escape_html(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for ch in raw.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Insert `snippet` just before `</head>`, or at the top of the document when there is none.
+pub fn inject_head(html: &str, snippet: &str) -> String {
+    match html.to_ascii_lowercase().find("</head>") {
+        Some(idx) => format!("{}{snippet}{}", &html[..idx], &html[idx..]),
+        None => format!("{snippet}{html}"),
+    }
+}
+
+/// Query parameter (and cookie) carrying the page to restore after login.
+pub const RETURN_TO_PARAM: &str = "return_to";
+pub const RETURN_TO_COOKIE: &str = "greentic_return_to";
+
+/// Login path with the originally requested page attached as `return_to`.
+pub fn login_redirect(auth: Option<&AuthPack>, requested: &str) -> String {
+    let encoded: String = url::form_urlencoded::byte_serialize(requested.as_bytes()).collect();
+    format!("{}?{RETURN_TO_PARAM}={encoded}", login_path(auth))
+}
+
 fn login_path(auth: Option<&AuthPack>) -> String {
     if let Some(auth) = auth
         && let Some(route) = auth.manifest.routes.first()
@@ -58,3 +255,378 @@ fn login_path(auth: Option<&AuthPack>) -> String {
     }
     "/login".to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::fragments::CompositeFragmentRenderer;
+    use crate::integration::{NullTelemetrySink, StubSessionManager};
+    use crate::packs::FsPackProvider;
+    use crate::server::AppState;
+    use crate::worker::{StubWorkerBackend, WorkerHost};
+    use axum::body::{Body, to_bytes};
+    use axum::http::{Request, StatusCode, header};
+    use serde_json::json;
+    use std::path::Path;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    fn write_pack(root: &Path, pack: &str, manifest: serde_json::Value, files: &[(&str, &str)]) {
+        let gui = root.join("tenant").join(pack).join("gui");
+        std::fs::create_dir_all(gui.join("assets").join("fragments")).unwrap();
+        std::fs::write(gui.join("manifest.json"), manifest.to_string()).unwrap();
+        for (name, contents) in files {
+            std::fs::write(gui.join("assets").join(name), contents).unwrap();
+        }
+    }
+
+    fn test_app(packs: &Path) -> axum::Router {
+        crate::server::router(AppState::new(
+            crate::config::test_config(),
+            Arc::new(FsPackProvider::new(packs.to_path_buf())),
+            Arc::new(CompositeFragmentRenderer::file_only()),
+            Arc::new(StubSessionManager),
+            Arc::new(NullTelemetrySink),
+            Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+        ))
+    }
+
+    async fn get(app: &axum::Router, uri: &str) -> (StatusCode, String) {
+        get_with_cookie(app, uri, None).await
+    }
+
+    async fn get_with_cookie(
+        app: &axum::Router,
+        uri: &str,
+        cookie: Option<&str>,
+    ) -> (StatusCode, String) {
+        let mut req = Request::get(uri).header(header::HOST, "localhost");
+        if let Some(cookie) = cookie {
+            req = req.header(header::COOKIE, cookie);
+        }
+        let req = req.body(Body::empty()).unwrap();
+        let resp = app.clone().oneshot(req).await.unwrap();
+        let status = resp.status();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn spa_layout_serves_entrypoint_and_route_fragments() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            json!({
+                "kind": "gui-layout",
+                "layout": { "slots": ["main"], "entrypoint_html": "index.html", "spa": true, "slot_selectors": {} },
+            }),
+            &[(
+                "index.html",
+                "<html><head></head><body><main id=\"summary\"></main></body></html>",
+            )],
+        );
+        write_pack(
+            temp.path(),
+            "billing",
+            json!({
+                "kind": "gui-feature",
+                "routes": [{ "path": "/invoices/:id", "html": "invoice.html" }],
+                "fragments": [{
+                    "id": "summary",
+                    "selector": "#summary",
+                    "component_world": "greentic:gui/gui-fragment@1.0.0",
+                    "component_name": "summary",
+                }],
+            }),
+            &[
+                ("invoice.html", "<main id=\"summary\"></main>"),
+                ("fragments/summary.html", "<b>invoice</b>"),
+            ],
+        );
+        let app = test_app(temp.path());
+
+        let (status, html) = get(&app, "/reports/2024").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("id=\"greentic-routes\""), "{html}");
+        assert!(html.contains("\"path\":\"/invoices/:id\""), "{html}");
+        assert!(html.contains("\"fragments\":[\"summary\"]"), "{html}");
+
+        let (status, _) = get(&app, "/static/app.js").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, html) = get(&app, "/invoices/9").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!html.contains("greentic-routes"), "{html}");
+
+        let (status, body) = get(&app, "/api/gui/fragments?path=%2Finvoices%2F9").await;
+        assert_eq!(status, StatusCode::OK);
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["params"]["id"], "9");
+        assert_eq!(body["fragments"][0]["html"], "<b>invoice</b>");
+
+        let (status, _) = get(&app, "/api/gui/fragments?path=%2Fapp.js").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn served_pages_join_the_incoming_trace() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            json!({
+                "kind": "gui-layout",
+                "layout": { "slots": ["main"], "entrypoint_html": "index.html", "spa": true, "slot_selectors": {} },
+            }),
+            &[("index.html", "<html><head></head><body></body></html>")],
+        );
+        let app = test_app(temp.path());
+        let (exporter, _guard) = crate::otlp::record_spans();
+
+        let incoming = "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-01";
+        let req = Request::get("/home")
+            .header(header::HOST, "localhost")
+            .header("traceparent", incoming)
+            .body(Body::empty())
+            .unwrap();
+        let resp = app.oneshot(req).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        let html = String::from_utf8(body.to_vec()).unwrap();
+        let meta = html
+            .split("<meta name=\"traceparent\" content=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .and_then(crate::otlp::TraceParent::parse)
+            .expect("traceparent meta tag");
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let page = spans
+            .iter()
+            .find(|span| span.name == "gui_request")
+            .expect("exported gui_request span");
+        let incoming = crate::otlp::TraceParent::parse(incoming).unwrap();
+        assert_eq!(page.span_context.trace_id().to_string(), incoming.trace_id);
+        assert_eq!(page.parent_span_id.to_string(), incoming.span_id);
+        assert_eq!(meta.trace_id, incoming.trace_id);
+        assert_eq!(meta.span_id, page.span_context.span_id().to_string());
+    }
+
+    #[tokio::test]
+    async fn path_prefix_tenants_are_routed_by_the_router() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            json!({
+                "kind": "gui-layout",
+                "layout": { "slots": ["main"], "entrypoint_html": "index.html", "spa": true, "slot_selectors": {} },
+            }),
+            &[("index.html", "<html><head></head><body></body></html>")],
+        );
+        let mut config = crate::config::test_config();
+        config.domains = toml::from_str(
+            r#"
+            [[domain]]
+            path_prefix = "/t/tenant"
+            tenant = "tenant"
+            "#,
+        )
+        .unwrap();
+        let app = crate::server::router(AppState::new(
+            config,
+            Arc::new(FsPackProvider::new(temp.path().to_path_buf())),
+            Arc::new(CompositeFragmentRenderer::file_only()),
+            Arc::new(StubSessionManager),
+            Arc::new(NullTelemetrySink),
+            Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+        ));
+
+        let (status, html) = get(&app, "/t/tenant/home").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains(r#"<meta name="greentic-base" content="/t/tenant">"#));
+        let (status, body) = get(&app, "/t/tenant/api/gui/config").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""tenant":"#));
+        let (status, _) = get(&app, "/home").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn feature_pages_render_inside_layout_slots() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            json!({
+                "kind": "gui-layout",
+                "layout": {
+                    "slots": ["header", "main"],
+                    "entrypoint_html": "index.html",
+                    "spa": false,
+                    "slot_selectors": { "main": "#content" },
+                },
+            }),
+            &[(
+                "index.html",
+                "<html><head><title>Acme</title></head><body>\
+                 <header data-slot=\"header\">Acme</header><div id=\"content\"></div></body></html>",
+            )],
+        );
+        write_pack(
+            temp.path(),
+            "billing",
+            json!({
+                "kind": "gui-feature",
+                "routes": [{ "path": "/invoices", "html": "invoices.html" }],
+                "slots": { "header": "header.html" },
+                "fragments": [{
+                    "id": "total",
+                    "selector": "#total",
+                    "component_world": "greentic:gui/gui-fragment@1.0.0",
+                    "component_name": "total",
+                }],
+            }),
+            &[
+                (
+                    "invoices.html",
+                    "<h1>Invoices</h1><span id=\"total\"></span>",
+                ),
+                ("header.html", "<nav>Billing</nav>"),
+                ("fragments/total.html", "42"),
+            ],
+        );
+        let app = test_app(temp.path());
+
+        let (status, html) = get(&app, "/invoices").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(html.contains("<title>Acme</title>"), "{html}");
+        assert!(
+            html.contains(
+                "<div id=\"content\"><h1>Invoices</h1><span id=\"total\">42</span></div>"
+            ),
+            "{html}"
+        );
+        assert!(html.contains("<nav>Billing</nav>"), "{html}");
+        assert!(!html.contains(">Acme</header>"), "{html}");
+    }
+
+    #[tokio::test]
+    async fn layout_menu_lists_routes_visible_to_the_session() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            json!({
+                "kind": "gui-layout",
+                "layout": { "slots": ["menu", "main"], "entrypoint_html": "index.html", "spa": false, "slot_selectors": {} },
+            }),
+            &[(
+                "index.html",
+                "<html><head></head><body><aside data-slot=\"menu\"></aside>\
+                 <div data-slot=\"main\"></div></body></html>",
+            )],
+        );
+        write_pack(
+            temp.path(),
+            "billing",
+            json!({
+                "kind": "gui-feature",
+                "routes": [
+                    { "path": "/invoices", "html": "page.html", "menu": { "label": "Invoices", "icon": "receipt", "order": 1 } },
+                    { "path": "/invoices/:id", "html": "page.html", "menu": { "label": "Invoice" } },
+                    { "path": "/reports", "html": "page.html", "authenticated": true, "menu": { "label": "Reports & Charts", "group": "Analytics" } },
+                    { "path": "/admin", "html": "page.html", "menu": { "label": "Admin", "scope": "gui.admin" } },
+                ],
+            }),
+            &[("page.html", "<h1>page</h1>")],
+        );
+        let app = test_app(temp.path());
+
+        let (_, html) = get(&app, "/invoices").await;
+        assert!(
+            html.contains(
+                "<a aria-current=\"page\" href=\"/invoices\"><span class=\"greentic-menu-icon\" data-icon=\"receipt\"></span>Invoices</a>"
+            ),
+            "{html}"
+        );
+        assert!(!html.contains("Reports"), "{html}");
+        assert!(!html.contains("Invoice<"), "{html}");
+
+        let cookie = Some("greentic_session_id=s1");
+        let (_, html) = get_with_cookie(&app, "/invoices", cookie).await;
+        assert!(
+            html.contains("<span class=\"greentic-menu-group-label\">Analytics</span>"),
+            "{html}"
+        );
+        assert!(html.contains(">Reports &amp; Charts</a>"), "{html}");
+        assert!(!html.contains("Admin"), "{html}");
+
+        let (_, body) = get_with_cookie(&app, "/api/gui/config", cookie).await;
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let labels: Vec<_> = body["menu"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert_eq!(labels, ["Invoices", "Reports & Charts"]);
+    }
+
+    #[tokio::test]
+    async fn skin_themes_pages_and_shadows_layout_assets() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            json!({
+                "kind": "gui-layout",
+                "layout": { "slots": [], "entrypoint_html": "index.html", "spa": true, "slot_selectors": {} },
+            }),
+            &[
+                (
+                    "index.html",
+                    "<html><head><link href=\"/default.ico\" rel=\"icon\"></head><body></body></html>",
+                ),
+                ("app.css", "layout"),
+                ("layout.css", "layout"),
+            ],
+        );
+        write_pack(
+            temp.path(),
+            "brand",
+            json!({
+                "kind": "gui-skin",
+                "tokens": { "color": { "primary": "#0a7" } },
+                "favicon": "icon.png",
+                "stylesheets": ["brand.css"],
+            }),
+            &[("app.css", "skin"), ("brand.css", "brand")],
+        );
+        let app = test_app(temp.path());
+
+        let (status, html) = get(&app, "/home").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            html.contains(
+                "<style id=\"greentic-skin\">:root{--color-primary:#0a7;}</style>\
+                 <link rel=\"icon\" href=\"/_packs/skin/brand/icon.png\">\
+                 <link rel=\"stylesheet\" href=\"/_packs/skin/brand/brand.css\"></head>"
+            ),
+            "{html}"
+        );
+        assert!(!html.contains("default.ico"), "{html}");
+
+        let (_, html) = get(&app, "/login").await;
+        assert!(html.contains("--color-primary:#0a7"), "{html}");
+
+        let (_, css) = get(&app, "/_packs/layout/layout/app.css").await;
+        assert_eq!(css, "skin");
+        let (_, css) = get(&app, "/_packs/layout/layout/layout.css").await;
+        assert_eq!(css, "layout");
+
+        let (_, body) = get(&app, "/api/gui/config").await;
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["theme"]["variables"]["--color-primary"], "#0a7");
+    }
+}
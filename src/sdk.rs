@@ -64,11 +64,13 @@ pub fn sdk_script() -> String {
     }
   }
 
-  async function startSession({ userId, team }) {
+  async function startSession({ userId, team, token }) {
     if (!config) await init();
+    const headers = { "Content-Type": "application/json" };
+    if (token) headers.Authorization = `Bearer ${token}`;
     const res = await fetch("/api/gui/session", {
       method: "POST",
-      headers: { "Content-Type": "application/json" },
+      headers,
       body: JSON.stringify({ user_id: userId, team }),
     });
     if (!res.ok) {
@@ -1,16 +1,29 @@
+use crate::admin::{self, AdminPrincipal};
 use crate::api;
+use crate::assets;
 use crate::auth;
 use crate::config::AppConfig;
-use crate::fragments::{FragmentError, FragmentRenderer, inject_fragments};
-use crate::integration::{SessionManager, TelemetryEvent, TelemetrySink};
+use crate::domains::{self, RequestTenant};
+use crate::fragments::{
+    FragmentError, FragmentRenderer, FragmentRequest, fill_slot, inject_fragments,
+};
+use crate::integration::{SessionManager, TelemetryEvent, TelemetrySink, make_telemetry_ctx};
+use crate::oauth_flow::{ConsumedFlows, FlowError, PendingFlow};
+use crate::otlp::TraceContext;
 use crate::packs::PackProvider;
-use crate::routing::{RouteDecision, resolve_route};
+use crate::routing::{
+    RouteDecision, inject_head, render_menu, resolve_route, route_data_script,
+    route_manifest_script,
+};
+use crate::skin::Theme;
+use crate::telemetry::EventRateLimiter;
 use crate::tenant::TenantGuiConfig;
+use crate::watch::PackChange;
 use crate::worker::WorkerHost;
 use anyhow::Context;
 use axum::Json;
 use axum::Router;
-use axum::extract::{Path, State};
+use axum::extract::{Extension, Path, RawQuery, State};
 use axum::http::{HeaderMap, StatusCode, header};
 use axum::response::{Html, IntoResponse, Redirect};
 use axum::routing::{get, post};
@@ -23,7 +36,7 @@ use std::time::Instant;
 use tokio::fs;
 use tokio::net::TcpListener;
 use tokio::signal;
-use tokio::sync::RwLock;
+use tokio::sync::{RwLock, broadcast};
 use tower_http::trace::TraceLayer;
 use tracing::warn;
 
@@ -38,6 +51,9 @@ pub struct AppState {
     tenant_cache: Arc<RwLock<HashMap<String, CachedTenant>>>,
     cache_hits: Arc<AtomicU64>,
     cache_misses: Arc<AtomicU64>,
+    pack_changes: broadcast::Sender<PackChange>,
+    consumed_flows: Arc<ConsumedFlows>,
+    event_limiter: Arc<EventRateLimiter>,
 }
 
 impl AppState {
@@ -49,6 +65,7 @@ impl AppState {
         telemetry: Arc<dyn TelemetrySink>,
         worker_host: Arc<WorkerHost>,
     ) -> Self {
+        let event_limiter = Arc::new(EventRateLimiter::new(config.telemetry_rate_limit));
         Self {
             config,
             pack_provider,
@@ -59,30 +76,134 @@ impl AppState {
             tenant_cache: Arc::new(RwLock::new(HashMap::new())),
             cache_hits: Arc::new(AtomicU64::new(0)),
             cache_misses: Arc::new(AtomicU64::new(0)),
+            pack_changes: broadcast::channel(64).0,
+            consumed_flows: Arc::new(ConsumedFlows::default()),
+            event_limiter,
         }
     }
 
-    pub async fn load_tenant(&self, domain: &str) -> anyhow::Result<TenantGuiConfig> {
-        let tenant = self.config.tenant_for_domain(domain).to_string();
-        if let Some(cfg) = self.cached_tenant(&tenant).await {
+    pub async fn load_tenant(&self, tenant: &str, domain: &str) -> anyhow::Result<TenantGuiConfig> {
+        if let Some(cfg) = self.cached_tenant(tenant).await {
             self.cache_hits.fetch_add(1, Ordering::Relaxed);
             return Ok(cfg);
         }
 
-        let cfg = TenantGuiConfig::load(&tenant, domain, self.pack_provider.clone()).await?;
-        self.insert_cache(tenant, cfg.clone()).await;
+        let cfg = self.load_from_packs(tenant, domain).await?;
+        self.insert_cache(tenant.to_string(), cfg.clone()).await;
         self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        self.prepare_fragments(&cfg);
         Ok(cfg)
     }
 
+    /// Reload one tenant in place, keeping the previous config if the new packs fail to load.
+    pub async fn reload_tenant(&self, tenant: &str) -> anyhow::Result<TenantGuiConfig> {
+        let previous = self
+            .tenant_cache
+            .read()
+            .await
+            .get(tenant)
+            .map(|entry| entry.config.clone());
+        let domain = previous
+            .as_ref()
+            .map(|cfg| cfg.domain.clone())
+            .unwrap_or_else(|| tenant.to_string());
+        let cfg = self.load_from_packs(tenant, &domain).await?;
+        if let Some(previous) = &previous {
+            self.evict_packs(previous).await;
+        }
+        self.insert_cache(tenant.to_string(), cfg.clone()).await;
+        self.prepare_fragments(&cfg);
+        Ok(cfg)
+    }
+
+    /// Precompile a tenant's fragment components in the background so first renders skip it.
+    fn prepare_fragments(&self, cfg: &TenantGuiConfig) {
+        let targets: Vec<_> = cfg
+            .features
+            .iter()
+            .flat_map(|feature| {
+                feature
+                    .manifest
+                    .fragments
+                    .iter()
+                    .map(|binding| (binding.clone(), feature.location.assets.clone()))
+            })
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+        let renderer = self.fragment_renderer.clone();
+        tokio::spawn(async move {
+            for (binding, assets_root) in targets {
+                renderer.prepare(&binding, &assets_root).await;
+            }
+        });
+    }
+
+    /// Drop cached archive indexes of a tenant's packs and compiled components of its feature
+    /// packs.
+    async fn evict_packs(&self, cfg: &TenantGuiConfig) {
+        let locations = [Some(&cfg.layout.location)]
+            .into_iter()
+            .chain([
+                cfg.auth.as_ref().map(|pack| &pack.location),
+                cfg.skin.as_ref().map(|pack| &pack.location),
+                cfg.telemetry.as_ref().map(|pack| &pack.location),
+            ])
+            .flatten()
+            .chain(cfg.features.iter().map(|pack| &pack.location));
+        for location in locations {
+            crate::vfs::forget(&location.root);
+        }
+        for feature in &cfg.features {
+            self.fragment_renderer.evict(&feature.location.assets).await;
+        }
+    }
+
+    async fn load_from_packs(&self, tenant: &str, domain: &str) -> anyhow::Result<TenantGuiConfig> {
+        let cfg = TenantGuiConfig::load(tenant, domain, self.pack_provider.clone()).await?;
+        if self.config.strict_routes && !cfg.route_conflicts.is_empty() {
+            let conflicts: Vec<String> =
+                cfg.route_conflicts.iter().map(|c| c.to_string()).collect();
+            anyhow::bail!(
+                "route conflicts for tenant {tenant}: {}",
+                conflicts.join("; ")
+            );
+        }
+        Ok(cfg)
+    }
+
+    /// Broadcast a pack change to live-reload subscribers (no-op without subscribers).
+    pub fn notify_pack_change(&self, change: PackChange) {
+        let _ = self.pack_changes.send(change);
+    }
+
+    pub fn subscribe_pack_changes(&self) -> broadcast::Receiver<PackChange> {
+        self.pack_changes.subscribe()
+    }
+
+    /// Mark an OAuth login flow completed, rejecting replays of the same callback.
+    pub fn consume_oauth_flow(&self, flow: &PendingFlow, now: i64) -> Result<(), FlowError> {
+        self.consumed_flows.consume(flow, now)
+    }
+
+    /// Grant up to `wanted` telemetry events to `session` under the per-minute rate limit.
+    pub fn take_events(&self, session: &str, wanted: usize, now_ms: i64) -> usize {
+        self.event_limiter.take(session, wanted, now_ms)
+    }
+
+    fn caching_enabled(&self) -> bool {
+        self.config.watch_packs || !self.config.pack_cache_ttl.is_zero()
+    }
+
     async fn cached_tenant(&self, tenant: &str) -> Option<TenantGuiConfig> {
-        let ttl = self.config.pack_cache_ttl;
-        if ttl.is_zero() {
+        if !self.caching_enabled() {
             return None;
         }
+        let ttl = self.config.pack_cache_ttl;
         let cache = self.tenant_cache.read().await;
         if let Some(entry) = cache.get(tenant)
-            && entry.created.elapsed() <= ttl
+            && (self.config.watch_packs || entry.created.elapsed() <= ttl)
         {
             return Some(entry.config.clone());
         }
@@ -90,7 +211,7 @@ impl AppState {
     }
 
     async fn insert_cache(&self, tenant: String, cfg: TenantGuiConfig) {
-        if self.config.pack_cache_ttl.is_zero() {
+        if !self.caching_enabled() {
             return;
         }
         let mut cache = self.tenant_cache.write().await;
@@ -104,13 +225,25 @@ impl AppState {
     }
 
     pub async fn clear_cache(&self) {
-        {
+        let evicted: Vec<_> = {
             let mut cache = self.tenant_cache.write().await;
-            cache.clear();
+            cache.drain().map(|(_, entry)| entry.config).collect()
+        };
+        for cfg in &evicted {
+            self.evict_packs(cfg).await;
         }
         self.pack_provider.clear_cache().await;
     }
 
+    /// Like [`AppState::clear_cache`], for one tenant.
+    pub async fn clear_tenant_cache(&self, tenant: &str) {
+        let evicted = self.tenant_cache.write().await.remove(tenant);
+        if let Some(entry) = &evicted {
+            self.evict_packs(&entry.config).await;
+        }
+        self.pack_provider.clear_tenant_cache(tenant).await;
+    }
+
     pub fn cache_stats(&self) -> (u64, u64) {
         (
             self.cache_hits.load(Ordering::Relaxed),
@@ -127,26 +260,35 @@ struct CachedTenant {
 
 pub async fn run(addr: SocketAddr, state: AppState) -> anyhow::Result<()> {
     let listener = TcpListener::bind(addr).await?;
-    axum::serve(listener, router(state))
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .context("server error")
+    axum::serve(
+        listener,
+        router(state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .context("server error")
 }
 
 pub fn router(state: AppState) -> Router {
     let mut router = Router::new()
         .route("/greentic/gui-sdk.js", get(api::serve_sdk))
+        .route("/greentic/theme.css", get(api::serve_theme))
         .route("/api/gui/config", get(api::get_gui_config))
         .route("/api/gui/worker/message", post(api::post_worker_message))
         .route("/api/gui/events", post(api::post_events))
-        .route("/api/gui/cache/clear", post(api::clear_cache))
-        .route("/api/gui/packs/reload", post(reload_packs))
         .route("/api/gui/session", post(api::issue_session))
-        .route("/auth/:provider/start", get(auth::start_auth))
-        .route("/auth/:provider/callback", get(auth::auth_callback))
-        .route("/auth/logout", get(auth::logout))
+        .route("/api/gui/fragments", get(api::get_route_fragments))
+        .route("/api/gui/dev/reload", get(api::dev_reload_events))
+        .route("/auth/{provider}/start", get(auth::start_auth))
+        .route("/auth/{provider}/callback", get(auth::auth_callback))
+        .route("/auth/logout", get(auth::logout).post(auth::logout))
         .route("/tests/sdk-harness", get(serve_sdk_harness))
-        .route("/*path", get(serve_route));
+        .route(
+            "/_packs/{kind}/{pack}/{*path}",
+            get(assets::serve_pack_asset),
+        )
+        .route("/{*path}", get(serve_route))
+        .merge(admin_routes(state.clone()));
 
     if state.config.enable_cors {
         use tower_http::cors::{Any, CorsLayer};
@@ -170,13 +312,33 @@ pub fn router(state: AppState) -> Router {
         router = router.layer(layer);
     }
 
-    router
+    // Path-prefix tenants must be stripped before routing, so the routes sit behind a fallback
+    // that only runs once the prefix middleware has rewritten the URI.
+    Router::new()
+        .fallback_service(router)
+        .layer(axum::middleware::from_fn_with_state(
+            state,
+            domains::strip_tenant_prefix,
+        ))
+}
+
+/// Cache and pack management endpoints, guarded by [`admin::require_admin`].
+fn admin_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/api/gui/cache/clear", post(api::clear_cache))
+        .route("/api/gui/packs/reload", post(reload_packs))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
+            admin::require_admin,
+        ))
 }
 
 #[axum::debug_handler]
 async fn serve_route(
     State(state): State<AppState>,
+    tenant: RequestTenant,
     Path(path): Path<String>,
+    RawQuery(query): RawQuery,
     headers: HeaderMap,
 ) -> impl IntoResponse {
     let path = if path.starts_with('/') {
@@ -184,18 +346,24 @@ async fn serve_route(
     } else {
         format!("/{}", path)
     };
-    let domain = host_from_headers(&headers).unwrap_or_else(|| state.config.default_tenant.clone());
-    let tenant_cfg = match state.load_tenant(&domain).await {
+    let tenant_cfg = match state.load_tenant(&tenant.tenant, &tenant.domain).await {
         Ok(cfg) => cfg,
         Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
     };
 
+    // Served pages carry this span's trace context so browser telemetry and worker calls made
+    // from the page join its trace; a trace started upstream is continued.
     let span = tracing::info_span!(
         "gui_request",
         tenant = %tenant_cfg.tenant_did,
         domain = %tenant_cfg.domain,
-        path = %path
+        path = %path,
+        trace_id = tracing::field::Empty,
+        span_id = tracing::field::Empty
     );
+    let trace = TraceContext::for_span(&span, TraceContext::from_headers(&headers).as_ref());
+    span.record("trace_id", trace.parent.trace_id.as_str());
+    span.record("span_id", trace.parent.span_id.as_str());
     let _enter = span.enter();
 
     let session_token = session_cookie(&headers);
@@ -211,6 +379,7 @@ async fn serve_route(
     let decision = match resolve_route(
         &tenant_cfg,
         &path,
+        query.as_deref(),
         session_token,
         state.session_manager.as_ref(),
     )
@@ -220,17 +389,33 @@ async fn serve_route(
         Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
     };
 
+    let theme = tenant_cfg.theme(tenant.path_prefix.as_deref());
     match decision {
         RouteDecision::Serve(content) => {
-            let content = *content;
+            let mut content = *content;
+            if let Some(selector) = &content.menu_slot {
+                let items = tenant_cfg.menu(content.session.as_ref());
+                if !items.is_empty() {
+                    let menu = render_menu(&items, &path, |href| tenant.url_for(href));
+                    match fill_slot(&content.html, selector, &menu) {
+                        Ok(html) => content.html = html,
+                        Err(err) => warn!(?err, "failed to render navigation menu"),
+                    }
+                }
+            }
             let base_html = content.html.clone();
             let html = match inject_fragments(
                 base_html.clone(),
                 &content.fragments,
-                content.session.as_ref(),
-                &tenant_cfg.tenant_did,
-                &path,
+                FragmentRequest {
+                    session: content.session.as_ref(),
+                    tenant_did: &tenant_cfg.tenant_did,
+                    route: &path,
+                    params: &content.params,
+                    trace: Some(&trace),
+                },
                 state.fragment_renderer.clone(),
+                &state.config.fragment_budget,
             )
             .await
             {
@@ -249,22 +434,42 @@ async fn serve_route(
                     base_html
                 }
             };
-            Html(html).into_response()
+            let mut head = trace.meta_tags();
+            head.push_str(&tenant.base_meta());
+            head.push_str(&route_data_script(&path, &content));
+            if content.spa_entrypoint {
+                head.push_str(&route_manifest_script(&tenant_cfg));
+            }
+            let html = inject_head(&html, &head);
+            Html(apply_theme(html, theme.as_deref())).into_response()
+        }
+        RouteDecision::Redirect(target) => {
+            // Auth-pack login pages may not forward `return_to`; the cookie carries it instead.
+            let requested = match &query {
+                Some(query) => format!("{path}?{query}"),
+                None => path.clone(),
+            };
+            (
+                StatusCode::FOUND,
+                [
+                    (header::LOCATION, tenant.url_for(&target)),
+                    (
+                        header::SET_COOKIE,
+                        auth::make_return_to_cookie(&state.config, &requested),
+                    ),
+                ],
+                "redirecting",
+            )
+                .into_response()
         }
-        RouteDecision::Redirect(target) => (
-            StatusCode::FOUND,
-            [(header::LOCATION, target.as_str())],
-            "redirecting",
-        )
-            .into_response(),
         RouteDecision::NotFound => match path.as_str() {
             "/login" => match fs::read_to_string("assets/login.html").await {
-                Ok(html) => Html(html).into_response(),
+                Ok(html) => Html(apply_theme(html, theme.as_deref())).into_response(),
                 Err(_) => (StatusCode::NOT_FOUND, "not found").into_response(),
             },
-            "/logout" => Redirect::to("/auth/logout").into_response(),
+            "/logout" => Redirect::to(&tenant.url_for("/auth/logout")).into_response(),
             "/unauthorized" => match fs::read_to_string("assets/unauthorized.html").await {
-                Ok(html) => Html(html).into_response(),
+                Ok(html) => Html(apply_theme(html, theme.as_deref())).into_response(),
                 Err(_) => (StatusCode::UNAUTHORIZED, "unauthorized").into_response(),
             },
             _ => (StatusCode::NOT_FOUND, "not found").into_response(),
@@ -272,6 +477,18 @@ async fn serve_route(
     }
 }
 
+/// Skin a served page; on failure the page is served unskinned.
+fn apply_theme(html: String, theme: Option<&Theme>) -> String {
+    match theme.map(|theme| theme.apply(&html)) {
+        Some(Ok(skinned)) => skinned,
+        Some(Err(err)) => {
+            warn!(?err, "failed to apply skin");
+            html
+        }
+        None => html,
+    }
+}
+
 async fn serve_sdk_harness() -> impl IntoResponse {
     match fs::read_to_string("assets/sdk-harness.html").await {
         Ok(html) => Html(html).into_response(),
@@ -279,17 +496,31 @@ async fn serve_sdk_harness() -> impl IntoResponse {
     }
 }
 
-/// Force reload of tenant packs by clearing cache and reloading default tenant.
+/// Force reload of tenant packs by clearing cache and reloading the requested tenant
+/// (the request's own tenant by default). Only the service token clears every tenant's cache.
 async fn reload_packs(
     State(state): State<AppState>,
+    request_tenant: RequestTenant,
+    Extension(principal): Extension<AdminPrincipal>,
     Json(body): Json<HashMap<String, String>>,
 ) -> impl IntoResponse {
-    state.clear_cache().await;
     let tenant = body
         .get("tenant")
         .map(|s| s.as_str())
-        .unwrap_or(&state.config.default_tenant);
-    let result = state.load_tenant(tenant).await;
+        .unwrap_or(&request_tenant.tenant);
+    if !principal.may_manage(tenant) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({ "error": "forbidden", "reason": "tenant_mismatch" })),
+        )
+            .into_response();
+    }
+    if principal.tenant.is_some() {
+        state.clear_tenant_cache(tenant).await;
+    } else {
+        state.clear_cache().await;
+    }
+    let result = state.load_tenant(tenant, tenant).await;
     let (status, err) = match result {
         Ok(_) => (StatusCode::NO_CONTENT, None),
         Err(err) => {
@@ -309,16 +540,11 @@ async fn reload_packs(
             "status": status.as_u16(),
             "error": err,
         }),
+        ctx: make_telemetry_ctx(tenant, None, Some("gui")),
+        ..Default::default()
     };
     state.telemetry.record_event(event).await;
-    status
-}
-
-pub fn host_from_headers(headers: &HeaderMap) -> Option<String> {
-    headers
-        .get(header::HOST)
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.to_string())
+    status.into_response()
 }
 
 fn session_cookie(headers: &HeaderMap) -> Option<String> {
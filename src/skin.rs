@@ -0,0 +1,358 @@
+//! Skin packs: design tokens rendered as CSS custom properties, web fonts, logo and favicon
+//! overrides and extra stylesheets, applied to every served page.
+
+use crate::packs::{DesignToken, SkinManifest};
+use crate::routing::{escape_html, inject_head};
+use kuchiki::traits::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Element id of the generated token stylesheet.
+pub const SKIN_STYLE_ID: &str = "greentic-skin";
+/// Attribute marking `<img>` elements whose `src` is replaced by the skin logo.
+pub const LOGO_ATTR: &str = "data-greentic-logo";
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SkinError {
+    #[error("invalid token name `{0}`")]
+    TokenName(String),
+    #[error("invalid value for token `{0}`")]
+    TokenValue(String),
+    #[error("invalid font face for `{0}`")]
+    Font(String),
+    #[error("invalid asset path `{0}`")]
+    AssetPath(String),
+    #[error("query selector {0} failed")]
+    Selector(String),
+}
+
+/// Resolved skin for one tenant, with asset paths turned into URLs.
+#[derive(Debug, Clone, Serialize)]
+pub struct Theme {
+    /// Custom properties keyed by their full name, e.g. `--color-primary`.
+    pub variables: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub logo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub favicon: Option<String>,
+    pub stylesheets: Vec<String>,
+    #[serde(skip)]
+    font_faces: Vec<String>,
+    #[serde(skip)]
+    asset_base: String,
+}
+
+impl Theme {
+    /// Resolve `manifest` against `asset_base`, the public URL of the skin's asset root.
+    pub fn new(manifest: &SkinManifest, asset_base: &str) -> Result<Self, SkinError> {
+        let url = |path: &str| asset_path(path).map(|path| format!("{asset_base}{path}"));
+
+        let mut variables = BTreeMap::new();
+        for (name, token) in &manifest.tokens {
+            flatten_token(name.trim_start_matches("--"), token, &mut variables)?;
+        }
+
+        let mut font_faces = Vec::with_capacity(manifest.fonts.len());
+        for font in &manifest.fonts {
+            if font.family.is_empty() || !is_safe_css(&font.family) || font.family.contains('"') {
+                return Err(SkinError::Font(font.family.clone()));
+            }
+            let mut face = format!(
+                "@font-face{{font-family:\"{}\";src:url(\"{}\"){};font-display:swap;",
+                font.family,
+                url(&font.src)?,
+                font_format(&font.src)
+                    .map(|format| format!(" format(\"{format}\")"))
+                    .unwrap_or_default()
+            );
+            for (property, value) in [("font-weight", &font.weight), ("font-style", &font.style)] {
+                if let Some(value) = value {
+                    if !is_safe_css(value) {
+                        return Err(SkinError::Font(font.family.clone()));
+                    }
+                    face.push_str(&format!("{property}:{value};"));
+                }
+            }
+            face.push('}');
+            font_faces.push(face);
+        }
+
+        let logo = manifest.logo.as_deref().map(url).transpose()?;
+        if let Some(logo) = &logo {
+            variables.insert("--skin-logo".to_string(), format!("url(\"{logo}\")"));
+        }
+        Ok(Self {
+            variables,
+            logo,
+            favicon: manifest.favicon.as_deref().map(url).transpose()?,
+            stylesheets: manifest
+                .stylesheets
+                .iter()
+                .map(|path| url(path))
+                .collect::<Result<_, _>>()?,
+            font_faces,
+            asset_base: asset_base.to_string(),
+        })
+    }
+
+    /// The same theme with its asset URLs under `prefix`, e.g. a tenant path prefix.
+    pub fn with_url_prefix(&self, prefix: &str) -> Self {
+        let from = format!("url(\"{}", self.asset_base);
+        let to = format!("url(\"{prefix}{}", self.asset_base);
+        let url = |url: &String| format!("{prefix}{url}");
+        Self {
+            variables: self
+                .variables
+                .iter()
+                .map(|(name, value)| (name.clone(), value.replace(&from, &to)))
+                .collect(),
+            logo: self.logo.as_ref().map(url),
+            favicon: self.favicon.as_ref().map(url),
+            stylesheets: self.stylesheets.iter().map(url).collect(),
+            font_faces: self
+                .font_faces
+                .iter()
+                .map(|face| face.replace(&from, &to))
+                .collect(),
+            asset_base: format!("{prefix}{}", self.asset_base),
+        }
+    }
+
+    /// Generated stylesheet: `@font-face` rules followed by a `:root` block of the tokens.
+    pub fn stylesheet(&self) -> String {
+        let mut css = self.font_faces.concat();
+        if !self.variables.is_empty() {
+            css.push_str(":root{");
+            for (name, value) in &self.variables {
+                css.push_str(&format!("{name}:{value};"));
+            }
+            css.push('}');
+        }
+        css
+    }
+
+    /// Head markup: the token stylesheet, favicon and skin stylesheet links.
+    pub fn head_html(&self) -> String {
+        let mut head = format!(
+            "<style id=\"{SKIN_STYLE_ID}\">{}</style>",
+            self.stylesheet()
+        );
+        if let Some(favicon) = &self.favicon {
+            head.push_str(&format!(
+                "<link rel=\"icon\" href=\"{}\">",
+                escape_html(favicon)
+            ));
+        }
+        for href in &self.stylesheets {
+            head.push_str(&format!(
+                "<link rel=\"stylesheet\" href=\"{}\">",
+                escape_html(href)
+            ));
+        }
+        head
+    }
+
+    /// Theme a page: drop its own favicons when the skin has one, point `[data-greentic-logo]`
+    /// images at the skin logo and append [`Theme::head_html`] to the head, after the page's own
+    /// stylesheets so the skin wins.
+    pub fn apply(&self, html: &str) -> Result<String, SkinError> {
+        let html = if self.favicon.is_some() || self.logo.is_some() {
+            let document = kuchiki::parse_html().one(html);
+            if self.favicon.is_some() {
+                let icons: Vec<_> = select(&document, "link[rel~=\"icon\"]")?.collect();
+                for icon in icons {
+                    icon.as_node().detach();
+                }
+            }
+            if let Some(logo) = &self.logo {
+                for img in select(&document, &format!("img[{LOGO_ATTR}]"))? {
+                    img.attributes.borrow_mut().insert("src", logo.clone());
+                }
+            }
+            document.to_string()
+        } else {
+            html.to_string()
+        };
+        Ok(inject_head(&html, &self.head_html()))
+    }
+}
+
+fn select(
+    document: &kuchiki::NodeRef,
+    selector: &str,
+) -> Result<kuchiki::iter::Select<kuchiki::iter::Elements<kuchiki::iter::Descendants>>, SkinError> {
+    document
+        .select(selector)
+        .map_err(|()| SkinError::Selector(selector.to_string()))
+}
+
+fn flatten_token(
+    name: &str,
+    token: &DesignToken,
+    out: &mut BTreeMap<String, String>,
+) -> Result<(), SkinError> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(SkinError::TokenName(name.to_string()));
+    }
+    match token {
+        DesignToken::Value(value) => {
+            if value.trim().is_empty() || !is_safe_css(value) {
+                return Err(SkinError::TokenValue(name.to_string()));
+            }
+            out.insert(format!("--{name}"), value.trim().to_string());
+        }
+        DesignToken::Number(value) => {
+            out.insert(format!("--{name}"), value.to_string());
+        }
+        DesignToken::Group(group) => {
+            for (child, token) in group {
+                flatten_token(&format!("{name}-{child}"), token, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Values must not be able to end the declaration, the rule or the `<style>` element.
+fn is_safe_css(value: &str) -> bool {
+    !value
+        .chars()
+        .any(|c| matches!(c, ';' | '{' | '}' | '<' | '>' | '\\') || c.is_control())
+}
+
+/// Skin file paths stay inside the pack assets and need no quoting in CSS `url()` or HTML.
+fn asset_path(path: &str) -> Result<&str, SkinError> {
+    let trimmed = path.trim_start_matches('/');
+    let valid = !trimmed.is_empty()
+        && !trimmed.contains("//")
+        && trimmed
+            .split('/')
+            .all(|segment| segment != ".." && segment != ".")
+        && !trimmed.chars().any(|c| {
+            c.is_whitespace()
+                || c.is_control()
+                || matches!(c, '"' | '\'' | '<' | '>' | '\\' | '(' | ')' | ':')
+        });
+    if valid {
+        Ok(trimmed)
+    } else {
+        Err(SkinError::AssetPath(path.to_string()))
+    }
+}
+
+fn font_format(src: &str) -> Option<&'static str> {
+    let ext = src.rsplit_once('.')?.1.to_ascii_lowercase();
+    match ext.as_str() {
+        "woff2" => Some("woff2"),
+        "woff" => Some("woff"),
+        "ttf" => Some("truetype"),
+        "otf" => Some("opentype"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn manifest(value: serde_json::Value) -> SkinManifest {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn renders_tokens_fonts_and_links() {
+        let skin = manifest(json!({
+            "kind": "gui-skin",
+            "tokens": {
+                "color": { "primary": "#0a7", "surface": "#fff" },
+                "--radius": 8,
+                "font-body": "\"Inter\", sans-serif",
+            },
+            "fonts": [{ "family": "Inter", "src": "fonts/inter.woff2", "weight": "400" }],
+            "logo": "logo.svg",
+            "favicon": "/favicon.png",
+            "stylesheets": ["brand.css"],
+        }));
+        let theme = Theme::new(&skin, "/_packs/skin/brand/").unwrap();
+        assert_eq!(
+            theme.stylesheet(),
+            "@font-face{font-family:\"Inter\";src:url(\"/_packs/skin/brand/fonts/inter.woff2\") \
+             format(\"woff2\");font-display:swap;font-weight:400;}\
+             :root{--color-primary:#0a7;--color-surface:#fff;--font-body:\"Inter\", sans-serif;\
+             --radius:8;--skin-logo:url(\"/_packs/skin/brand/logo.svg\");}"
+        );
+        assert_eq!(
+            theme.favicon.as_deref(),
+            Some("/_packs/skin/brand/favicon.png")
+        );
+        assert_eq!(theme.stylesheets, ["/_packs/skin/brand/brand.css"]);
+
+        let prefixed = theme.with_url_prefix("/t/acme");
+        assert_eq!(
+            prefixed.stylesheet(),
+            theme
+                .stylesheet()
+                .replace("url(\"/_packs", "url(\"/t/acme/_packs")
+        );
+        assert_eq!(
+            prefixed.logo.as_deref(),
+            Some("/t/acme/_packs/skin/brand/logo.svg")
+        );
+        assert_eq!(
+            prefixed.favicon.as_deref(),
+            Some("/t/acme/_packs/skin/brand/favicon.png")
+        );
+        assert_eq!(
+            prefixed.stylesheets,
+            ["/t/acme/_packs/skin/brand/brand.css"]
+        );
+    }
+
+    #[test]
+    fn rejects_values_that_escape_the_stylesheet() {
+        let theme = |value: serde_json::Value| Theme::new(&manifest(value), "/");
+        assert_eq!(
+            theme(json!({ "tokens": { "color": "red;}</style><script>" } })).unwrap_err(),
+            SkinError::TokenValue("color".into())
+        );
+        assert_eq!(
+            theme(json!({ "tokens": { "a b": "red" } })).unwrap_err(),
+            SkinError::TokenName("a b".into())
+        );
+        assert_eq!(
+            theme(json!({ "stylesheets": ["../layout/app.css"] })).unwrap_err(),
+            SkinError::AssetPath("../layout/app.css".into())
+        );
+        assert_eq!(
+            theme(json!({ "logo": "https://evil.example/logo.svg" })).unwrap_err(),
+            SkinError::AssetPath("https://evil.example/logo.svg".into())
+        );
+    }
+
+    #[test]
+    fn applies_favicon_and_logo_overrides() {
+        let skin = manifest(json!({ "logo": "logo.svg", "favicon": "icon.png" }));
+        let theme = Theme::new(&skin, "/s/").unwrap();
+        let html = theme
+            .apply(
+                "<html><head><link href=\"/old.ico\" rel=\"shortcut icon\"></head>\
+                 <body><img data-greentic-logo src=\"/default.svg\"></body></html>",
+            )
+            .unwrap();
+        assert!(!html.contains("old.ico"), "{html}");
+        assert!(
+            html.contains("<img data-greentic-logo=\"\" src=\"/s/logo.svg\">"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<link rel=\"icon\" href=\"/s/icon.png\"></head>"),
+            "{html}"
+        );
+    }
+}
@@ -0,0 +1,422 @@
+//! Telemetry packs: the event allowlist and metadata redaction enforced by `/api/gui/events`,
+//! and the sampling and auto-capture policy handed to the SDK through `/api/gui/config`.
+//! Also the per-session rate limit and client clock correction applied to ingested events.
+
+use crate::packs::{AutoCapture, RedactAction, RedactionRule, TelemetryManifest};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use thiserror::Error;
+
+const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Error)]
+pub enum PolicyError {
+    #[error("sample rate for `{0}` must be between 0 and 1, got {1}")]
+    SampleRate(String, f64),
+    #[error("redaction rule needs a `field` or a `pattern`")]
+    EmptyRule,
+    #[error("invalid redaction field `{0}`")]
+    Field(String),
+    #[error("invalid redaction pattern `{0}`: {1}")]
+    Pattern(String, regex::Error),
+}
+
+/// Server side of a tenant's telemetry manifest. The default allows every event unchanged.
+#[derive(Debug, Default)]
+pub struct TelemetryPolicy {
+    allowed_events: Vec<String>,
+    rules: Vec<Redaction>,
+}
+
+#[derive(Debug)]
+struct Redaction {
+    field: Option<Vec<String>>,
+    pattern: Option<Regex>,
+    action: RedactAction,
+}
+
+impl TelemetryPolicy {
+    pub fn new(manifest: &TelemetryManifest) -> Result<Self, PolicyError> {
+        let rates = std::iter::once(("*", manifest.sample_rate))
+            .chain(manifest.sampling.iter().map(|(k, v)| (k.as_str(), *v)));
+        for (event_type, rate) in rates {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(PolicyError::SampleRate(event_type.to_string(), rate));
+            }
+        }
+        let rules = manifest
+            .redact
+            .iter()
+            .map(Redaction::compile)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            allowed_events: manifest.allowed_events.clone(),
+            rules,
+        })
+    }
+
+    /// Whether `/api/gui/events` accepts `event_type`.
+    pub fn allows(&self, event_type: &str) -> bool {
+        self.allowed_events.is_empty()
+            || self
+                .allowed_events
+                .iter()
+                .any(|allowed| match allowed.strip_suffix('*') {
+                    Some(prefix) => event_type.starts_with(prefix),
+                    None => allowed == event_type,
+                })
+    }
+
+    /// Apply every redaction rule to `metadata`, in manifest order.
+    pub fn redact(&self, metadata: &mut Value) {
+        for rule in &self.rules {
+            match &rule.field {
+                Some(path) => redact_path(metadata, path, rule),
+                None => rule.redact_value(metadata),
+            }
+        }
+    }
+}
+
+impl Redaction {
+    fn compile(rule: &RedactionRule) -> Result<Self, PolicyError> {
+        let field = match &rule.field {
+            Some(field) => {
+                let path: Vec<String> = field.split('.').map(str::to_string).collect();
+                if path.iter().any(String::is_empty) {
+                    return Err(PolicyError::Field(field.clone()));
+                }
+                Some(path)
+            }
+            None => None,
+        };
+        let pattern = match &rule.pattern {
+            Some(pattern) => {
+                Some(Regex::new(pattern).map_err(|e| PolicyError::Pattern(pattern.clone(), e))?)
+            }
+            None => None,
+        };
+        if field.is_none() && pattern.is_none() {
+            return Err(PolicyError::EmptyRule);
+        }
+        Ok(Self {
+            field,
+            pattern,
+            action: rule.action,
+        })
+    }
+
+    /// Redact pattern matches inside every string under `value`, or all of `value` when the
+    /// rule has no pattern.
+    fn redact_value(&self, value: &mut Value) {
+        let Some(pattern) = &self.pattern else {
+            *value = match self.action {
+                RedactAction::Hash => Value::String(digest(&value_text(value))),
+                _ => Value::String(REDACTED.to_string()),
+            };
+            return;
+        };
+        match value {
+            Value::String(text) => {
+                let replaced =
+                    pattern.replace_all(text, |caps: &regex::Captures<'_>| match self.action {
+                        RedactAction::Hash => digest(&caps[0]),
+                        _ => REDACTED.to_string(),
+                    });
+                *text = replaced.into_owned();
+            }
+            Value::Array(items) => items.iter_mut().for_each(|item| self.redact_value(item)),
+            Value::Object(map) => map.values_mut().for_each(|item| self.redact_value(item)),
+            _ => {}
+        }
+    }
+}
+
+fn redact_path(value: &mut Value, path: &[String], rule: &Redaction) {
+    let Some((head, rest)) = path.split_first() else {
+        rule.redact_value(value);
+        return;
+    };
+    match value {
+        Value::Object(map) => {
+            let keys: Vec<String> = if head == "*" {
+                map.keys().cloned().collect()
+            } else {
+                map.contains_key(head)
+                    .then(|| head.clone())
+                    .into_iter()
+                    .collect()
+            };
+            for key in keys {
+                if rest.is_empty() && rule.action == RedactAction::Remove && rule.pattern.is_none()
+                {
+                    map.remove(&key);
+                } else if let Some(child) = map.get_mut(&key) {
+                    redact_path(child, rest, rule);
+                }
+            }
+        }
+        Value::Array(items) => {
+            let indices: Vec<usize> = if head == "*" {
+                (0..items.len()).collect()
+            } else {
+                head.parse()
+                    .ok()
+                    .filter(|i| *i < items.len())
+                    .into_iter()
+                    .collect()
+            };
+            for idx in indices {
+                if rest.is_empty() && rule.action == RedactAction::Remove && rule.pattern.is_none()
+                {
+                    items[idx] = Value::Null;
+                } else {
+                    redact_path(&mut items[idx], rest, rule);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Short SHA-256 of `text`, used where telemetry needs a stable but opaque value.
+pub fn digest(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    format!("sha256:{}", hex::encode(&digest[..8]))
+}
+
+/// Client half of the manifest, returned as `telemetry` by `/api/gui/config`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientPolicy {
+    pub sample_rate: f64,
+    pub sampling: BTreeMap<String, f64>,
+    pub allowed_events: Vec<String>,
+    pub auto_capture: AutoCapture,
+}
+
+impl From<&TelemetryManifest> for ClientPolicy {
+    fn from(manifest: &TelemetryManifest) -> Self {
+        Self {
+            sample_rate: manifest.sample_rate,
+            sampling: manifest.sampling.clone(),
+            allowed_events: manifest.allowed_events.clone(),
+            auto_capture: manifest.auto_capture,
+        }
+    }
+}
+
+const RATE_WINDOW_MS: i64 = 60_000;
+
+/// Keys tracked per window; keys past it share one overflow budget.
+const MAX_RATE_KEYS: usize = 10_000;
+const OVERFLOW_KEY: &str = "overflow";
+
+/// Fixed one-minute windows of accepted events, keyed by session.
+#[derive(Debug)]
+pub struct EventRateLimiter {
+    limit: u32,
+    max_keys: usize,
+    windows: Mutex<RateWindow>,
+}
+
+/// Events used per key in the window starting at `start`.
+#[derive(Debug, Default)]
+struct RateWindow {
+    start: i64,
+    used: HashMap<String, u32>,
+}
+
+impl EventRateLimiter {
+    /// `limit` events per key and minute; 0 disables the limit.
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            max_keys: MAX_RATE_KEYS,
+            windows: Mutex::new(RateWindow::default()),
+        }
+    }
+
+    /// Take up to `wanted` events from `key`'s current window and return how many were granted.
+    pub fn take(&self, key: &str, wanted: usize, now_ms: i64) -> usize {
+        if self.limit == 0 {
+            return wanted;
+        }
+        let window_start = now_ms - now_ms.rem_euclid(RATE_WINDOW_MS);
+        let mut window = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if window.start != window_start {
+            window.start = window_start;
+            window.used.clear();
+        }
+        let key = if window.used.len() >= self.max_keys && !window.used.contains_key(key) {
+            OVERFLOW_KEY
+        } else {
+            key
+        };
+        let used = window.used.entry(key.to_string()).or_insert(0);
+        let granted = wanted.min(self.limit.saturating_sub(*used) as usize);
+        *used += granted as u32;
+        granted
+    }
+
+    /// Seconds until the current window ends, for `Retry-After`.
+    pub fn retry_after_secs(now_ms: i64) -> i64 {
+        (RATE_WINDOW_MS - now_ms.rem_euclid(RATE_WINDOW_MS) + 999) / 1000
+    }
+}
+
+/// Oldest an event may claim to be once corrected; older timestamps are clamped to it.
+const MAX_EVENT_AGE_MS: i64 = 60 * 60 * 1000;
+
+/// Map a client `timestamp` onto the server clock. `client_sent_ms` is the client's clock when
+/// it sent the request, so the difference to `server_now_ms` is its skew; without it the event
+/// is assumed to be sent as it happened. Results never lie in the future nor more than
+/// an hour in the past.
+pub fn correct_timestamp(
+    client_ts_ms: Option<i64>,
+    client_sent_ms: Option<i64>,
+    server_now_ms: i64,
+) -> i64 {
+    let Some(client_ts) = client_ts_ms else {
+        return server_now_ms;
+    };
+    let sent = client_sent_ms.unwrap_or(client_ts);
+    client_ts
+        .saturating_add(server_now_ms.saturating_sub(sent))
+        .clamp(
+            server_now_ms.saturating_sub(MAX_EVENT_AGE_MS),
+            server_now_ms,
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn policy(manifest: Value) -> Result<TelemetryPolicy, PolicyError> {
+        TelemetryPolicy::new(&serde_json::from_value(manifest).unwrap())
+    }
+
+    #[test]
+    fn allowlist_matches_exact_types_and_prefixes() {
+        let policy = policy(json!({ "allowed_events": ["page_view", "billing.*"] })).unwrap();
+        assert!(policy.allows("page_view"));
+        assert!(policy.allows("billing.invoice_opened"));
+        assert!(!policy.allows("click"));
+        assert!(TelemetryPolicy::default().allows("anything"));
+    }
+
+    #[test]
+    fn redacts_fields_and_patterns() {
+        let policy = policy(json!({
+            "redact": [
+                { "field": "user.email", "action": "remove" },
+                { "field": "items.*.card" },
+                { "field": "account", "action": "hash" },
+                { "pattern": "[\\w.]+@[\\w.]+" },
+            ],
+        }))
+        .unwrap();
+        let mut metadata = json!({
+            "user": { "email": "a@b.c", "name": "Ann" },
+            "items": [{ "card": "4111", "sku": "x" }],
+            "account": 42,
+            "note": "mail bob@example.com today",
+        });
+        policy.redact(&mut metadata);
+        assert_eq!(
+            metadata,
+            json!({
+                "user": { "name": "Ann" },
+                "items": [{ "card": "[redacted]", "sku": "x" }],
+                "account": digest("42"),
+                "note": "mail [redacted] today",
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_manifests() {
+        assert!(matches!(
+            policy(json!({ "sample_rate": 1.5 })),
+            Err(PolicyError::SampleRate(_, _))
+        ));
+        assert!(matches!(
+            policy(json!({ "redact": [{ "action": "mask" }] })),
+            Err(PolicyError::EmptyRule)
+        ));
+        assert!(matches!(
+            policy(json!({ "redact": [{ "pattern": "(" }] })),
+            Err(PolicyError::Pattern(_, _))
+        ));
+    }
+
+    #[test]
+    fn rate_limit_applies_per_key_and_window() {
+        let limiter = EventRateLimiter::new(5);
+        assert_eq!(limiter.take("a", 3, 1_000), 3);
+        assert_eq!(limiter.take("a", 3, 2_000), 2);
+        assert_eq!(limiter.take("a", 1, 3_000), 0);
+        assert_eq!(limiter.take("b", 1, 3_000), 1);
+        assert_eq!(limiter.take("a", 4, 61_000), 4);
+        assert_eq!(EventRateLimiter::retry_after_secs(61_000), 59);
+        assert_eq!(EventRateLimiter::new(0).take("a", 1_000, 0), 1_000);
+    }
+
+    #[test]
+    fn rate_limit_keys_beyond_the_cap_share_one_budget() {
+        let limiter = EventRateLimiter {
+            max_keys: 2,
+            ..EventRateLimiter::new(5)
+        };
+        assert_eq!(limiter.take("a", 1, 1_000), 1);
+        assert_eq!(limiter.take("b", 1, 1_000), 1);
+        assert_eq!(limiter.take("c", 3, 1_000), 3);
+        assert_eq!(limiter.take("d", 3, 1_000), 2);
+        assert_eq!(limiter.take("a", 4, 1_000), 4);
+        assert_eq!(limiter.windows.lock().unwrap().used.len(), 3);
+        assert_eq!(limiter.take("d", 3, 61_000), 3);
+    }
+
+    #[test]
+    fn corrects_client_clock_skew() {
+        // Client clock runs 10s behind; the event happened 2s before the batch was sent.
+        assert_eq!(
+            correct_timestamp(Some(88_000), Some(90_000), 100_000),
+            98_000
+        );
+        assert_eq!(correct_timestamp(Some(95_000), None, 100_000), 100_000);
+        assert_eq!(
+            correct_timestamp(Some(99_000), Some(90_000), 100_000),
+            100_000
+        );
+        assert_eq!(correct_timestamp(None, Some(90_000), 100_000), 100_000);
+    }
+
+    #[test]
+    fn clamps_timestamps_older_than_the_max_age() {
+        let now = 10 * MAX_EVENT_AGE_MS;
+        let oldest = now - MAX_EVENT_AGE_MS;
+        assert_eq!(correct_timestamp(Some(0), Some(now), now), oldest);
+        assert_eq!(correct_timestamp(Some(i64::MIN), None, now), oldest);
+        assert_eq!(
+            correct_timestamp(Some(i64::MIN), Some(i64::MAX), now),
+            oldest
+        );
+        assert_eq!(
+            correct_timestamp(Some(oldest + 1), Some(now), now),
+            oldest + 1
+        );
+    }
+}
@@ -1,21 +1,73 @@
+use crate::assets::pack_asset_url;
+use crate::fragment_host::PackScope;
+use crate::fragments::FragmentWorld;
+use crate::integration::SessionInfo;
 use crate::packs::{
-    AuthManifest, FeatureManifest, GuiPack, LayoutManifest, PackProvider, normalize_route,
+    AuthManifest, FeatureManifest, GuiPack, LayoutManifest, PackProvider, SkinManifest,
+    TelemetryManifest, normalize_route,
 };
+use crate::route_pattern::{RouteMatch, RouteParams, RoutePattern};
+use crate::skin::{SkinError, Theme};
+use crate::telemetry::{ClientPolicy, PolicyError, TelemetryPolicy};
+use anyhow::Context;
 use greentic_types::SecretRequirement;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashSet;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
+use tracing::warn;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "TenantGuiParts")]
 pub struct TenantGuiConfig {
     pub tenant_did: String,
     pub domain: String,
     pub layout: LayoutPack,
     pub auth: Option<AuthPack>,
-    pub skin: Option<PackLocation>,
-    pub telemetry: Option<PackLocation>,
+    pub skin: Option<SkinPack>,
+    pub telemetry: Option<TelemetryPack>,
     pub features: Vec<FeaturePack>,
     pub secret_requirements: Vec<SecretRequirement>,
+    /// Duplicate or overlapping routes found while loading the packs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route_conflicts: Vec<RouteConflict>,
+    /// Auth and feature routes, parsed at load.
+    #[serde(skip)]
+    pub routes: RouteTable,
+}
+
+#[derive(Deserialize)]
+struct TenantGuiParts {
+    tenant_did: String,
+    domain: String,
+    layout: LayoutPack,
+    auth: Option<AuthPack>,
+    skin: Option<SkinPack>,
+    telemetry: Option<TelemetryPack>,
+    features: Vec<FeaturePack>,
+    secret_requirements: Vec<SecretRequirement>,
+    #[serde(default)]
+    route_conflicts: Vec<RouteConflict>,
+}
+
+impl TryFrom<TenantGuiParts> for TenantGuiConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(parts: TenantGuiParts) -> anyhow::Result<Self> {
+        let routes = RouteTable::new(parts.auth.as_ref(), &parts.features)?;
+        Ok(Self {
+            tenant_did: parts.tenant_did,
+            domain: parts.domain,
+            layout: parts.layout,
+            auth: parts.auth,
+            skin: parts.skin,
+            telemetry: parts.telemetry,
+            features: parts.features,
+            secret_requirements: parts.secret_requirements,
+            route_conflicts: parts.route_conflicts,
+            routes,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -42,6 +94,76 @@ pub struct FeaturePack {
     pub secret_requirements: Vec<SecretRequirement>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SkinPackParts")]
+pub struct SkinPack {
+    pub manifest: SkinManifest,
+    pub location: PackLocation,
+    /// `manifest` resolved against the pack's asset URL, built once at load.
+    #[serde(skip)]
+    pub theme: Theme,
+}
+
+impl SkinPack {
+    pub fn new(manifest: SkinManifest, location: PackLocation) -> Result<Self, SkinError> {
+        let theme = Theme::new(&manifest, &pack_asset_url("skin", &location))?;
+        Ok(Self {
+            manifest,
+            location,
+            theme,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct SkinPackParts {
+    manifest: SkinManifest,
+    location: PackLocation,
+}
+
+impl TryFrom<SkinPackParts> for SkinPack {
+    type Error = SkinError;
+
+    fn try_from(parts: SkinPackParts) -> Result<Self, Self::Error> {
+        Self::new(parts.manifest, parts.location)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "TelemetryPackParts")]
+pub struct TelemetryPack {
+    pub manifest: TelemetryManifest,
+    pub location: PackLocation,
+    /// Server side of `manifest`, built once at load.
+    #[serde(skip)]
+    pub policy: Arc<TelemetryPolicy>,
+}
+
+impl TelemetryPack {
+    pub fn new(manifest: TelemetryManifest, location: PackLocation) -> Result<Self, PolicyError> {
+        let policy = Arc::new(TelemetryPolicy::new(&manifest)?);
+        Ok(Self {
+            manifest,
+            location,
+            policy,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct TelemetryPackParts {
+    manifest: TelemetryManifest,
+    location: PackLocation,
+}
+
+impl TryFrom<TelemetryPackParts> for TelemetryPack {
+    type Error = PolicyError;
+
+    fn try_from(parts: TelemetryPackParts) -> Result<Self, Self::Error> {
+        Self::new(parts.manifest, parts.location)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PackLocation {
     pub root: std::path::PathBuf,
@@ -52,6 +174,19 @@ pub struct PackLocation {
     pub pack_hint: Option<String>,
 }
 
+impl PackLocation {
+    /// Stable pack name used in asset URLs (the pack root's file name, minus `.gtpack`).
+    pub fn pack_name(&self) -> String {
+        let name = if crate::vfs::is_gtpack(&self.root) {
+            self.root.file_stem()
+        } else {
+            self.root.file_name()
+        };
+        name.map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
 impl TenantGuiConfig {
     pub async fn load(
         tenant: &str,
@@ -99,31 +234,39 @@ impl TenantGuiConfig {
 
         let skin = match pack_provider.load_skin(tenant).await? {
             Some(GuiPack::Skin {
+                manifest,
                 root,
                 pack_hint,
                 secret_requirements,
-                ..
-            }) => Some(PackLocation {
-                assets: root.join("gui").join("assets"),
-                root,
-                secret_requirements,
-                pack_hint,
-            }),
+            }) => {
+                let context = format!("invalid skin manifest in {root:?}");
+                let location = PackLocation {
+                    assets: root.join("gui").join("assets"),
+                    root,
+                    secret_requirements,
+                    pack_hint,
+                };
+                Some(SkinPack::new(manifest, location).context(context)?)
+            }
             _ => None,
         };
 
         let telemetry = match pack_provider.load_telemetry(tenant).await? {
             Some(GuiPack::Telemetry {
+                manifest,
                 root,
                 pack_hint,
                 secret_requirements,
-                ..
-            }) => Some(PackLocation {
-                assets: root.join("gui").join("assets"),
-                root,
-                secret_requirements,
-                pack_hint,
-            }),
+            }) => {
+                let context = format!("invalid telemetry manifest in {root:?}");
+                let location = PackLocation {
+                    assets: root.join("gui").join("assets"),
+                    root,
+                    secret_requirements,
+                    pack_hint,
+                };
+                Some(TelemetryPack::new(manifest, location).context(context)?)
+            }
             _ => None,
         };
 
@@ -151,16 +294,31 @@ impl TenantGuiConfig {
                 _ => None,
             })
             .collect::<Vec<_>>();
+        for feature in &feature_packs {
+            for binding in &feature.manifest.fragments {
+                FragmentWorld::parse(&binding.component_world).with_context(|| {
+                    format!(
+                        "invalid fragment {} in {:?}",
+                        binding.id, feature.location.root
+                    )
+                })?;
+            }
+        }
+        let routes = RouteTable::new(auth.as_ref(), &feature_packs)?;
+        let route_conflicts = routes.conflicts();
+        for conflict in &route_conflicts {
+            warn!(%tenant, kind = ?conflict.kind, "{conflict}");
+        }
 
         let mut secret_requirements = layout_pack.secret_requirements.clone();
         if let Some(auth) = &auth {
             secret_requirements.extend(auth.secret_requirements.clone());
         }
         if let Some(skin) = &skin {
-            secret_requirements.extend(skin.secret_requirements.clone());
+            secret_requirements.extend(skin.location.secret_requirements.clone());
         }
         if let Some(telemetry) = &telemetry {
-            secret_requirements.extend(telemetry.secret_requirements.clone());
+            secret_requirements.extend(telemetry.location.secret_requirements.clone());
         }
         for feature in &feature_packs {
             secret_requirements.extend(feature.secret_requirements.clone());
@@ -176,48 +334,69 @@ impl TenantGuiConfig {
             telemetry,
             features: feature_packs,
             secret_requirements,
+            route_conflicts,
+            routes,
         })
     }
 
-    pub fn resolve_route(&self, path: &str) -> Option<ResolvedRoute> {
-        let path = normalize_route(path);
-        for feature in &self.features {
-            for route in &feature.manifest.routes {
-                if path_matches(&path, &route.path) {
-                    let fragments = feature
-                        .manifest
-                        .fragments
-                        .iter()
-                        .cloned()
-                        .map(|binding| FragmentTarget {
-                            binding,
-                            assets_root: feature.location.assets.clone(),
-                        })
-                        .collect();
-                    return Some(ResolvedRoute {
-                        source: RouteSource::Feature(feature.clone()),
-                        html_path: feature.location.assets.join(&route.html),
-                        authenticated: route.authenticated,
-                        fragments,
-                    });
-                }
+    /// Look up a mounted pack by URL kind (`layout`, `auth`, `skin`, `telemetry`, `feature`) and name.
+    pub fn pack_location(&self, kind: &str, pack: &str) -> Option<&PackLocation> {
+        let location = match kind {
+            "layout" => Some(&self.layout.location),
+            "auth" => self.auth.as_ref().map(|a| &a.location),
+            "skin" => self.skin.as_ref().map(|s| &s.location),
+            "telemetry" => self.telemetry.as_ref().map(|t| &t.location),
+            "feature" => {
+                return self
+                    .features
+                    .iter()
+                    .map(|f| &f.location)
+                    .find(|loc| loc.pack_name() == pack);
             }
+            _ => None,
+        };
+        location.filter(|loc| loc.pack_name() == pack)
+    }
+
+    /// Skin theme with asset URLs under the request's `path_prefix`; `None` without a skin pack.
+    pub fn theme(&self, path_prefix: Option<&str>) -> Option<Cow<'_, Theme>> {
+        let theme = &self.skin.as_ref()?.theme;
+        Some(match path_prefix {
+            Some(prefix) => Cow::Owned(theme.with_url_prefix(prefix)),
+            None => Cow::Borrowed(theme),
+        })
+    }
+
+    /// Event allowlist and redaction enforced by `/api/gui/events`.
+    pub fn telemetry_policy(&self) -> &TelemetryPolicy {
+        static ALLOW_ALL: LazyLock<TelemetryPolicy> = LazyLock::new(TelemetryPolicy::default);
+        match &self.telemetry {
+            Some(telemetry) => &telemetry.policy,
+            None => &ALLOW_ALL,
         }
+    }
 
-        if let Some(auth) = &self.auth {
-            for route in &auth.manifest.routes {
-                if path_matches(&path, &route.path) {
-                    return Some(ResolvedRoute {
-                        source: RouteSource::Auth(auth.clone()),
-                        html_path: auth.location.assets.join(&route.html),
-                        authenticated: !route.public,
-                        fragments: vec![],
-                    });
-                }
-            }
+    /// Sampling, allowlist and auto-capture settings for the SDK.
+    pub fn telemetry_client_policy(&self) -> ClientPolicy {
+        match &self.telemetry {
+            Some(telemetry) => ClientPolicy::from(&telemetry.manifest),
+            None => ClientPolicy::from(&TelemetryManifest::default()),
         }
+    }
 
-        // fallback to layout entrypoint
+    /// Resolve `path` to the most specific feature route across all feature packs, then to an
+    /// auth route. Otherwise `/`, and for SPA layouts any path that does not look like an asset,
+    /// falls back to the layout entrypoint so the client router can take over.
+    pub fn resolve_route(&self, path: &str) -> Option<ResolvedRoute> {
+        let path = normalize_route(path);
+        if let Some(resolved) = self.resolve_pack_route(&path) {
+            return Some(resolved);
+        }
+
+        let spa_fallback = self.layout.manifest.layout.spa && !looks_like_asset(&path);
+        if path != "/" && !spa_fallback {
+            return None;
+        }
         Some(ResolvedRoute {
             source: RouteSource::Layout(self.layout.clone()),
             html_path: self
@@ -227,8 +406,152 @@ impl TenantGuiConfig {
                 .join(&self.layout.manifest.layout.entrypoint_html),
             authenticated: false,
             fragments: vec![],
+            pattern: None,
+            params: RouteParams::new(),
+        })
+    }
+
+    fn resolve_pack_route(&self, path: &str) -> Option<ResolvedRoute> {
+        let (entry, matched) = self.routes.resolve(path)?;
+        let pattern = Some(entry.pattern.as_str().to_string());
+        match entry.feature {
+            Some(index) => {
+                let feature = self.features.get(index)?;
+                let route = feature.manifest.routes.get(entry.route)?;
+                let pack = Arc::new(PackScope::for_feature(feature));
+                let fragments = feature
+                    .manifest
+                    .fragments
+                    .iter()
+                    .cloned()
+                    .map(|binding| FragmentTarget {
+                        binding,
+                        assets_root: feature.location.assets.clone(),
+                        pack: pack.clone(),
+                    })
+                    .collect();
+                Some(ResolvedRoute {
+                    source: RouteSource::Feature(feature.clone()),
+                    html_path: feature.location.assets.join(&route.html),
+                    authenticated: route.authenticated,
+                    fragments,
+                    pattern,
+                    params: matched.params,
+                })
+            }
+            None => {
+                let auth = self.auth.as_ref()?;
+                let route = auth.manifest.routes.get(entry.route)?;
+                Some(ResolvedRoute {
+                    source: RouteSource::Auth(auth.clone()),
+                    html_path: auth.location.assets.join(&route.html),
+                    authenticated: !route.public,
+                    fragments: vec![],
+                    pattern,
+                    params: matched.params,
+                })
+            }
+        }
+    }
+
+    /// Client-side route table injected into SPA entrypoints: feature routes in declaration
+    /// order, then auth routes.
+    pub fn route_manifest(&self) -> Vec<RouteManifestEntry> {
+        let features = self.features.iter().flat_map(|feature| {
+            let fragments: Vec<String> = feature
+                .manifest
+                .fragments
+                .iter()
+                .map(|binding| binding.id.clone())
+                .collect();
+            feature
+                .manifest
+                .routes
+                .iter()
+                .map(move |route| RouteManifestEntry {
+                    path: route.path.clone(),
+                    authenticated: route.authenticated,
+                    fragments: fragments.clone(),
+                    pack: feature.location.pack_name(),
+                })
+        });
+        let auth = self.auth.iter().flat_map(|auth| {
+            auth.manifest.routes.iter().map(|route| RouteManifestEntry {
+                path: route.path.clone(),
+                authenticated: !route.public,
+                fragments: vec![],
+                pack: auth.location.pack_name(),
+            })
+        });
+        features.chain(auth).collect()
+    }
+
+    /// Navigation entries for the static feature routes that declare `menu` metadata, hiding
+    /// routes the session may not open. Sorted by group (ungrouped first), order, then label.
+    pub fn menu(&self, session: Option<&SessionInfo>) -> Vec<MenuItem> {
+        let mut items: Vec<MenuItem> = self
+            .routes
+            .entries
+            .iter()
+            .filter(|entry| entry.pattern.is_static())
+            .filter_map(|entry| {
+                let route = self
+                    .features
+                    .get(entry.feature?)?
+                    .manifest
+                    .routes
+                    .get(entry.route)?;
+                let menu = route.menu.as_ref()?;
+                if (route.authenticated || menu.scope.is_some()) && session.is_none() {
+                    return None;
+                }
+                if let (Some(scope), Some(session)) = (&menu.scope, session)
+                    && !session.scopes.contains(scope)
+                {
+                    return None;
+                }
+                Some(MenuItem {
+                    label: menu.label.clone(),
+                    href: entry.pattern.as_str().to_string(),
+                    icon: menu.icon.clone(),
+                    group: menu.group.clone(),
+                    order: menu.order,
+                })
+            })
+            .collect();
+        items.sort_by(|a, b| (&a.group, a.order, &a.label).cmp(&(&b.group, b.order, &b.label)));
+        items
+    }
+
+    /// Validate a post-login return path: a same-origin absolute path that resolves to a feature
+    /// route or the layout root. Returns the normalized path and query.
+    pub fn safe_return_to(&self, raw: &str) -> Option<String> {
+        if !raw.starts_with('/')
+            || raw.starts_with("//")
+            || raw.contains('\\')
+            || raw.chars().any(char::is_control)
+        {
+            return None;
+        }
+        let base = url::Url::parse("http://gui.invalid/").ok()?;
+        let url = base.join(raw).ok()?;
+        if url.origin() != base.origin() || !self.is_return_target(url.path()) {
+            return None;
+        }
+        Some(match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
         })
     }
+
+    /// Auth routes are never return targets, which avoids login loops.
+    fn is_return_target(&self, path: &str) -> bool {
+        let path = normalize_route(path);
+        path == "/"
+            || self
+                .resolve_route(&path)
+                .is_some_and(|route| matches!(route.source, RouteSource::Feature(_)))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -239,6 +562,16 @@ pub enum RouteSource {
     Feature(FeaturePack),
 }
 
+impl RouteSource {
+    pub fn pack_name(&self) -> String {
+        match self {
+            RouteSource::Layout(pack) => pack.location.pack_name(),
+            RouteSource::Auth(pack) => pack.location.pack_name(),
+            RouteSource::Feature(pack) => pack.location.pack_name(),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ResolvedRoute {
     #[allow(dead_code)]
@@ -246,20 +579,199 @@ pub struct ResolvedRoute {
     pub html_path: std::path::PathBuf,
     pub authenticated: bool,
     pub fragments: Vec<FragmentTarget>,
+    /// Manifest pattern that matched; `None` for the layout fallback.
+    pub pattern: Option<String>,
+    pub params: RouteParams,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MenuItem {
+    pub label: String,
+    pub href: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    pub order: i32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteManifestEntry {
+    pub path: String,
+    pub authenticated: bool,
+    /// Ids of the fragments rendered for this route.
+    pub fragments: Vec<String>,
+    pub pack: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Both routes match exactly the same paths; pattern and pack name break the tie.
+    Duplicate,
+    /// Routes in different feature packs share some paths; the more specific one serves them.
+    Overlap,
+    /// A feature route matches paths of an auth route, which then never serves them.
+    ShadowsAuth,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteConflict {
+    pub kind: ConflictKind,
+    pub route: String,
+    pub pack: String,
+    pub other_route: String,
+    pub other_pack: String,
+}
+
+impl std::fmt::Display for RouteConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let what = match self.kind {
+            ConflictKind::Duplicate => "duplicates",
+            ConflictKind::Overlap => "overlaps",
+            ConflictKind::ShadowsAuth => "shadows auth route",
+        };
+        write!(
+            f,
+            "route {} in pack {} {what} {} in pack {}",
+            self.route, self.pack, self.other_route, self.other_pack
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FragmentTarget {
     pub binding: crate::packs::FragmentBinding,
     pub assets_root: std::path::PathBuf,
+    /// Data the binding's host imports may read.
+    pub pack: Arc<PackScope>,
+}
+
+/// Paths whose last segment has a known file extension are real 404s, never SPA routes.
+fn looks_like_asset(path: &str) -> bool {
+    let last = path.rsplit('/').next().unwrap_or_default();
+    last.contains('.') && mime_guess::from_path(last).first().is_some()
+}
+
+/// Auth and feature routes parsed once at load, feature routes first and each most specific
+/// pattern first.
+#[derive(Debug, Clone, Default)]
+pub struct RouteTable {
+    entries: Vec<RouteEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct RouteEntry {
+    pattern: RoutePattern,
+    pack: String,
+    /// Index into the tenant's feature packs; `None` for an auth route.
+    feature: Option<usize>,
+    /// Index into the pack manifest's `routes`.
+    route: usize,
 }
 
-fn path_matches(path: &str, pattern: &str) -> bool {
-    if pattern.ends_with("/*") {
-        let base = pattern.trim_end_matches('*');
-        return path.starts_with(base.trim_end_matches('/'));
+impl RouteEntry {
+    /// More specific wins; ties go to the lexically smaller pattern, then pack name, so the
+    /// result never depends on pack load order.
+    fn beats(&self, matched: &RouteMatch, other: &Self, other_matched: &RouteMatch) -> bool {
+        matched
+            .specificity
+            .cmp(&other_matched.specificity)
+            .then_with(|| other.pattern.as_str().cmp(self.pattern.as_str()))
+            .then_with(|| other.pack.cmp(&self.pack))
+            .is_gt()
+    }
+}
+
+impl RouteTable {
+    /// Parse every auth and feature route; invalid patterns fail the load.
+    pub fn new(auth: Option<&AuthPack>, features: &[FeaturePack]) -> anyhow::Result<Self> {
+        let auth_routes = auth.into_iter().flat_map(|a| {
+            a.manifest
+                .routes
+                .iter()
+                .enumerate()
+                .map(move |(route, r)| (&a.location, None, route, &r.path))
+        });
+        let feature_routes = features.iter().enumerate().flat_map(|(feature, f)| {
+            f.manifest
+                .routes
+                .iter()
+                .enumerate()
+                .map(move |(route, r)| (&f.location, Some(feature), route, &r.path))
+        });
+        let mut entries = auth_routes
+            .chain(feature_routes)
+            .map(|(location, feature, route, path)| {
+                let pattern = RoutePattern::parse(path)
+                    .with_context(|| format!("invalid route in pack {}", location.pack_name()))?;
+                Ok(RouteEntry {
+                    pattern,
+                    pack: location.pack_name(),
+                    feature,
+                    route,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        entries.sort_by(|a, b| {
+            a.feature
+                .is_none()
+                .cmp(&b.feature.is_none())
+                .then_with(|| b.pattern.specificity().cmp(&a.pattern.specificity()))
+                .then_with(|| a.pattern.as_str().cmp(b.pattern.as_str()))
+                .then_with(|| a.pack.cmp(&b.pack))
+        });
+        Ok(Self { entries })
+    }
+
+    /// The best route for a normalized `path`: a feature route if any matches, else an auth
+    /// route. Candidates are compared by how they match `path`, as optional and wildcard tails
+    /// can rank differently than their patterns do.
+    fn resolve(&self, path: &str) -> Option<(&RouteEntry, RouteMatch)> {
+        let mut best: Option<(&RouteEntry, RouteMatch)> = None;
+        for entry in &self.entries {
+            if entry.feature.is_none() && best.is_some() {
+                break;
+            }
+            let Some(matched) = entry.pattern.matches(path) else {
+                continue;
+            };
+            if best.as_ref().is_none_or(|(current, current_matched)| {
+                entry.beats(&matched, current, current_matched)
+            }) {
+                best = Some((entry, matched));
+            }
+        }
+        best
+    }
+
+    /// Duplicate and overlapping routes across the table.
+    fn conflicts(&self) -> Vec<RouteConflict> {
+        let mut conflicts = Vec::new();
+        for (idx, a) in self.entries.iter().enumerate() {
+            for b in &self.entries[idx + 1..] {
+                let (a_auth, b_auth) = (a.feature.is_none(), b.feature.is_none());
+                let kind = if a_auth != b_auth && a.pattern.overlaps(&b.pattern) {
+                    ConflictKind::ShadowsAuth
+                } else if a.pattern.same_shape(&b.pattern) {
+                    ConflictKind::Duplicate
+                } else if !a_auth && !b_auth && a.pack != b.pack && a.pattern.overlaps(&b.pattern) {
+                    ConflictKind::Overlap
+                } else {
+                    continue;
+                };
+                // Feature routes come first in the table, so `a` is the one shadowing `b`.
+                conflicts.push(RouteConflict {
+                    kind,
+                    route: a.pattern.as_str().to_string(),
+                    pack: a.pack.clone(),
+                    other_route: b.pattern.as_str().to_string(),
+                    other_pack: b.pack.clone(),
+                });
+            }
+        }
+        conflicts
     }
-    normalize_route(pattern) == path
 }
 
 fn dedup_requirements(requirements: Vec<SecretRequirement>) -> Vec<SecretRequirement> {
@@ -299,7 +811,7 @@ mod tests {
     use std::path::PathBuf;
 
     fn sample_config() -> TenantGuiConfig {
-        TenantGuiConfig {
+        with_routes(TenantGuiConfig {
             tenant_did: "tenant".into(),
             domain: "example.com".into(),
             layout: LayoutPack {
@@ -328,6 +840,7 @@ mod tests {
             auth: None,
             skin: None,
             telemetry: None,
+            route_conflicts: vec![],
             features: vec![FeaturePack {
                 manifest: FeatureManifest {
                     kind: "gui-feature".into(),
@@ -335,9 +848,13 @@ mod tests {
                         path: "/invoices".into(),
                         authenticated: true,
                         html: "invoices.html".into(),
+                        menu: None,
                     }],
                     digital_workers: vec![],
                     fragments: vec![],
+                    slots: HashMap::new(),
+                    config: Default::default(),
+                    i18n: HashMap::new(),
                 },
                 location: PackLocation {
                     root: PathBuf::from("/tmp/feature"),
@@ -348,7 +865,13 @@ mod tests {
                 secret_requirements: vec![],
             }],
             secret_requirements: vec![],
-        }
+            routes: RouteTable::default(),
+        })
+    }
+
+    fn with_routes(mut cfg: TenantGuiConfig) -> TenantGuiConfig {
+        cfg.routes = RouteTable::new(cfg.auth.as_ref(), &cfg.features).unwrap();
+        cfg
     }
 
     #[test]
@@ -364,6 +887,297 @@ mod tests {
         );
     }
 
+    fn feature(name: &str, routes: &[(&str, &str)]) -> FeaturePack {
+        let root = PathBuf::from("/tmp").join(name);
+        FeaturePack {
+            manifest: FeatureManifest {
+                kind: "gui-feature".into(),
+                routes: routes
+                    .iter()
+                    .map(|(path, html)| FeatureRoute {
+                        path: path.to_string(),
+                        authenticated: false,
+                        html: html.to_string(),
+                        menu: None,
+                    })
+                    .collect(),
+                digital_workers: vec![],
+                fragments: vec![],
+                slots: HashMap::new(),
+                config: Default::default(),
+                i18n: HashMap::new(),
+            },
+            location: PackLocation {
+                assets: root.join("gui").join("assets"),
+                root,
+                secret_requirements: vec![],
+                pack_hint: None,
+            },
+            secret_requirements: vec![],
+        }
+    }
+
+    #[test]
+    fn most_specific_route_wins_across_packs() {
+        let mut cfg = sample_config();
+        cfg.features = vec![
+            feature("catchall", &[("/invoices/*", "any.html")]),
+            feature(
+                "billing",
+                &[
+                    ("/invoices/:id", "show.html"),
+                    ("/invoices/new", "new.html"),
+                ],
+            ),
+        ];
+        let mut cfg = with_routes(cfg);
+        let table: Vec<&str> = cfg
+            .routes
+            .entries
+            .iter()
+            .map(|e| e.pattern.as_str())
+            .collect();
+        assert_eq!(table, ["/invoices/new", "/invoices/:id", "/invoices/*"]);
+        let html = |cfg: &TenantGuiConfig, path: &str| {
+            let route = cfg.resolve_route(path).unwrap();
+            let name = route.html_path.file_name().unwrap().to_string_lossy();
+            (name.to_string(), route.params)
+        };
+        assert_eq!(html(&cfg, "/invoices/new").0, "new.html");
+        let (name, params) = html(&cfg, "/invoices/7");
+        assert_eq!(name, "show.html");
+        assert_eq!(params.get("id").map(String::as_str), Some("7"));
+        assert_eq!(html(&cfg, "/invoices/7/edit").0, "any.html");
+        assert_eq!(html(&cfg, "/invoicesXYZ").0, "index.html");
+
+        cfg.features.reverse();
+        let cfg = with_routes(cfg);
+        assert_eq!(html(&cfg, "/invoices/new").0, "new.html");
+        assert_eq!(html(&cfg, "/invoices/7").0, "show.html");
+    }
+
+    fn write_pack(root: &std::path::Path, pack: &str, manifest: serde_json::Value) {
+        let gui = root.join("tenant").join(pack).join("gui");
+        std::fs::create_dir_all(&gui).unwrap();
+        std::fs::write(gui.join("manifest.json"), manifest.to_string()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reports_route_conflicts_and_fails_strict_loads() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            serde_json::json!({
+                "kind": "gui-layout",
+                "layout": { "slots": [], "entrypoint_html": "index.html", "spa": false, "slot_selectors": {} },
+            }),
+        );
+        write_pack(
+            temp.path(),
+            "auth",
+            serde_json::json!({
+                "kind": "gui-auth",
+                "routes": [{ "path": "/login", "public": true, "html": "login.html" }],
+                "oauth": {},
+                "ui_bindings": {},
+            }),
+        );
+        let feature = |routes: serde_json::Value| serde_json::json!({ "kind": "gui-feature", "routes": routes });
+        write_pack(
+            temp.path(),
+            "billing",
+            feature(serde_json::json!([
+                { "path": "/billing", "html": "a.html" },
+                { "path": "/invoices/new", "html": "new.html" },
+                { "path": "/invoices/:id", "html": "show.html" },
+            ])),
+        );
+        write_pack(
+            temp.path(),
+            "legacy",
+            feature(serde_json::json!([
+                { "path": "billing/", "html": "b.html" },
+                { "path": "/:page", "html": "page.html" },
+            ])),
+        );
+
+        let provider = Arc::new(crate::packs::FsPackProvider::new(temp.path().to_path_buf()));
+        let cfg = TenantGuiConfig::load("tenant", "localhost", provider.clone())
+            .await
+            .unwrap();
+        let found: Vec<_> = cfg
+            .route_conflicts
+            .iter()
+            .map(|c| (c.kind, c.route.as_str(), c.other_route.as_str()))
+            .collect();
+        assert!(found.contains(&(ConflictKind::Duplicate, "/billing", "/billing")));
+        assert!(found.contains(&(ConflictKind::Overlap, "/billing", "/:page")));
+        assert!(found.contains(&(ConflictKind::ShadowsAuth, "/:page", "/login")));
+        // Routes within one pack that only overlap are intentional.
+        assert!(
+            !found
+                .iter()
+                .any(|(_, a, b)| *a == "/invoices/new" && *b == "/invoices/:id")
+        );
+
+        let mut config = crate::config::test_config();
+        config.strict_routes = true;
+        let state = crate::server::AppState::new(
+            config,
+            provider,
+            Arc::new(crate::fragments::CompositeFragmentRenderer::file_only()),
+            Arc::new(crate::integration::StubSessionManager),
+            Arc::new(crate::integration::NullTelemetrySink),
+            Arc::new(crate::worker::WorkerHost::new(Arc::new(
+                crate::worker::StubWorkerBackend,
+            ))),
+        );
+        let err = state.load_tenant("tenant", "localhost").await.unwrap_err();
+        assert!(err.to_string().contains("route conflicts"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_fragment_worlds_at_load() {
+        let temp = tempfile::tempdir().unwrap();
+        write_pack(
+            temp.path(),
+            "layout",
+            serde_json::json!({
+                "kind": "gui-layout",
+                "layout": { "slots": [], "entrypoint_html": "index.html", "spa": false, "slot_selectors": {} },
+            }),
+        );
+        write_pack(
+            temp.path(),
+            "billing",
+            serde_json::json!({
+                "kind": "gui-feature",
+                "routes": [{ "path": "/billing", "html": "a.html" }],
+                "fragments": [{
+                    "id": "summary",
+                    "selector": "#summary",
+                    "component_world": "greentic:gui/gui-fragment@2.0.0",
+                    "component_name": "summary",
+                }],
+            }),
+        );
+
+        let provider = Arc::new(crate::packs::FsPackProvider::new(temp.path().to_path_buf()));
+        let err = TenantGuiConfig::load("tenant", "localhost", provider)
+            .await
+            .unwrap_err();
+        let message = format!("{err:#}");
+        assert!(message.contains("invalid fragment summary"), "{message}");
+        assert!(message.contains("gui-fragment@2.0.0"), "{message}");
+    }
+
+    #[test]
+    fn deserialized_configs_parse_their_routes() {
+        let mut value = serde_json::to_value(sample_config()).unwrap();
+        let cfg: TenantGuiConfig = serde_json::from_value(value.clone()).unwrap();
+        assert!(cfg.resolve_route("/invoices").is_some());
+
+        value["features"][0]["manifest"]["routes"][0]["path"] = "/*rest/more".into();
+        let err = serde_json::from_value::<TenantGuiConfig>(value).unwrap_err();
+        assert!(err.to_string().contains("invalid route"), "{err}");
+    }
+
+    #[test]
+    fn only_spa_layouts_fall_back_to_the_entrypoint() {
+        let mut cfg = sample_config();
+        let is_layout = |cfg: &TenantGuiConfig, path: &str| {
+            cfg.resolve_route(path)
+                .is_some_and(|route| matches!(route.source, RouteSource::Layout(_)))
+        };
+        assert!(is_layout(&cfg, "/dashboard/settings"));
+        assert!(is_layout(&cfg, "/releases/v1.2"));
+        assert!(cfg.resolve_route("/assets/app.js").is_none());
+        assert!(cfg.resolve_route("/favicon.ico").is_none());
+
+        cfg.layout.manifest.layout.spa = false;
+        assert!(cfg.resolve_route("/dashboard/settings").is_none());
+        assert!(is_layout(&cfg, "/"));
+    }
+
+    #[test]
+    fn return_to_must_be_same_origin_known_route() {
+        let cfg = sample_config();
+        assert_eq!(
+            cfg.safe_return_to("/invoices?id=7").as_deref(),
+            Some("/invoices?id=7")
+        );
+        assert_eq!(
+            cfg.safe_return_to("/reports/../invoices").as_deref(),
+            Some("/invoices")
+        );
+        assert_eq!(cfg.safe_return_to("/").as_deref(), Some("/"));
+        for rejected in [
+            "//evil.test/invoices",
+            "https://evil.test/invoices",
+            "/\\evil.test",
+            "invoices",
+            "/unknown",
+            "/invoices\n",
+        ] {
+            assert_eq!(cfg.safe_return_to(rejected), None, "{rejected:?}");
+        }
+    }
+
+    #[test]
+    fn telemetry_policy_is_built_at_load() {
+        let mut cfg = sample_config();
+        assert!(cfg.telemetry_policy().allows("anything"));
+
+        let location = PackLocation {
+            root: PathBuf::from("/tmp/telemetry"),
+            assets: PathBuf::from("/tmp/telemetry/gui/assets"),
+            secret_requirements: vec![],
+            pack_hint: None,
+        };
+        let manifest = TelemetryManifest {
+            allowed_events: vec!["page_view".into()],
+            ..Default::default()
+        };
+        cfg.telemetry = Some(TelemetryPack::new(manifest, location.clone()).unwrap());
+        assert!(cfg.telemetry_policy().allows("page_view"));
+        assert!(!cfg.telemetry_policy().allows("click"));
+
+        // Deserialized packs are validated too instead of falling back to allow-all.
+        let invalid = serde_json::json!({
+            "manifest": { "kind": "gui-telemetry", "sample_rate": 2.0 },
+            "location": location,
+        });
+        assert!(serde_json::from_value::<TelemetryPack>(invalid).is_err());
+    }
+
+    #[test]
+    fn theme_is_built_at_load() {
+        let mut cfg = sample_config();
+        assert!(cfg.theme(None).is_none());
+
+        let location = PackLocation {
+            root: PathBuf::from("/tmp/brand"),
+            assets: PathBuf::from("/tmp/brand/gui/assets"),
+            secret_requirements: vec![],
+            pack_hint: None,
+        };
+        let manifest: SkinManifest =
+            serde_json::from_value(serde_json::json!({ "stylesheets": ["brand.css"] })).unwrap();
+        cfg.skin = Some(SkinPack::new(manifest, location.clone()).unwrap());
+        assert!(matches!(cfg.theme(None), Some(Cow::Borrowed(_))));
+        assert_eq!(
+            cfg.theme(Some("/t/acme")).unwrap().stylesheets,
+            ["/t/acme/_packs/skin/brand/brand.css"]
+        );
+
+        let invalid = serde_json::json!({
+            "manifest": { "kind": "gui-skin", "stylesheets": ["../layout/app.css"] },
+            "location": location,
+        });
+        assert!(serde_json::from_value::<SkinPack>(invalid).is_err());
+    }
+
     #[test]
     fn dedups_secret_requirements() {
         let mut req1 = SecretRequirement::default();
@@ -0,0 +1,232 @@
+//! Virtual filesystem over pack roots that are either directories or `.gtpack` archives.
+//!
+//! Paths keep their on-disk shape: `/packs/tenant/billing.gtpack/gui/assets/app.css` addresses
+//! the `gui/assets/app.css` entry inside the `billing.gtpack` zip.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, LazyLock, RwLock};
+use std::time::SystemTime;
+use zip::ZipArchive;
+use zip::read::ZipArchiveMetadata;
+
+/// Modification time and size of a file.
+pub type Stamp = (Option<SystemTime>, u64);
+
+/// Central directories of the archives read so far, so reads skip re-scanning the zip. An entry
+/// is reused only while its archive keeps the stamp it was indexed at; [`forget`] drops it.
+static ARCHIVES: LazyLock<RwLock<HashMap<PathBuf, ArchiveIndex>>> = LazyLock::new(RwLock::default);
+
+#[derive(Clone)]
+struct ArchiveIndex {
+    stamp: Stamp,
+    metadata: Arc<ZipArchiveMetadata>,
+}
+
+fn cached_index(archive: &Path) -> Option<ArchiveIndex> {
+    ARCHIVES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(archive)
+        .cloned()
+}
+
+/// Open `archive`, reusing its cached index unless the file changed since it was indexed.
+fn open_archive(archive: &Path) -> io::Result<ZipArchive<File>> {
+    let file = File::open(archive)?;
+    let meta = file.metadata()?;
+    let stamp = (meta.modified().ok(), meta.len());
+    if let Some(index) = cached_index(archive).filter(|index| index.stamp == stamp) {
+        // SAFETY: the metadata was parsed from this file at the same modification time and
+        // size; zip reads are bounds-checked and CRC-verified should it differ regardless.
+        return Ok(unsafe { ZipArchive::unsafe_new_with_metadata(file, index.metadata) });
+    }
+    let zip = ZipArchive::new(file).map_err(io::Error::other)?;
+    let index = ArchiveIndex {
+        stamp,
+        metadata: zip.metadata(),
+    };
+    ARCHIVES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(archive.to_path_buf(), index);
+    Ok(zip)
+}
+
+/// Drop the cached indexes of archives at or below `root`, or holding it, so the next read
+/// re-scans them. Called when packs are reloaded.
+pub fn forget(root: &Path) {
+    ARCHIVES
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .retain(|archive, _| !archive.starts_with(root) && !root.starts_with(archive));
+}
+
+/// Split a path into `(archive, entry)` when one of its ancestors is a `.gtpack` file.
+pub fn split_archive_path(path: &Path) -> Option<(PathBuf, String)> {
+    let mut archive = PathBuf::new();
+    let mut components = path.components();
+    for component in components.by_ref() {
+        archive.push(component);
+        if is_gtpack(&archive) && (cached_index(&archive).is_some() || archive.is_file()) {
+            let entry = components
+                .filter_map(|c| match c {
+                    Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            return Some((archive, entry));
+        }
+    }
+    None
+}
+
+pub fn is_gtpack(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gtpack"))
+}
+
+/// Whether `path` lives inside (or is) a `.gtpack` archive.
+pub fn is_archive_path(path: &Path) -> bool {
+    split_archive_path(path).is_some()
+}
+
+pub fn read_blocking(path: &Path) -> io::Result<Vec<u8>> {
+    let Some((archive, entry)) = split_archive_path(path) else {
+        return std::fs::read(path);
+    };
+    let mut zip = open_archive(&archive)?;
+    let mut file = zip.by_name(&entry).map_err(|err| match err {
+        zip::result::ZipError::FileNotFound => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{entry} not found in {}", archive.display()),
+        ),
+        other => io::Error::other(other),
+    })?;
+    let mut buf = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+pub fn is_file_blocking(path: &Path) -> bool {
+    let Some((archive, entry)) = split_archive_path(path) else {
+        return path.is_file();
+    };
+    let Ok(mut zip) = open_archive(&archive) else {
+        return false;
+    };
+    zip.by_name(&entry).is_ok_and(|f| f.is_file())
+}
+
+pub async fn read(path: &Path) -> io::Result<Vec<u8>> {
+    if !is_archive_path(path) {
+        return tokio::fs::read(path).await;
+    }
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || read_blocking(&path))
+        .await
+        .map_err(io::Error::other)?
+}
+
+pub async fn read_to_string(path: &Path) -> io::Result<String> {
+    let bytes = read(path).await?;
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Modification time and size of `path`, or of the archive holding it. Indexed archives answer
+/// with the stamp they were indexed at, without touching the file, until [`forget`] drops them.
+pub async fn stamp(path: &Path) -> io::Result<Stamp> {
+    let file = match split_archive_path(path) {
+        Some((archive, _)) => match cached_index(&archive) {
+            Some(index) => return Ok(index.stamp),
+            None => archive,
+        },
+        None => path.to_path_buf(),
+    };
+    let meta = tokio::fs::metadata(file).await?;
+    Ok((meta.modified().ok(), meta.len()))
+}
+
+pub async fn is_file(path: &Path) -> bool {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || is_file_blocking(&path))
+        .await
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    #[tokio::test]
+    async fn reads_entries_from_gtpack_and_plain_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let archive = temp.path().join("demo.gtpack");
+        let mut writer = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        writer
+            .start_file("gui/assets/index.html", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"<h1>packed</h1>").unwrap();
+        writer.finish().unwrap();
+        std::fs::write(temp.path().join("plain.txt"), "plain").unwrap();
+
+        let entry = archive.join("gui").join("assets").join("index.html");
+        assert_eq!(
+            split_archive_path(&entry),
+            Some((archive.clone(), "gui/assets/index.html".to_string()))
+        );
+        assert_eq!(read_to_string(&entry).await.unwrap(), "<h1>packed</h1>");
+        assert!(is_file(&entry).await);
+        assert!(!is_file(&archive.join("gui").join("missing.html")).await);
+        let missing = read(&archive.join("gui/missing.html")).await.unwrap_err();
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            read_to_string(&temp.path().join("plain.txt"))
+                .await
+                .unwrap(),
+            "plain"
+        );
+    }
+
+    fn write_archive(path: &Path, body: &[u8]) {
+        let mut writer = zip::ZipWriter::new(File::create(path).unwrap());
+        writer
+            .start_file("gui/a.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(body).unwrap();
+        writer.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn archive_indexes_are_cached_until_forgotten() {
+        let temp = tempfile::tempdir().unwrap();
+        let archive = temp.path().join("demo.gtpack");
+        let entry = archive.join("gui/a.txt");
+        write_archive(&archive, b"one");
+        assert_eq!(read_to_string(&entry).await.unwrap(), "one");
+        let indexed = stamp(&entry).await.unwrap();
+        assert!(cached_index(&archive).is_some());
+
+        // Stamps come from the index; reads notice the rewritten archive and re-index it.
+        write_archive(&archive, b"two, longer");
+        assert_eq!(stamp(&entry).await.unwrap(), indexed);
+        assert_eq!(read_to_string(&entry).await.unwrap(), "two, longer");
+        let reindexed = stamp(&entry).await.unwrap();
+        assert_ne!(reindexed, indexed);
+
+        write_archive(&archive, b"three, longest");
+        assert_eq!(stamp(&entry).await.unwrap(), reindexed);
+        forget(temp.path());
+        assert!(cached_index(&archive).is_none());
+        let meta = std::fs::metadata(&archive).unwrap();
+        assert_eq!(
+            stamp(&entry).await.unwrap(),
+            (meta.modified().ok(), meta.len())
+        );
+    }
+}
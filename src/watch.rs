@@ -0,0 +1,284 @@
+use crate::server::AppState;
+use crate::tenant::TenantGuiConfig;
+use notify::{Config, Event, PollWatcher, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Tenant-scoped notification emitted after changed packs were reloaded successfully.
+#[derive(Debug, Clone, Serialize)]
+pub struct PackChange {
+    pub tenant: String,
+    /// Pack directory (or `.gtpack` file) names that changed.
+    pub packs: Vec<String>,
+    pub reload: ReloadKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReloadKind {
+    /// Only skin stylesheets changed; browsers can swap CSS in place.
+    Css,
+    Full,
+}
+
+/// Keeps the underlying filesystem watcher alive; dropping it stops hot reload.
+pub struct PackWatcher {
+    _watcher: Box<dyn Watcher + Send>,
+}
+
+/// Watch `root` (the `FsPackProvider` pack root) and reload affected tenants after `debounce`.
+///
+/// Uses the platform watcher (inotify on Linux) and falls back to polling when it is unavailable.
+pub fn watch_packs(
+    state: AppState,
+    root: PathBuf,
+    debounce: Duration,
+) -> anyhow::Result<PackWatcher> {
+    let root = std::fs::canonicalize(&root)?;
+    let (tx, rx) = mpsc::unbounded_channel::<PathBuf>();
+    let handler = move |res: notify::Result<Event>| match res {
+        Ok(event) => {
+            for path in event.paths {
+                let _ = tx.send(path);
+            }
+        }
+        Err(err) => warn!(?err, "pack watcher error"),
+    };
+
+    let watcher: Box<dyn Watcher + Send> = match native_watcher(&root, handler.clone()) {
+        Ok(watcher) => Box::new(watcher),
+        Err(err) => {
+            warn!(
+                ?err,
+                "native pack watcher unavailable; falling back to polling"
+            );
+            let mut watcher =
+                PollWatcher::new(handler, Config::default().with_poll_interval(POLL_INTERVAL))?;
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            Box::new(watcher)
+        }
+    };
+
+    info!(root = %root.display(), "watching packs for changes");
+    tokio::spawn(reload_loop(state, root, rx, debounce));
+    Ok(PackWatcher { _watcher: watcher })
+}
+
+fn native_watcher<F>(root: &Path, handler: F) -> notify::Result<RecommendedWatcher>
+where
+    F: notify::EventHandler,
+{
+    let mut watcher = RecommendedWatcher::new(handler, Config::default())?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    Ok(watcher)
+}
+
+async fn reload_loop(
+    state: AppState,
+    root: PathBuf,
+    mut rx: mpsc::UnboundedReceiver<PathBuf>,
+    debounce: Duration,
+) {
+    while let Some(path) = rx.recv().await {
+        let mut changed: HashMap<String, HashSet<PathBuf>> = HashMap::new();
+        record_change(&root, &path, &mut changed);
+
+        // Collect the burst of events an editor save or pack copy produces.
+        let deadline = tokio::time::sleep(debounce);
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                _ = &mut deadline => break,
+                next = rx.recv() => match next {
+                    Some(path) => record_change(&root, &path, &mut changed),
+                    None => break,
+                },
+            }
+        }
+
+        for (tenant, paths) in changed {
+            match state.reload_tenant(&tenant).await {
+                Ok(cfg) => {
+                    info!(%tenant, "reloaded tenant packs after change");
+                    state.notify_pack_change(classify_change(&cfg, &paths));
+                }
+                Err(err) => warn!(
+                    %tenant,
+                    ?err,
+                    "pack change failed validation; keeping previous tenant config"
+                ),
+            }
+        }
+    }
+    debug!("pack watcher channel closed");
+}
+
+/// Record `path` relative to its tenant directory.
+fn record_change(root: &Path, path: &Path, changed: &mut HashMap<String, HashSet<PathBuf>>) {
+    let Some(tenant) = tenant_for_path(root, path) else {
+        return;
+    };
+    let rel = path
+        .strip_prefix(root.join(&tenant))
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    changed.entry(tenant).or_default().insert(rel);
+}
+
+fn classify_change(cfg: &TenantGuiConfig, paths: &HashSet<PathBuf>) -> PackChange {
+    let skin_pack = cfg
+        .skin
+        .as_ref()
+        .and_then(|skin| skin.location.root.file_name())
+        .map(|name| name.to_string_lossy().to_string());
+    let mut packs = HashSet::new();
+    let mut css_only = !paths.is_empty();
+    for rel in paths {
+        let pack = match rel.components().next() {
+            Some(Component::Normal(name)) => name.to_string_lossy().to_string(),
+            _ => {
+                css_only = false;
+                continue;
+            }
+        };
+        let is_css = rel.extension().is_some_and(|ext| ext == "css");
+        if !is_css || skin_pack.as_deref() != Some(pack.as_str()) {
+            css_only = false;
+        }
+        packs.insert(pack);
+    }
+    let mut packs: Vec<String> = packs.into_iter().collect();
+    packs.sort();
+    PackChange {
+        tenant: cfg.tenant_did.clone(),
+        packs,
+        reload: if css_only {
+            ReloadKind::Css
+        } else {
+            ReloadKind::Full
+        },
+    }
+}
+
+/// Map a changed path to its tenant: the first component under the pack root.
+fn tenant_for_path(root: &Path, path: &Path) -> Option<String> {
+    match path.strip_prefix(root).ok()?.components().next()? {
+        Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fragments::CompositeFragmentRenderer;
+    use crate::integration::{NullTelemetrySink, StubSessionManager};
+    use crate::packs::FsPackProvider;
+    use crate::worker::{StubWorkerBackend, WorkerHost};
+    use std::sync::Arc;
+
+    #[test]
+    fn maps_changed_paths_to_tenants() {
+        let root = Path::new("/packs");
+        assert_eq!(
+            tenant_for_path(root, Path::new("/packs/acme/layout/gui/manifest.json")),
+            Some("acme".to_string())
+        );
+        assert_eq!(tenant_for_path(root, Path::new("/packs")), None);
+        assert_eq!(tenant_for_path(root, Path::new("/elsewhere/acme")), None);
+    }
+
+    #[test]
+    fn skin_css_changes_allow_css_only_reload() {
+        let mut cfg: TenantGuiConfig = serde_json::from_value(serde_json::json!({
+            "tenant_did": "tenant",
+            "domain": "localhost",
+            "layout": {
+                "manifest": {
+                    "kind": "gui-layout",
+                    "layout": {
+                        "slots": [],
+                        "entrypoint_html": "index.html",
+                        "spa": false,
+                        "slot_selectors": {},
+                    },
+                },
+                "location": { "root": "/packs/tenant/layout", "assets": "/packs/tenant/layout/gui/assets" },
+            },
+            "auth": null,
+            "skin": {
+                "manifest": { "kind": "gui-skin" },
+                "location": { "root": "/packs/tenant/brand", "assets": "/packs/tenant/brand/gui/assets" },
+            },
+            "telemetry": null,
+            "features": [],
+            "secret_requirements": [],
+        }))
+        .unwrap();
+        let css: HashSet<PathBuf> = [PathBuf::from("brand/gui/assets/theme.css")].into();
+        let change = classify_change(&cfg, &css);
+        assert_eq!(change.reload, ReloadKind::Css);
+        assert_eq!(change.packs, vec!["brand".to_string()]);
+
+        let mixed: HashSet<PathBuf> = [
+            PathBuf::from("brand/gui/assets/theme.css"),
+            PathBuf::from("layout/gui/assets/index.html"),
+        ]
+        .into();
+        assert_eq!(classify_change(&cfg, &mixed).reload, ReloadKind::Full);
+
+        cfg.skin = None;
+        assert_eq!(classify_change(&cfg, &css).reload, ReloadKind::Full);
+    }
+
+    #[tokio::test]
+    async fn failed_reload_keeps_previous_tenant_config() {
+        let temp = tempfile::tempdir().unwrap();
+        let gui = temp.path().join("tenant").join("layout").join("gui");
+        std::fs::create_dir_all(&gui).unwrap();
+        let manifest = |entrypoint: &str| {
+            serde_json::json!({
+                "kind": "gui-layout",
+                "layout": {
+                    "slots": ["main"],
+                    "entrypoint_html": entrypoint,
+                    "spa": false,
+                    "slot_selectors": {},
+                },
+            })
+            .to_string()
+        };
+        std::fs::write(gui.join("manifest.json"), manifest("index.html")).unwrap();
+
+        let mut config = crate::config::test_config();
+        config.watch_packs = true;
+        let state = AppState::new(
+            config,
+            Arc::new(FsPackProvider::new(temp.path().to_path_buf())),
+            Arc::new(CompositeFragmentRenderer::file_only()),
+            Arc::new(StubSessionManager),
+            Arc::new(NullTelemetrySink),
+            Arc::new(WorkerHost::new(Arc::new(StubWorkerBackend))),
+        );
+        let loaded = state.load_tenant("tenant", "localhost").await.unwrap();
+        assert_eq!(loaded.layout.manifest.layout.entrypoint_html, "index.html");
+
+        std::fs::write(gui.join("manifest.json"), "{ not json").unwrap();
+        assert!(state.reload_tenant("tenant").await.is_err());
+        let cached = state.load_tenant("tenant", "localhost").await.unwrap();
+        assert_eq!(cached.layout.manifest.layout.entrypoint_html, "index.html");
+
+        std::fs::write(gui.join("manifest.json"), manifest("home.html")).unwrap();
+        let reloaded = state.reload_tenant("tenant").await.unwrap();
+        assert_eq!(reloaded.layout.manifest.layout.entrypoint_html, "home.html");
+        let reloaded = state.load_tenant("tenant", "localhost").await.unwrap();
+        assert_eq!(reloaded.layout.manifest.layout.entrypoint_html, "home.html");
+        assert_eq!(reloaded.domain, "localhost");
+    }
+}
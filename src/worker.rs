@@ -1,3 +1,4 @@
+use crate::otlp::TraceContext;
 use async_trait::async_trait;
 use greentic_interfaces_host::worker::{HostWorkerMessage, HostWorkerRequest, HostWorkerResponse};
 use greentic_types::{SecretRequirement, TenantCtx};
@@ -11,6 +12,16 @@ use tracing::{info, warn};
 #[async_trait]
 pub trait WorkerBackend: Send + Sync {
     async fn invoke(&self, req: HostWorkerRequest) -> anyhow::Result<HostWorkerResponse>;
+
+    /// Invoke within the `worker_invoke` span described by `trace`. Backends that can carry trace
+    /// context to the worker override this.
+    async fn invoke_traced(
+        &self,
+        req: HostWorkerRequest,
+        _trace: &TraceContext,
+    ) -> anyhow::Result<HostWorkerResponse> {
+        self.invoke(req).await
+    }
 }
 
 /// Structured error bubbled up when the upstream runtime reports missing secrets.
@@ -117,6 +128,24 @@ async fn parse_missing_secrets_response(resp: reqwest::Response) -> Option<Missi
 #[async_trait]
 impl WorkerBackend for HttpWorkerBackend {
     async fn invoke(&self, req: HostWorkerRequest) -> anyhow::Result<HostWorkerResponse> {
+        self.send(req, None).await
+    }
+
+    async fn invoke_traced(
+        &self,
+        req: HostWorkerRequest,
+        trace: &TraceContext,
+    ) -> anyhow::Result<HostWorkerResponse> {
+        self.send(req, Some(trace)).await
+    }
+}
+
+impl HttpWorkerBackend {
+    async fn send(
+        &self,
+        req: HostWorkerRequest,
+        trace: Option<&TraceContext>,
+    ) -> anyhow::Result<HostWorkerResponse> {
         let url = self.cfg.base_url.join("/workers/invoke")?;
         let mut last_err = None;
         for attempt in 0..=self.cfg.retries {
@@ -124,6 +153,9 @@ impl WorkerBackend for HttpWorkerBackend {
             if let Some(token) = &self.cfg.auth_token {
                 request = request.bearer_auth(token);
             }
+            for (name, value) in trace.map(TraceContext::headers).unwrap_or_default() {
+                request = request.header(name, value);
+            }
             match request.send().await {
                 Ok(resp) => {
                     let status = resp.status();
@@ -173,21 +205,30 @@ impl WorkerHost {
         Self { backend }
     }
 
+    /// Invoke `worker_id`, continuing the caller's trace when `trace` is given.
     pub async fn invoke_worker(
         &self,
         tenant_ctx: TenantCtx,
         worker_id: &str,
         payload: Value,
+        trace: Option<TraceContext>,
     ) -> anyhow::Result<Value> {
+        let parent_span_id = trace.as_ref().map(|t| t.parent.span_id.clone());
         let span = tracing::info_span!(
             "worker_invoke",
             worker_id = %worker_id,
             tenant = %tenant_ctx.tenant_id,
-            session = ?tenant_ctx.session_id
+            session = ?tenant_ctx.session_id,
+            trace_id = tracing::field::Empty,
+            span_id = tracing::field::Empty,
+            parent_span_id = ?parent_span_id
         );
+        let trace = TraceContext::for_span(&span, trace.as_ref());
+        span.record("trace_id", trace.parent.trace_id.as_str());
+        span.record("span_id", trace.parent.span_id.as_str());
         let _guard = span.enter();
         let req = build_host_worker_request(tenant_ctx, worker_id, payload.clone());
-        match self.backend.invoke(req).await {
+        match self.backend.invoke_traced(req, &trace).await {
             Ok(resp) => host_worker_response_to_json(resp),
             Err(err) => {
                 if err.downcast_ref::<MissingSecretsError>().is_some() {
@@ -236,7 +277,7 @@ mod tests {
             greentic_types::TenantId::new("tenant").unwrap(),
         );
         let resp = host
-            .invoke_worker(tenant_ctx, "worker.echo", serde_json::json!({"x":1}))
+            .invoke_worker(tenant_ctx, "worker.echo", serde_json::json!({"x":1}), None)
             .await
             .unwrap();
         let messages = resp
@@ -247,6 +288,85 @@ mod tests {
         assert_eq!(messages.len(), 1);
         assert_eq!(messages[0]["payload"], serde_json::json!({"x":1}));
     }
+
+    #[tokio::test]
+    async fn http_backend_forwards_trace_context_to_gateway() {
+        type Seen = Arc<std::sync::Mutex<Vec<(Option<String>, Option<String>)>>>;
+        let seen = Seen::default();
+        let gateway = axum::Router::new()
+            .route(
+                "/workers/invoke",
+                axum::routing::post(
+                    |axum::extract::State(seen): axum::extract::State<Seen>,
+                     headers: http::HeaderMap,
+                     axum::Json(req): axum::Json<HostWorkerRequest>| async move {
+                        let header = |name: &str| {
+                            headers
+                                .get(name)
+                                .and_then(|v| v.to_str().ok())
+                                .map(str::to_string)
+                        };
+                        seen.lock()
+                            .unwrap()
+                            .push((header("traceparent"), header("tracestate")));
+                        axum::Json(StubWorkerBackend.invoke(req).await.unwrap())
+                    },
+                ),
+            )
+            .with_state(seen.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, gateway).await });
+
+        let backend = HttpWorkerBackend::new(WorkerGatewayConfig {
+            base_url: format!("http://{addr}").parse().unwrap(),
+            timeout: std::time::Duration::from_secs(5),
+            auth_token: None,
+            retries: 0,
+            backoff_base: std::time::Duration::from_millis(1),
+        })
+        .unwrap();
+        let host = WorkerHost::new(Arc::new(backend));
+        let (exporter, _guard) = crate::otlp::record_spans();
+        let browser = TraceContext {
+            parent: crate::otlp::TraceParent::parse(
+                "00-0102030405060708090a0b0c0d0e0f10-0102030405060708-01",
+            )
+            .unwrap(),
+            state: Some("vendor=1".into()),
+        };
+        let tenant_ctx = greentic_types::TenantCtx::new(
+            greentic_types::EnvId::new("dev").unwrap(),
+            greentic_types::TenantId::new("tenant").unwrap(),
+        );
+        host.invoke_worker(
+            tenant_ctx,
+            "worker.echo",
+            serde_json::json!({}),
+            Some(browser.clone()),
+        )
+        .await
+        .unwrap();
+
+        let seen = seen.lock().unwrap().clone();
+        let (traceparent, tracestate) = &seen[0];
+        let forwarded = crate::otlp::TraceParent::parse(traceparent.as_deref().unwrap()).unwrap();
+        assert_eq!(forwarded.trace_id, browser.parent.trace_id);
+        assert_ne!(forwarded.span_id, browser.parent.span_id);
+        assert_eq!(tracestate.as_deref(), Some("vendor=1"));
+        // The gateway's parent is the exported worker_invoke span, itself in the browser's trace.
+        let spans = exporter.get_finished_spans().unwrap();
+        let invoke = spans
+            .iter()
+            .find(|span| span.name == "worker_invoke")
+            .unwrap();
+        assert_eq!(
+            invoke.span_context.trace_id().to_string(),
+            browser.parent.trace_id
+        );
+        assert_eq!(invoke.parent_span_id.to_string(), browser.parent.span_id);
+        assert_eq!(invoke.span_context.span_id().to_string(), forwarded.span_id);
+    }
 }
 
 /// Build a worker backend from env/config. Defaults to stub.